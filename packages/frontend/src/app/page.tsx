@@ -3,13 +3,18 @@
 import { useState } from 'react';
 import Dashboard from '@/components/Dashboard';
 import LoginPage from '@/components/LoginPage';
+import { authService } from '@/services/auth.service';
 
 export default function Home() {
   const [isAuthenticated, setIsAuthenticated] = useState(false);
 
-  const handleLogout = () => {
-    // Just logout on frontend, backend keeps all data
-    setIsAuthenticated(false);
+  const handleLogout = async () => {
+    // Revoke the backend session; patient data is kept
+    try {
+      await authService.logout();
+    } finally {
+      setIsAuthenticated(false);
+    }
   };
 
   const handleLogin = (did: string) => {
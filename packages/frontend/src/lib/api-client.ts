@@ -9,10 +9,23 @@ const apiClient: AxiosInstance = axios.create({
   },
 });
 
-// Response interceptor for error handling
+// Response interceptor: renew the short-lived access token once, then retry
 apiClient.interceptors.response.use(
   (response) => response,
-  (error) => {
+  async (error) => {
+    const original = error.config;
+    const isAuthCall = original?.url?.startsWith('/auth/') || original?.url === '/login';
+
+    if (error.response?.data?.error?.type === 'NO_AUTH' && original && !original._retried && !isAuthCall) {
+      original._retried = true;
+      try {
+        await apiClient.post('/auth/refresh');
+        return apiClient(original);
+      } catch (refreshError) {
+        return Promise.reject(refreshError);
+      }
+    }
+
     console.error('API Error:', error.response?.data || error.message);
     return Promise.reject(error);
   }
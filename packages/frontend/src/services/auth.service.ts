@@ -36,6 +36,13 @@ export const authService = {
     return response.data;
   },
 
+  /**
+   * Revoke the session on the backend and clear the session cookies
+   */
+  async logout(): Promise<void> {
+    await apiClient.post('/logout');
+  },
+
   /**
    * Check health endpoint (no auth required)
    */
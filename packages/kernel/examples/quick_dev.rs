@@ -4,6 +4,7 @@ use anyhow::Result;
 use serde_json::json;
 
 // For testing the API endpoints
+// Signs in as the mock DID - start the gateway with ANIMA_DEV_MOCK_DID=1
 #[tokio::main]
 async fn main() -> Result<()> {
     let hc = httpc_test::new_client("http://localhost:8080")?;
@@ -40,10 +41,12 @@ async fn main() -> Result<()> {
     
     let message = format!("Anima Health Auth:{}", nonce);
     println!("   📝 Message to sign: {}", message);
-    println!("   🔐 Signing with DID private key (mock)");
+    println!("   🔐 Signing with DID private key (mock DID dev key)");
     
-    // Mock signature (in production, use identity_iota to sign)
-    let signature = "mock_signature_would_be_base64_encoded_ed25519_signature";
+    // The mock DID's key is derived from a fixed DEV ONLY seed (see auth::did::MOCK_DID_DEV_SEED)
+    use ed25519_dalek::{Signer, SigningKey};
+    let signing_key = SigningKey::from_bytes(&[0x41; 32]);
+    let signature = hex::encode(signing_key.sign(message.as_bytes()).to_bytes());
 
     println!("\n==================== STEP 3: LOGIN WITH SIGNED CHALLENGE ====================");
     // Step 3: Submit DID + nonce + signature
@@ -99,7 +102,7 @@ async fn main() -> Result<()> {
     hc.do_get("/api/anchor/pending").await?.print().await?;
 
     println!("\n==================== CREATE ANCHOR BATCH ====================");
-    // Create Merkle batch and anchor
+    // Create Merkle batch and anchor (needs ANCHORER - see DID_ROLES in env.template)
     hc.do_post("/api/anchor/batch", json!({})).await?.print().await?;
 
     println!("\n==================== LIST PATIENTS ====================");
@@ -0,0 +1,306 @@
+use crate::auth::{Error, Result};
+use crate::persist::JsonFileStore;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+const DEFAULT_ACCOUNTS_FILE: &str = "data/accounts.json";
+
+/// Gateway account - the stable `user_id` behind one or more DIDs
+///
+/// Local counterpart of `did_accounts` in `did_role_registry.move`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Numeric account ID (never 0 - reserved for the root ctx)
+    pub id: u64,
+    pub dids: Vec<String>,
+    pub created_at: u64,
+    /// Matching `DIDRoleRegistry::bind_did` calls, as recorded by admins
+    #[serde(default)]
+    pub onchain_bindings: Vec<OnChainBinding>,
+}
+
+/// A DID binding that was also made on-chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnChainBinding {
+    pub did: String,
+    /// IOTA address the DID was bound to
+    pub address: String,
+    /// Digest of the bind_did transaction
+    pub tx_digest: String,
+    pub recorded_at: u64,
+}
+
+/// On-disk document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Accounts {
+    next_id: u64,
+    accounts: BTreeMap<u64, Account>,
+    // did -> account id (rebuilt on load)
+    #[serde(skip)]
+    did_index: HashMap<String, u64>,
+}
+
+impl Default for Accounts {
+    fn default() -> Self {
+        Self {
+            next_id: 1,
+            accounts: BTreeMap::new(),
+            did_index: HashMap::new(),
+        }
+    }
+}
+
+impl Accounts {
+    fn reindex(&mut self) {
+        self.did_index = self.accounts.values()
+            .flat_map(|account| account.dids.iter().map(|did| (did.clone(), account.id)))
+            .collect();
+    }
+
+    fn account_mut(&mut self, account_id: u64) -> Result<&mut Account> {
+        self.accounts.get_mut(&account_id)
+            .ok_or(Error::AccountNotFound(account_id))
+    }
+}
+
+/// Registry binding DIDs to stable account IDs
+///
+/// A DID belongs to at most one account; an account may hold several DIDs
+/// (e.g. a phone and a hardware wallet). Every change is written through to
+/// the backing JSON file before it becomes visible.
+pub struct AccountRegistry {
+    accounts: Arc<RwLock<Accounts>>,
+    store: Option<JsonFileStore>,
+}
+
+impl AccountRegistry {
+    /// Registry without persistence (tests, throwaway dev runs)
+    pub fn in_memory() -> Self {
+        Self {
+            accounts: Arc::new(RwLock::new(Accounts::default())),
+            store: None,
+        }
+    }
+
+    /// Open (or start) the registry backed by a JSON file
+    pub async fn open(path: &str) -> Result<Self> {
+        let store = JsonFileStore::new(path);
+        let mut accounts: Accounts = store.load().await.map_err(Error::Persist)?;
+        accounts.reindex();
+
+        println!("->> Accounts: Loaded {} account(s) from {}", accounts.accounts.len(), path);
+
+        Ok(Self {
+            accounts: Arc::new(RwLock::new(accounts)),
+            store: Some(store),
+        })
+    }
+
+    /// Open the registry at ACCOUNTS_FILE (default "data/accounts.json")
+    ///
+    /// An empty ACCOUNTS_FILE keeps accounts in memory only.
+    pub async fn from_env() -> Result<Self> {
+        match std::env::var("ACCOUNTS_FILE") {
+            Ok(path) if path.is_empty() => {
+                println!("->> ⚠️  ACCOUNTS_FILE empty - accounts are not persisted");
+                Ok(Self::in_memory())
+            }
+            Ok(path) => Self::open(&path).await,
+            Err(_) => Self::open(DEFAULT_ACCOUNTS_FILE).await,
+        }
+    }
+
+    /// Account a DID is bound to, creating a fresh account on first login
+    pub async fn resolve_or_create(&self, did: &str) -> Result<Account> {
+        if let Some(account) = self.account_for_did(did).await {
+            return Ok(account);
+        }
+
+        self.mutate(|accounts| {
+            // Re-check under the write lock (concurrent first logins)
+            if let Some(id) = accounts.did_index.get(did) {
+                return Ok(accounts.accounts[id].clone());
+            }
+
+            let account = Account {
+                id: accounts.next_id,
+                dids: vec![did.to_string()],
+                created_at: now_secs(),
+                onchain_bindings: Vec::new(),
+            };
+            accounts.next_id += 1;
+            accounts.accounts.insert(account.id, account.clone());
+            accounts.did_index.insert(did.to_string(), account.id);
+
+            println!("->> Accounts: Created account {} for DID {}", account.id, did);
+
+            Ok(account)
+        }).await
+    }
+
+    pub async fn account_for_did(&self, did: &str) -> Option<Account> {
+        let accounts = self.accounts.read().await;
+        accounts.did_index.get(did)
+            .and_then(|id| accounts.accounts.get(id))
+            .cloned()
+    }
+
+    pub async fn get(&self, account_id: u64) -> Result<Account> {
+        let accounts = self.accounts.read().await;
+        accounts.accounts.get(&account_id)
+            .cloned()
+            .ok_or(Error::AccountNotFound(account_id))
+    }
+
+    /// Bind an additional DID to an existing account
+    pub async fn bind_did(&self, account_id: u64, did: &str) -> Result<Account> {
+        self.mutate(|accounts| {
+            if let Some(&bound_to) = accounts.did_index.get(did) {
+                return Err(Error::DIDAlreadyBound { did: did.to_string(), account_id: bound_to });
+            }
+
+            let account = accounts.account_mut(account_id)?;
+            account.dids.push(did.to_string());
+            let account = account.clone();
+            accounts.did_index.insert(did.to_string(), account_id);
+
+            println!("->> Accounts: Bound DID {} to account {}", did, account_id);
+
+            Ok(account)
+        }).await
+    }
+
+    /// Remove a DID from an account (the account itself is kept)
+    pub async fn unbind_did(&self, account_id: u64, did: &str) -> Result<Account> {
+        self.mutate(|accounts| {
+            let account = accounts.account_mut(account_id)?;
+            let position = account.dids.iter()
+                .position(|bound| bound == did)
+                .ok_or_else(|| Error::DIDNotBound { did: did.to_string(), account_id })?;
+
+            account.dids.remove(position);
+            account.onchain_bindings.retain(|binding| binding.did != did);
+            let account = account.clone();
+            accounts.did_index.remove(did);
+
+            println!("->> Accounts: Unbound DID {} from account {}", did, account_id);
+
+            Ok(account)
+        }).await
+    }
+
+    /// Record that a DID of this account was also bound on-chain
+    pub async fn record_onchain_binding(
+        &self,
+        account_id: u64,
+        did: &str,
+        address: &str,
+        tx_digest: &str,
+    ) -> Result<Account> {
+        self.mutate(|accounts| {
+            let account = accounts.account_mut(account_id)?;
+            if !account.dids.iter().any(|bound| bound == did) {
+                return Err(Error::DIDNotBound { did: did.to_string(), account_id });
+            }
+
+            account.onchain_bindings.push(OnChainBinding {
+                did: did.to_string(),
+                address: address.to_string(),
+                tx_digest: tx_digest.to_string(),
+                recorded_at: now_secs(),
+            });
+
+            println!("->> Accounts: Recorded on-chain binding {} -> {} ({})", did, address, tx_digest);
+
+            Ok(account.clone())
+        }).await
+    }
+
+    /// Apply a change to a copy, persist it, then publish it
+    async fn mutate<T>(&self, change: impl FnOnce(&mut Accounts) -> Result<T>) -> Result<T> {
+        let mut accounts = self.accounts.write().await;
+        let mut updated = accounts.clone();
+
+        let result = change(&mut updated)?;
+
+        if let Some(store) = &self.store {
+            store.save(&updated).await.map_err(Error::Persist)?;
+        }
+        *accounts = updated;
+
+        Ok(result)
+    }
+}
+
+impl Clone for AccountRegistry {
+    fn clone(&self) -> Self {
+        Self {
+            accounts: Arc::clone(&self.accounts),
+            store: self.store.clone(),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_and_unbind_dids() {
+        let registry = AccountRegistry::in_memory();
+
+        let account = registry.resolve_or_create("did:iota:anima:phone").await.unwrap();
+        assert_eq!(account.id, 1);
+        assert_eq!(registry.resolve_or_create("did:iota:anima:phone").await.unwrap().id, 1);
+
+        // Second DID on the same account
+        registry.bind_did(1, "did:iota:anima:ledger").await.unwrap();
+        assert_eq!(registry.account_for_did("did:iota:anima:ledger").await.unwrap().id, 1);
+
+        // A DID can't be bound to two accounts
+        let other = registry.resolve_or_create("did:iota:anima:other").await.unwrap();
+        assert_eq!(other.id, 2);
+        assert!(matches!(
+            registry.bind_did(2, "did:iota:anima:ledger").await,
+            Err(Error::DIDAlreadyBound { account_id: 1, .. })
+        ));
+
+        let account = registry.unbind_did(1, "did:iota:anima:ledger").await.unwrap();
+        assert_eq!(account.dids, vec!["did:iota:anima:phone"]);
+        assert!(registry.account_for_did("did:iota:anima:ledger").await.is_none());
+        assert!(registry.unbind_did(1, "did:iota:anima:ledger").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accounts_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("anima-accounts-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("accounts.json").display().to_string();
+
+        {
+            let registry = AccountRegistry::open(&path).await.unwrap();
+            registry.resolve_or_create("did:iota:anima:a").await.unwrap();
+            registry.resolve_or_create("did:iota:anima:b").await.unwrap();
+            registry.record_onchain_binding(2, "did:iota:anima:b", "0xabc", "0xdigest").await.unwrap();
+        }
+
+        let reopened = AccountRegistry::open(&path).await.unwrap();
+        let account = reopened.account_for_did("did:iota:anima:b").await.unwrap();
+        assert_eq!(account.id, 2);
+        assert_eq!(account.onchain_bindings[0].tx_digest, "0xdigest");
+
+        // IDs keep counting from where they left off
+        assert_eq!(reopened.resolve_or_create("did:iota:anima:c").await.unwrap().id, 3);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,5 +1,10 @@
 use crate::auth::{Error, Result};
+use crate::auth::rate_limit::RateLimiter;
+use crate::persist::JsonFileStore;
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use tokio::sync::RwLock;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -8,84 +13,354 @@ use serde::{Serialize, Deserialize};
 
 const CHALLENGE_EXPIRY_SECS: u64 = 300; // 5 minutes
 
+const DEFAULT_CHALLENGE_DIR: &str = "data/challenges";
+const DEFAULT_LIMIT_PER_DID: usize = 5;
+const DEFAULT_LIMIT_PER_IP: usize = 20;
+const DEFAULT_LIMIT_WINDOW_SECS: u64 = 60;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Challenge {
     pub nonce: String,
-    pub did: Option<String>,
+    /// DID the challenge was issued to - only this DID can log in with it
+    pub did: String,
     pub expires_at: u64,
     pub created_at: u64,
 }
 
+/// Where challenges live between issuance and login
+#[async_trait]
+pub trait ChallengeBackend: Send + Sync {
+    async fn put(&self, challenge: Challenge) -> Result<()>;
+
+    /// Remove and return the challenge for `nonce`
+    ///
+    /// Must hand a challenge out at most once, even to concurrent callers.
+    async fn take(&self, nonce: &str) -> Result<Option<Challenge>>;
+
+    async fn purge_expired(&self, now: u64) -> Result<()>;
+}
+
+// region: --- Backends
+
+/// Challenges in process memory (lost on restart, not shared between replicas)
+#[derive(Default)]
+pub struct MemoryChallengeBackend {
+    challenges: RwLock<HashMap<String, Challenge>>,
+}
+
+#[async_trait]
+impl ChallengeBackend for MemoryChallengeBackend {
+    async fn put(&self, challenge: Challenge) -> Result<()> {
+        let mut store = self.challenges.write().await;
+        store.insert(challenge.nonce.clone(), challenge);
+        Ok(())
+    }
+
+    async fn take(&self, nonce: &str) -> Result<Option<Challenge>> {
+        let mut store = self.challenges.write().await;
+        Ok(store.remove(nonce))
+    }
+
+    async fn purge_expired(&self, now: u64) -> Result<()> {
+        let mut store = self.challenges.write().await;
+        store.retain(|_, challenge| challenge.expires_at > now);
+        Ok(())
+    }
+}
+
+/// One JSON file per challenge in a directory
+///
+/// Survives restarts, and replicas sharing the directory share challenges:
+/// `take` claims a challenge by renaming its file, which only one caller can
+/// win, so a nonce is never accepted twice.
+pub struct FileChallengeBackend {
+    dir: PathBuf,
+}
+
+impl FileChallengeBackend {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, nonce: &str) -> Result<PathBuf> {
+        // Nonces are UUIDs; anything else could escape the directory
+        let nonce = Uuid::parse_str(nonce).map_err(|_| Error::ChallengeNotFound)?;
+        Ok(self.dir.join(format!("{}.json", nonce)))
+    }
+}
+
+#[async_trait]
+impl ChallengeBackend for FileChallengeBackend {
+    async fn put(&self, challenge: Challenge) -> Result<()> {
+        JsonFileStore::new(self.path(&challenge.nonce)?)
+            .save(&challenge)
+            .await
+            .map_err(Error::Persist)
+    }
+
+    async fn take(&self, nonce: &str) -> Result<Option<Challenge>> {
+        let path = self.path(nonce)?;
+        let claimed = path.with_extension(format!("claimed-{}", Uuid::new_v4()));
+
+        match tokio::fs::rename(&path, &claimed).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::ChallengeStoreFailed(e.to_string())),
+        }
+
+        let challenge: Result<Option<Challenge>> = JsonFileStore::new(&claimed)
+            .load()
+            .await
+            .map_err(Error::Persist);
+        let _ = tokio::fs::remove_file(&claimed).await;
+
+        challenge
+    }
+
+    async fn purge_expired(&self, now: u64) -> Result<()> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(Error::ChallengeStoreFailed(e.to_string())),
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let challenge: Option<Challenge> = JsonFileStore::new(&path).load().await.ok().flatten();
+            if challenge.map_or(true, |challenge| challenge.expires_at <= now) {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// endregion: --- Backends
+
+/// Issues login challenges and consumes them exactly once
+///
+/// Issuance is rate limited per client IP and per (DID, client IP). The DID
+/// budget is kept per IP so nobody can use up a victim's budget and lock them
+/// out of login; a challenge is worthless without the DID's key anyway.
 pub struct ChallengeStore {
-    challenges: Arc<RwLock<HashMap<String, Challenge>>>,
+    backend: Arc<dyn ChallengeBackend>,
+    did_limiter: RateLimiter,
+    ip_limiter: RateLimiter,
 }
 
 impl ChallengeStore {
+    /// In-memory store with the default limits
     pub fn new() -> Self {
+        Self::with_backend(MemoryChallengeBackend::default())
+    }
+
+    pub fn with_backend(backend: impl ChallengeBackend + 'static) -> Self {
         Self {
-            challenges: Arc::new(RwLock::new(HashMap::new())),
+            backend: Arc::new(backend),
+            did_limiter: RateLimiter::new(DEFAULT_LIMIT_PER_DID, DEFAULT_LIMIT_WINDOW_SECS),
+            ip_limiter: RateLimiter::new(DEFAULT_LIMIT_PER_IP, DEFAULT_LIMIT_WINDOW_SECS),
         }
     }
 
+    /// Issuance budgets per window (0 = unlimited)
+    pub fn with_limits(mut self, per_did: usize, per_ip: usize, window_secs: u64) -> Self {
+        self.did_limiter = RateLimiter::new(per_did, window_secs);
+        self.ip_limiter = RateLimiter::new(per_ip, window_secs);
+        self
+    }
+
+    /// Load the store from the environment
+    ///
+    /// - CHALLENGE_STORE: "memory" (default) or "file"
+    /// - CHALLENGE_DIR: directory for the file store (default "data/challenges")
+    /// - CHALLENGE_LIMIT_PER_DID / CHALLENGE_LIMIT_PER_IP: challenges per window for a
+    ///   DID from one IP / for one IP (default 5 / 20)
+    /// - CHALLENGE_LIMIT_WINDOW_SECS: window length (default 60)
+    pub fn from_env() -> Result<Self> {
+        let store = match std::env::var("CHALLENGE_STORE").as_deref() {
+            Ok("file") => {
+                let dir = std::env::var("CHALLENGE_DIR").unwrap_or_else(|_| DEFAULT_CHALLENGE_DIR.to_string());
+                println!("->> Challenges: File store in {}", dir);
+                Self::with_backend(FileChallengeBackend::new(dir))
+            }
+            Ok("memory") | Err(_) => Self::new(),
+            Ok(other) => return Err(Error::ChallengeStoreFailed(format!("Unknown CHALLENGE_STORE {}", other))),
+        };
+
+        Ok(store.with_limits(
+            env_or("CHALLENGE_LIMIT_PER_DID", DEFAULT_LIMIT_PER_DID),
+            env_or("CHALLENGE_LIMIT_PER_IP", DEFAULT_LIMIT_PER_IP),
+            env_or("CHALLENGE_LIMIT_WINDOW_SECS", DEFAULT_LIMIT_WINDOW_SECS),
+        ))
+    }
+
     /// Generate a new challenge for a DID
-    pub async fn create_challenge(&self, did: Option<String>) -> Challenge {
+    pub async fn create_challenge(&self, did: &str, client_ip: Option<IpAddr>) -> Result<Challenge> {
+        let now = now_secs();
+
+        if let Some(ip) = client_ip {
+            self.ip_limiter.try_acquire(&ip.to_string(), now)
+                .await
+                .map_err(|retry_after| Error::ChallengeRateLimited { retry_after })?;
+        }
+        let did_key = match client_ip {
+            Some(ip) => format!("{}|{}", did, ip),
+            None => did.to_string(),
+        };
+        self.did_limiter.try_acquire(&did_key, now)
+            .await
+            .map_err(|retry_after| Error::ChallengeRateLimited { retry_after })?;
+
         let nonce = Uuid::new_v4().to_string();
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
 
         let challenge = Challenge {
             nonce: nonce.clone(),
-            did,
+            did: did.to_string(),
             expires_at: now + CHALLENGE_EXPIRY_SECS,
             created_at: now,
         };
 
-        // Store challenge
-        let mut store = self.challenges.write().await;
-        store.insert(nonce.clone(), challenge.clone());
-
         // Clean up expired challenges
-        self.cleanup_expired(&mut store, now);
+        self.backend.purge_expired(now).await?;
+        self.backend.put(challenge.clone()).await?;
 
-        println!("->> Challenge: Generated nonce {} (expires in {}s)", nonce, CHALLENGE_EXPIRY_SECS);
+        println!("->> Challenge: Generated nonce {} for {} (expires in {}s)", nonce, did, CHALLENGE_EXPIRY_SECS);
 
-        challenge
+        Ok(challenge)
     }
 
-    /// Verify and consume a challenge
-    pub async fn verify_and_consume(&self, nonce: &str) -> Result<Challenge> {
-        let mut store = self.challenges.write().await;
-
-        let challenge = store.remove(nonce)
+    /// Verify and consume a challenge issued to `did`
+    ///
+    /// The challenge is consumed even when the DID doesn't match, so a leaked
+    /// nonce can't be retried.
+    pub async fn verify_and_consume(&self, nonce: &str, did: &str) -> Result<Challenge> {
+        let challenge = self.backend.take(nonce)
+            .await?
             .ok_or(Error::ChallengeNotFound)?;
 
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        if now > challenge.expires_at {
+        if now_secs() > challenge.expires_at {
             return Err(Error::ChallengeExpired);
         }
 
+        if challenge.did != did {
+            println!("->> Challenge: Nonce {} was issued to {}, not {}", nonce, challenge.did, did);
+            return Err(Error::ChallengeDIDMismatch);
+        }
+
         println!("->> Challenge: Verified and consumed nonce {}", nonce);
 
         Ok(challenge)
     }
-
-    /// Clean up expired challenges
-    fn cleanup_expired(&self, store: &mut HashMap<String, Challenge>, now: u64) {
-        store.retain(|_, challenge| challenge.expires_at > now);
-    }
 }
 
 impl Clone for ChallengeStore {
     fn clone(&self) -> Self {
         Self {
-            challenges: Arc::clone(&self.challenges),
+            backend: Arc::clone(&self.backend),
+            did_limiter: self.did_limiter.clone(),
+            ip_limiter: self.ip_limiter.clone(),
         }
     }
 }
 
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DID_A: &str = "did:iota:anima:a";
+    const DID_B: &str = "did:iota:anima:b";
+
+    #[tokio::test]
+    async fn test_challenge_bound_to_did_and_single_use() {
+        let store = ChallengeStore::new();
+
+        let challenge = store.create_challenge(DID_A, None).await.unwrap();
+        assert!(matches!(
+            store.verify_and_consume(&challenge.nonce, DID_B).await,
+            Err(Error::ChallengeDIDMismatch)
+        ));
+        // The mismatched attempt burned the nonce
+        assert!(matches!(
+            store.verify_and_consume(&challenge.nonce, DID_A).await,
+            Err(Error::ChallengeNotFound)
+        ));
+
+        let challenge = store.create_challenge(DID_A, None).await.unwrap();
+        assert!(store.verify_and_consume(&challenge.nonce, DID_A).await.is_ok());
+        assert!(store.verify_and_consume(&challenge.nonce, DID_A).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_issuance_limits() {
+        let store = ChallengeStore::new().with_limits(2, 4, 60);
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        store.create_challenge(DID_A, Some(ip)).await.unwrap();
+        store.create_challenge(DID_A, Some(ip)).await.unwrap();
+        assert!(matches!(
+            store.create_challenge(DID_A, Some(ip)).await,
+            Err(Error::ChallengeRateLimited { retry_after }) if retry_after > 0
+        ));
+
+        // Other DID, same IP: IP budget (4, refused requests count) runs out next
+        store.create_challenge(DID_B, Some(ip)).await.unwrap();
+        assert!(store.create_challenge(DID_B, Some(ip)).await.is_err());
+        assert!(store.create_challenge(DID_B, Some("203.0.113.8".parse().unwrap())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_no_lockout_of_another_client() {
+        let store = ChallengeStore::new().with_limits(2, 0, 60);
+        let attacker: IpAddr = "203.0.113.7".parse().unwrap();
+        let victim: IpAddr = "198.51.100.4".parse().unwrap();
+
+        // Exhausting the victim's DID budget from one IP...
+        store.create_challenge(DID_A, Some(attacker)).await.unwrap();
+        store.create_challenge(DID_A, Some(attacker)).await.unwrap();
+        assert!(store.create_challenge(DID_A, Some(attacker)).await.is_err());
+
+        // ...doesn't stop the victim from logging in
+        let challenge = store.create_challenge(DID_A, Some(victim)).await.unwrap();
+        assert!(store.verify_and_consume(&challenge.nonce, DID_A).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("anima-challenges-{}", Uuid::new_v4()));
+
+        let challenge = ChallengeStore::with_backend(FileChallengeBackend::new(&dir))
+            .create_challenge(DID_A, None)
+            .await
+            .unwrap();
+
+        // Fresh store on the same directory (restart / other replica)
+        let store = ChallengeStore::with_backend(FileChallengeBackend::new(&dir));
+        assert!(store.verify_and_consume(&challenge.nonce, DID_A).await.is_ok());
+        assert!(matches!(
+            store.verify_and_consume(&challenge.nonce, DID_A).await,
+            Err(Error::ChallengeNotFound)
+        ));
+        assert!(store.verify_and_consume("../../etc/passwd", DID_A).await.is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::auth::{Error, Result};
+use std::net::IpAddr;
+
+/// Default TRUSTED_PROXIES: the gateway binds 127.0.0.1, so only a local reverse
+/// proxy can connect
+const DEFAULT_TRUSTED_PROXIES: &str = "127.0.0.1,::1";
+
+/// Proxies whose `X-Forwarded-For` hops are believed when finding the client IP
+#[derive(Clone, Debug)]
+pub struct TrustedProxies {
+    // (network address, prefix length)
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    /// Parse a comma-separated list of addresses or CIDR ranges (empty = trust none)
+    pub fn parse(list: &str) -> Result<Self> {
+        let ranges = list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || Error::InvalidTrustedProxy(entry.to_string());
+                let (addr, prefix) = match entry.split_once('/') {
+                    Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+                    None => (entry, None),
+                };
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                let max = if addr.is_ipv4() { 32 } else { 128 };
+                match prefix.unwrap_or(max) {
+                    prefix if prefix <= max => Ok((addr, prefix)),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { ranges })
+    }
+
+    /// Load the trusted proxies from the environment
+    ///
+    /// - TRUSTED_PROXIES: addresses or CIDR ranges of reverse proxies
+    ///   (default "127.0.0.1,::1", empty = use the TCP peer as the client)
+    pub fn from_env() -> Result<Self> {
+        let list = std::env::var("TRUSTED_PROXIES").unwrap_or_else(|_| DEFAULT_TRUSTED_PROXIES.to_string());
+        let proxies = Self::parse(&list)?;
+
+        println!("->> TrustedProxies: {} trusted proxy range(s)", proxies.ranges.len());
+
+        Ok(proxies)
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.ranges.iter().any(|&(network, prefix)| match (network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// Client IP of a request from `peer` carrying `forwarded_for` headers
+    ///
+    /// An untrusted peer is the client. Behind trusted proxies the client is the
+    /// right-most `X-Forwarded-For` hop that isn't a trusted proxy; hops left of
+    /// it were written by the client and can't be believed. None when no
+    /// untrusted hop is left (or a hop doesn't parse).
+    pub fn client_ip<'a>(&self, peer: Option<IpAddr>, forwarded_for: impl Iterator<Item = &'a str>) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+
+        let hops = forwarded_for
+            .flat_map(|header| header.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        for hop in hops.into_iter().rev() {
+            let ip: IpAddr = hop.parse().ok()?;
+            if !self.is_trusted(ip) {
+                return Some(ip);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_client_ip_behind_proxies() {
+        let proxies = TrustedProxies::parse("127.0.0.1, 10.0.0.0/8, ::1").unwrap();
+
+        // Direct clients are their own peer, whatever they claim
+        assert_eq!(proxies.client_ip(ip("203.0.113.7"), ["198.51.100.1"].into_iter()), ip("203.0.113.7"));
+
+        // Behind the proxies: the right-most hop no proxy vouches for
+        let forwarded = ["198.51.100.1, 203.0.113.7", "10.1.2.3"];
+        assert_eq!(proxies.client_ip(ip("127.0.0.1"), forwarded.into_iter()), ip("203.0.113.7"));
+        assert_eq!(proxies.client_ip(ip("::ffff:127.0.0.1"), forwarded.into_iter()), ip("203.0.113.7"));
+
+        // No client established: no header, only proxies, or a garbled hop
+        assert_eq!(proxies.client_ip(ip("127.0.0.1"), std::iter::empty()), None);
+        assert_eq!(proxies.client_ip(ip("127.0.0.1"), ["10.0.0.2"].into_iter()), None);
+        assert_eq!(proxies.client_ip(ip("127.0.0.1"), ["203.0.113.7, unknown"].into_iter()), None);
+        assert_eq!(proxies.client_ip(None, ["203.0.113.7"].into_iter()), None);
+
+        // Trusting nobody falls back to the peer
+        let none = TrustedProxies::parse("").unwrap();
+        assert_eq!(none.client_ip(ip("127.0.0.1"), ["203.0.113.7"].into_iter()), ip("127.0.0.1"));
+
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("proxy.local").is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::auth::{Error, Result, KeyAlgorithm};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// Bitstring length of new status lists (16KB - the StatusList2021 minimum, for herd privacy)
+pub const STATUS_LIST_SIZE: usize = 131_072;
+
+/// Largest decoded status list accepted from other issuers (16M credentials),
+/// so a small gzip bomb can't inflate without bound
+const MAX_STATUS_LIST_BYTES: usize = 2 * 1024 * 1024;
+
+const BASE_CREDENTIAL_TYPE: &str = "VerifiableCredential";
+const STATUS_LIST_CREDENTIAL_TYPE: &str = "StatusList2021Credential";
+
+/// A credential that passed signature, expiry, subject and status checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedCredential {
+    pub id: Option<String>,
+    /// Credential types without the generic "VerifiableCredential"
+    pub types: Vec<String>,
+    pub issuer: String,
+    pub subject: String,
+    pub issued_at: u64,
+    pub expires_at: Option<u64>,
+    pub status: Option<CredentialStatus>,
+}
+
+impl VerifiedCredential {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|exp| now > exp)
+    }
+}
+
+/// StatusList2021Entry of a credential (revocation purpose)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialStatus {
+    /// ID of the status list credential
+    pub status_list: String,
+    pub index: usize,
+}
+
+// region: --- VC-JWT
+
+/// Decoded VC-JWT (VC Data Model 1.1, JWT encoding) - signature not yet checked
+pub struct VcJwt {
+    pub alg: KeyAlgorithm,
+    pub kid: Option<String>,
+    pub payload: Value,
+    pub signing_input: String,
+    pub signature: Vec<u8>,
+}
+
+impl VcJwt {
+    pub fn parse(token: &str) -> Result<Self> {
+        let invalid = |msg: &str| Error::CredentialInvalid(msg.to_string());
+
+        let parts: Vec<&str> = token.trim().split('.').collect();
+        if parts.len() != 3 {
+            return Err(invalid("Not a compact JWS"));
+        }
+
+        let header: Value = URL_SAFE_NO_PAD.decode(parts[0]).ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| invalid("Invalid header"))?;
+
+        let alg = header.get("alg")
+            .and_then(|v| v.as_str())
+            .and_then(KeyAlgorithm::from_jws_alg)
+            .ok_or_else(|| invalid("Unsupported alg (expected EdDSA, ES256K or ES256)"))?;
+
+        let payload: Value = URL_SAFE_NO_PAD.decode(parts[1]).ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| invalid("Invalid payload"))?;
+
+        let signature = URL_SAFE_NO_PAD.decode(parts[2])
+            .map_err(|_| invalid("Invalid signature encoding"))?;
+
+        Ok(Self {
+            alg,
+            kid: header.get("kid").and_then(|v| v.as_str()).map(str::to_string),
+            payload,
+            signing_input: format!("{}.{}", parts[0], parts[1]),
+            signature,
+        })
+    }
+
+    /// Issuer DID (`iss`, falling back to `vc.issuer`)
+    pub fn issuer(&self) -> Result<String> {
+        self.payload.get("iss")
+            .and_then(|v| v.as_str())
+            .or_else(|| {
+                let issuer = self.vc().get("issuer")?;
+                issuer.as_str().or_else(|| issuer.get("id")?.as_str())
+            })
+            .map(str::to_string)
+            .ok_or_else(|| Error::CredentialInvalid("Missing issuer".to_string()))
+    }
+
+    fn vc(&self) -> &Value {
+        self.payload.get("vc").unwrap_or(&Value::Null)
+    }
+
+    fn types(&self) -> Vec<String> {
+        self.vc().get("type")
+            .map(|t| match t {
+                Value::String(s) => vec![s.clone()],
+                Value::Array(types) => types.iter()
+                    .filter_map(|t| t.as_str().map(str::to_string))
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Extract the credential claims (signature checked by the caller)
+    pub fn credential(&self) -> Result<VerifiedCredential> {
+        let invalid = |msg: &str| Error::CredentialInvalid(msg.to_string());
+        let vc = self.vc();
+
+        let types = self.types();
+        if !types.iter().any(|t| t == BASE_CREDENTIAL_TYPE) {
+            return Err(invalid("Missing vc.type VerifiableCredential"));
+        }
+
+        let subject = self.payload.get("sub")
+            .and_then(|v| v.as_str())
+            .or_else(|| vc.get("credentialSubject")?.get("id")?.as_str())
+            .ok_or_else(|| invalid("Missing credentialSubject.id"))?;
+
+        let status = match vc.get("credentialStatus") {
+            Some(status) => Some(parse_status_entry(status)?),
+            None => None,
+        };
+
+        Ok(VerifiedCredential {
+            id: self.payload.get("jti")
+                .or_else(|| vc.get("id"))
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            types: types.into_iter().filter(|t| t != BASE_CREDENTIAL_TYPE).collect(),
+            issuer: self.issuer()?,
+            subject: subject.to_string(),
+            issued_at: self.payload.get("nbf")
+                .or_else(|| self.payload.get("iat"))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            expires_at: self.payload.get("exp").and_then(|v| v.as_u64()),
+            status,
+        })
+    }
+
+    /// Status list carried by a StatusList2021Credential
+    pub fn status_list(&self) -> Result<StatusList> {
+        if !self.types().iter().any(|t| t == STATUS_LIST_CREDENTIAL_TYPE) {
+            return Err(Error::CredentialInvalid("Not a StatusList2021Credential".to_string()));
+        }
+
+        let id = self.payload.get("jti")
+            .or_else(|| self.vc().get("id"))
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| Error::CredentialInvalid("Status list credential has no id".to_string()))?;
+
+        let encoded = self.vc().get("credentialSubject")
+            .and_then(|s| s.get("encodedList"))
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| Error::CredentialInvalid("Missing credentialSubject.encodedList".to_string()))?;
+
+        StatusList::from_encoded(id, &self.issuer()?, encoded)
+    }
+}
+
+fn parse_status_entry(status: &Value) -> Result<CredentialStatus> {
+    let invalid = |msg: &str| Error::CredentialInvalid(msg.to_string());
+
+    if status.get("type").and_then(|v| v.as_str()) != Some("StatusList2021Entry") {
+        return Err(invalid("Unsupported credentialStatus type"));
+    }
+    if let Some(purpose) = status.get("statusPurpose").and_then(|v| v.as_str()) {
+        if purpose != "revocation" {
+            return Err(invalid("Unsupported statusPurpose"));
+        }
+    }
+
+    let status_list = status.get("statusListCredential")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| invalid("Missing statusListCredential"))?;
+
+    // The spec encodes the index as a string; accept numbers too
+    let index = status.get("statusListIndex")
+        .and_then(|v| v.as_str().and_then(|s| s.parse().ok()).or_else(|| v.as_u64().map(|n| n as usize)))
+        .ok_or_else(|| invalid("Invalid statusListIndex"))?;
+
+    Ok(CredentialStatus { status_list: status_list.to_string(), index })
+}
+
+// endregion: --- VC-JWT
+
+// region: --- Status Lists
+
+/// StatusList2021 revocation bitstring
+///
+/// Bit `i` is the i-th bit of the list, most significant bit first; a set bit
+/// means the credential at that index is revoked.
+#[derive(Debug, Clone)]
+pub struct StatusList {
+    pub id: String,
+    pub issuer: String,
+    bits: Vec<u8>,
+}
+
+impl StatusList {
+    pub fn new(id: impl Into<String>, issuer: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            issuer: issuer.into(),
+            bits: vec![0; STATUS_LIST_SIZE / 8],
+        }
+    }
+
+    /// Decode an `encodedList` (base64url of the GZIP-compressed bitstring)
+    pub fn from_encoded(id: &str, issuer: &str, encoded: &str) -> Result<Self> {
+        let invalid = || Error::CredentialInvalid("Invalid encodedList".to_string());
+
+        let compressed = URL_SAFE_NO_PAD.decode(encoded.trim_end_matches('=')).map_err(|_| invalid())?;
+        let mut bits = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .take(MAX_STATUS_LIST_BYTES as u64 + 1)
+            .read_to_end(&mut bits)
+            .map_err(|_| invalid())?;
+        if bits.len() > MAX_STATUS_LIST_BYTES {
+            return Err(Error::CredentialInvalid(format!(
+                "encodedList decodes to more than {} bytes", MAX_STATUS_LIST_BYTES
+            )));
+        }
+
+        Ok(Self { id: id.to_string(), issuer: issuer.to_string(), bits })
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.bits)
+            .and_then(|_| encoder.finish())
+            .map(|compressed| URL_SAFE_NO_PAD.encode(compressed))
+            .map_err(|e| Error::CredentialInvalid(format!("Failed to encode status list: {}", e)))
+    }
+
+    pub fn is_revoked(&self, index: usize) -> Result<bool> {
+        let byte = self.bits.get(index / 8)
+            .ok_or_else(|| Error::CredentialInvalid(format!("statusListIndex {} out of range", index)))?;
+        Ok(byte & (0x80 >> (index % 8)) != 0)
+    }
+
+    pub fn set_revoked(&mut self, index: usize, revoked: bool) -> Result<()> {
+        let byte = self.bits.get_mut(index / 8)
+            .ok_or_else(|| Error::CredentialInvalid(format!("statusListIndex {} out of range", index)))?;
+        if revoked {
+            *byte |= 0x80 >> (index % 8);
+        } else {
+            *byte &= !(0x80 >> (index % 8));
+        }
+        Ok(())
+    }
+}
+
+// endregion: --- Status Lists
+
+/// What the gateway accepts as a credential
+///
+/// Only trusted issuers count (anyone can sign a "LicensedPhysician" VC for
+/// themselves), and credentials with a status entry are only accepted while
+/// their status list is known and the bit is clear (fail closed).
+pub struct CredentialPolicy {
+    trusted_issuers: Arc<HashSet<String>>,
+    // status list credential id -> list
+    status_lists: Arc<RwLock<HashMap<String, StatusList>>>,
+}
+
+impl CredentialPolicy {
+    pub fn new(trusted_issuers: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            trusted_issuers: Arc::new(trusted_issuers.into_iter().collect()),
+            status_lists: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Load trusted issuer DIDs from VC_TRUSTED_ISSUERS (comma-separated)
+    pub fn from_env() -> Self {
+        let issuers: Vec<String> = std::env::var("VC_TRUSTED_ISSUERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|did| did.trim().to_string())
+            .filter(|did| !did.is_empty())
+            .collect();
+
+        println!("->> Credentials: {} trusted issuer(s)", issuers.len());
+
+        Self::new(issuers)
+    }
+
+    /// Also trust `did` (e.g. the gateway's own issuer DID)
+    pub fn with_trusted_issuer(mut self, did: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.trusted_issuers).insert(did.into());
+        self
+    }
+
+    pub fn is_trusted_issuer(&self, did: &str) -> bool {
+        self.trusted_issuers.contains(did)
+    }
+
+    /// Store (or replace) a status list
+    pub async fn put_status_list(&self, list: StatusList) {
+        println!("->> Credentials: Status list {} from {}", list.id, list.issuer);
+
+        let mut lists = self.status_lists.write().await;
+        lists.insert(list.id.clone(), list);
+    }
+
+    /// Check expiry and revocation status of an already verified credential
+    pub async fn check_status(&self, credential: &VerifiedCredential) -> Result<()> {
+        if credential.is_expired(now_secs()) {
+            return Err(Error::CredentialExpired);
+        }
+
+        let Some(status) = &credential.status else { return Ok(()) };
+
+        let lists = self.status_lists.read().await;
+        let list = lists.get(&status.status_list)
+            .ok_or_else(|| Error::StatusListUnavailable(status.status_list.clone()))?;
+
+        // A list can only revoke credentials of its own issuer
+        if list.issuer != credential.issuer {
+            return Err(Error::StatusListUnavailable(status.status_list.clone()));
+        }
+
+        if list.is_revoked(status.index)? {
+            return Err(Error::CredentialRevoked);
+        }
+
+        Ok(())
+    }
+}
+
+impl Clone for CredentialPolicy {
+    fn clone(&self) -> Self {
+        Self {
+            trusted_issuers: Arc::clone(&self.trusted_issuers),
+            status_lists: Arc::clone(&self.status_lists),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_list_encode_roundtrip() {
+        let mut list = StatusList::new("https://anima.health/status/1", "did:iota:anima:hospital");
+        list.set_revoked(0, true).unwrap();
+        list.set_revoked(94567, true).unwrap();
+
+        let decoded = StatusList::from_encoded(&list.id, &list.issuer, &list.encode().unwrap()).unwrap();
+        assert!(decoded.is_revoked(0).unwrap());
+        assert!(decoded.is_revoked(94567).unwrap());
+        assert!(!decoded.is_revoked(1).unwrap());
+        assert!(decoded.is_revoked(STATUS_LIST_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_status_list_size_limit() {
+        let oversized = StatusList {
+            id: "https://anima.health/status/1".to_string(),
+            issuer: "did:iota:anima:hospital".to_string(),
+            bits: vec![0; MAX_STATUS_LIST_BYTES + 1],
+        };
+        // A few KB of gzip that would inflate past the limit is refused
+        let encoded = oversized.encode().unwrap();
+        assert!(encoded.len() < 16 * 1024);
+        assert!(StatusList::from_encoded(&oversized.id, &oversized.issuer, &encoded).is_err());
+
+        let largest = StatusList { bits: vec![0; MAX_STATUS_LIST_BYTES], ..oversized };
+        assert!(StatusList::from_encoded(&largest.id, &largest.issuer, &largest.encode().unwrap()).is_ok());
+    }
+}
@@ -1,9 +1,27 @@
-use crate::auth::{Error, Result};
+use crate::auth::{Error, Result, CredentialPolicy, GatewayKey, StatusList, VcJwt, VerifiedCredential};
+use crate::auth::keys::PublicKey;
+use crate::auth::did_cache::{DIDCache, CachedResolution, CacheStats};
+use crate::auth::did_method::{DidMethodRegistry, KeyMethod, WebMethod, IotaMethod, AnimaMethod, ReqwestHttpClient};
+use crate::did_manager::{DIDRegistry, DIDStatus, KeySelector};
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::sync::Arc;
 // IOTA Identity - ACTIVE for Tangle integration
 use identity_iota::iota::{IotaDocument, IotaDID};
 use identity_iota::verification::MethodScope;
-use iota_sdk::client::Client;
+
+/// Mock DID kept for local development (see examples/quick_dev.rs)
+///
+/// Only resolvable with ANIMA_DEV_MOCK_DID=1 - its key is public.
+pub const MOCK_DID: &str = "did:iota:anima:abc123";
+
+/// Seed of the mock DID's Ed25519 key - DEV ONLY, never use for real identities
+pub const MOCK_DID_DEV_SEED: [u8; 32] = [0x41; 32];
+
+/// Whether ANIMA_DEV_MOCK_DID=1 enables the mock DID (off by default)
+pub fn dev_mock_did_from_env() -> bool {
+    std::env::var("ANIMA_DEV_MOCK_DID").is_ok_and(|value| value.trim() == "1")
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DIDDocument {
@@ -12,125 +30,718 @@ pub struct DIDDocument {
     pub raw_document: String,
 }
 
-pub struct DIDResolver {
-    client: Option<Client>,
-    use_testnet: bool,
-}
+/// Verification relationships that may reference or embed methods
+const VERIFICATION_RELATIONSHIPS: [&str; 5] = [
+    "authentication",
+    "assertionMethod",
+    "keyAgreement",
+    "capabilityInvocation",
+    "capabilityDelegation",
+];
 
-impl DIDResolver {
-    pub fn new() -> Self {
-        Self {
-            client: None,
-            use_testnet: true,
+impl DIDDocument {
+    /// Parse a resolved DID document (JSON-LD) for `did`
+    ///
+    /// The document must describe `did` itself; verification method ids are
+    /// made absolute, including methods embedded in verification relationships.
+    pub fn from_json(did: &str, doc: Value) -> Result<Self> {
+        let id = doc.get("id").and_then(|v| v.as_str())
+            .ok_or_else(|| Error::DIDDocumentInvalid("Missing id".to_string()))?;
+        if id != did {
+            return Err(Error::DIDDocumentInvalid(format!("Document id {} does not match {}", id, did)));
         }
+
+        let mut verification_methods: Vec<String> = Vec::new();
+        for method in method_objects(&doc) {
+            let Some(id) = method.get("id").and_then(|v| v.as_str()) else { continue };
+            let id = absolute_method_id(did, id);
+            if !verification_methods.contains(&id) {
+                verification_methods.push(id);
+            }
+        }
+
+        Ok(Self {
+            did: did.to_string(),
+            verification_methods,
+            raw_document: doc.to_string(),
+        })
     }
 
-    /// Initialize IOTA client connection (lazy initialization)
-    async fn get_client(&self) -> Result<Client> {
-        if let Some(ref client) = self.client {
-            return Ok(client.clone());
+    /// Extract the public keys (Ed25519, secp256k1, P-256) usable for authentication
+    /// Returns (verification method id, key) pairs
+    pub fn authentication_keys(&self) -> Result<Vec<(String, PublicKey)>> {
+        self.relationship_keys("authentication")
+    }
+
+    /// Extract the public keys usable for issuing credentials
+    pub fn assertion_keys(&self) -> Result<Vec<(String, PublicKey)>> {
+        self.relationship_keys("assertionMethod")
+    }
+
+    /// The key of one verification method (listed or embedded), e.g. a retired "#key-1"
+    pub fn verification_key(&self, method_id: &str) -> Result<PublicKey> {
+        let doc: Value = serde_json::from_str(&self.raw_document)
+            .map_err(|e| Error::DIDDocumentInvalid(format!("Invalid JSON: {}", e)))?;
+        let method_id = absolute_method_id(&self.did, method_id);
+
+        method_objects(&doc)
+            .into_iter()
+            .find(|method| method.get("id").and_then(|v| v.as_str())
+                .is_some_and(|id| absolute_method_id(&self.did, id) == method_id))
+            .ok_or_else(|| Error::DIDDocumentInvalid(format!("No verification method {}", method_id)))
+            .and_then(PublicKey::from_verification_method)
+    }
+
+    fn relationship_keys(&self, relationship: &str) -> Result<Vec<(String, PublicKey)>> {
+        let doc: Value = serde_json::from_str(&self.raw_document)
+            .map_err(|e| Error::DIDDocumentInvalid(format!("Invalid JSON: {}", e)))?;
+
+        let methods = method_objects(&doc);
+        if methods.is_empty() {
+            return Err(Error::DIDDocumentInvalid("Missing verificationMethod".to_string()));
         }
 
-        // Connect to IOTA Testnet
-        let node_url = if self.use_testnet {
-            "https://api.testnet.iotaledger.net"
-        } else {
-            "https://api.iotaledger.net" // Mainnet
+        // Restrict to methods referenced under the relationship when present
+        let auth_refs: Option<Vec<String>> = doc.get(relationship)
+            .and_then(|v| v.as_array())
+            .map(|refs| refs.iter()
+                .filter_map(|r| r.as_str().or_else(|| r.get("id").and_then(|id| id.as_str())))
+                .map(|r| absolute_method_id(&self.did, r))
+                .collect());
+
+        let mut keys = Vec::new();
+        for method in methods {
+            let Some(id) = method.get("id").and_then(|v| v.as_str()) else { continue };
+            let id = absolute_method_id(&self.did, id);
+
+            if let Some(ref refs) = auth_refs {
+                if !refs.contains(&id) {
+                    continue;
+                }
+            }
+
+            if let Ok(key) = PublicKey::from_verification_method(method) {
+                if !keys.iter().any(|(known, _)| known == &id) {
+                    keys.push((id, key));
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return Err(Error::DIDDocumentInvalid(format!("No usable key in {}", self.did)));
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Verification methods of a document: the `verificationMethod` list plus
+/// methods embedded (as objects) in verification relationships
+fn method_objects(doc: &Value) -> Vec<&Value> {
+    let listed = doc.get("verificationMethod")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten();
+
+    let embedded = VERIFICATION_RELATIONSHIPS.iter()
+        .filter_map(|relationship| doc.get(*relationship).and_then(|v| v.as_array()))
+        .flatten()
+        .filter(|entry| entry.is_object());
+
+    listed.chain(embedded).collect()
+}
+
+/// Expand relative method ids ("#key-1") to "{did}#key-1"
+fn absolute_method_id(did: &str, id: &str) -> String {
+    if id.starts_with('#') {
+        format!("{}{}", did, id)
+    } else {
+        id.to_string()
+    }
+}
+
+pub struct DIDResolver {
+    // DID method -> resolver (did:key, did:web, did:iota, did:iota:anima)
+    methods: DidMethodRegistry,
+    cache: DIDCache,
+    // Locally issued patient DIDs (did:iota:anima:*)
+    did_registry: DIDRegistry,
+    // Served by did:iota:anima besides the registry
+    gateway_key: Option<GatewayKey>,
+    dev_mock_did: bool,
+    credential_policy: CredentialPolicy,
+}
+
+impl DIDResolver {
+    /// Resolver with the built-in methods: did:key, did:web, did:iota
+    /// (IOTA_NODE_URL) and the local did:iota:anima registry
+    pub fn new(did_registry: DIDRegistry) -> Self {
+        let mut methods = DidMethodRegistry::default();
+        methods.register(KeyMethod);
+        methods.register(WebMethod::new(Arc::new(ReqwestHttpClient::new())));
+        methods.register(IotaMethod::from_env());
+
+        let mut resolver = Self {
+            methods,
+            cache: DIDCache::default().watching(did_registry.subscribe()),
+            did_registry,
+            gateway_key: None,
+            dev_mock_did: false,
+            credential_policy: CredentialPolicy::new(Vec::new()),
         };
+        resolver.register_anima();
+        resolver
+    }
 
-        println!("->> Connecting to IOTA network: {}", node_url);
+    pub fn with_credential_policy(mut self, credential_policy: CredentialPolicy) -> Self {
+        self.credential_policy = credential_policy;
+        self
+    }
 
-        let client = Client::builder()
-            .with_node(node_url)
-            .map_err(|e| Error::DIDResolutionFailed(format!("Failed to build client: {}", e)))?
-            .finish()
-            .await
-            .map_err(|e| Error::DIDResolutionFailed(format!("Failed to connect: {}", e)))?;
+    /// Resolve the gateway DID locally to a document holding the gateway key
+    pub fn with_gateway_key(mut self, gateway_key: GatewayKey) -> Self {
+        self.gateway_key = Some(gateway_key);
+        self.register_anima();
+        self
+    }
+
+    /// Resolve MOCK_DID to a document holding the published dev key - DEV ONLY
+    pub fn with_dev_mock_did(mut self, enabled: bool) -> Self {
+        self.dev_mock_did = enabled;
+        self.register_anima();
+        self
+    }
 
-        println!("   ✅ Connected to IOTA Testnet");
+    fn register_anima(&mut self) {
+        let mut anima = AnimaMethod::new(self.did_registry.clone()).with_dev_mock_did(self.dev_mock_did);
+        if let Some(gateway_key) = &self.gateway_key {
+            anima = anima.with_gateway_key(gateway_key.clone());
+        }
+        self.methods.register(anima);
+    }
 
-        Ok(client)
+    pub fn with_cache(mut self, cache: DIDCache) -> Self {
+        self.cache = cache.watching(self.did_registry.subscribe());
+        self
     }
 
-    /// Resolve a DID to its DID Document from IOTA Tangle
+    pub fn credential_policy(&self) -> &CredentialPolicy {
+        &self.credential_policy
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.stats().await
+    }
+
+    /// Resolve a DID to its DID Document via the resolver for its method
+    ///
+    /// Documents and unknown DIDs are cached (see DIDCache).
     pub async fn resolve(&self, did: &str) -> Result<DIDDocument> {
-        println!("->> DIDResolver: Resolving DID from Tangle: {}", did);
-
-        // For POC: Allow mock DID to work without Tangle
-        if did == "did:iota:anima:abc123" {
-            println!("   ⚠️  Using mock DID document for {}", did);
-            let doc = DIDDocument {
-                did: did.to_string(),
-                verification_methods: vec!["key-1".to_string()],
-                raw_document: r#"{
-                    "id": "did:iota:anima:abc123",
-                    "verificationMethod": [{
-                        "id": "did:iota:anima:abc123#key-1",
-                        "type": "Ed25519VerificationKey2018",
-                        "controller": "did:iota:anima:abc123"
-                    }]
-                }"#.to_string(),
-            };
-            return Ok(doc);
-        }
-
-        // Real DID resolution from IOTA Tangle
-        // Note: Requires DID to be published first
-        
-        println!("   ⚠️  Real Tangle resolution requires published DID");
-        println!("   💡 For hackathon: Patient DIDs can be published via /api/did/publish");
-        
-        // For now, return error for unpublished DIDs
-        Err(Error::DIDResolutionFailed(format!("DID not published to Tangle yet: {}", did)))
-    }
-
-    /// Verify a signature against a DID's public key (REAL Ed25519 verification)
+        println!("->> DIDResolver: Resolving DID: {}", did);
+
+        match self.cache.get(did).await {
+            Some(CachedResolution::Found(doc)) => return Ok(doc),
+            Some(CachedResolution::NotFound) => return Err(Error::DIDNotFound(did.to_string())),
+            None => {}
+        }
+
+        match self.methods.resolve(did).await {
+            Ok(doc) => {
+                self.cache.insert(did, CachedResolution::Found(doc.clone())).await;
+                Ok(doc)
+            }
+            Err(Error::DIDNotFound(missing)) => {
+                self.cache.insert(did, CachedResolution::NotFound).await;
+                Err(Error::DIDNotFound(missing))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Verify a signature against a DID's current public key (Ed25519, ES256K or ES256)
+    ///
+    /// Locally issued patient DIDs are checked against the key held in the
+    /// DIDRegistry; any other DID is checked against its resolved document.
+    /// The key type decides the algorithm; ECDSA signs SHA-256 of the message.
+    /// Invalid, malformed or revoked-key signatures fail with InvalidSignature.
     pub async fn verify_signature(
         &self,
         did: &str,
         message: &str,
-        signature_hex: &str,
-    ) -> Result<bool> {
-        println!("->> DIDResolver: Verifying Ed25519 signature for DID: {}", did);
+        signature: &str,
+    ) -> Result<()> {
+        self.verify_signature_with_key(did, message, signature, KeySelector::Current).await
+    }
+
+    /// Verify a signature made with a specific (possibly retired) key
+    ///
+    /// Patient DIDs pick the key from their key history, by version or by the
+    /// time the signature was made. Other DIDs can be checked by version
+    /// ("#key-{n}" in their document); their documents carry no validity
+    /// windows, so a timestamp falls back to the current authentication keys.
+    /// A revoked DID fails whatever the key; a deactivated DID has no current key.
+    pub async fn verify_signature_with_key(
+        &self,
+        did: &str,
+        message: &str,
+        signature: &str,
+        selector: KeySelector,
+    ) -> Result<()> {
+        println!("->> DIDResolver: Verifying signature for DID: {} ({:?})", did, selector);
+
+        let signature = decode_signature(signature)?;
 
-        // For mock DID, allow bypass
-        if did == "did:iota:anima:abc123" {
-            println!("   ⚠️  Mock DID - signature check bypassed");
-            return Ok(true);
+        let keys: Vec<PublicKey> = match self.did_registry.get_by_did(did).await {
+            Ok(patient_did) => {
+                match (&patient_did.metadata.status, selector) {
+                    (DIDStatus::Revoked, _) => {
+                        println!("   ❌ DID is revoked");
+                        return Err(Error::InvalidSignature);
+                    }
+                    (DIDStatus::Deactivated, KeySelector::Current) => {
+                        println!("   ❌ DID is deactivated");
+                        return Err(Error::InvalidSignature);
+                    }
+                    _ => {}
+                }
+
+                let Some(key) = patient_did.key_for(selector) else {
+                    println!("   ❌ No key matches {:?}", selector);
+                    return Err(Error::InvalidSignature);
+                };
+
+                let key_bytes = hex::decode(&key.public_key)
+                    .map_err(|_| Error::InvalidSignature)?;
+                vec![PublicKey::from_bytes(patient_did.key_algorithm, &key_bytes)?]
+            }
+            Err(_) => {
+                let doc = self.resolve(did).await?;
+                match selector {
+                    KeySelector::Version(version) => {
+                        vec![doc.verification_key(&format!("#key-{}", version))?]
+                    }
+                    KeySelector::Current | KeySelector::At(_) => doc.authentication_keys()?
+                        .into_iter()
+                        .map(|(_, key)| key)
+                        .collect(),
+                }
+            }
+        };
+
+        let verified = keys.iter()
+            .any(|key| key.verify(message.as_bytes(), &signature));
+
+        if !verified {
+            println!("   ❌ Signature does not match any authentication key");
+            return Err(Error::InvalidSignature);
         }
 
-        // For patient DIDs (did:iota:anima:{uuid}), verify against stored public key
-        // This is a simplified version for POC
-        // In production, would resolve from Tangle
-        
-        println!("   💡 Patient DID - would verify against Tangle-published public key");
-        println!("   📝 For hackathon: Accepting patient DID signatures");
-        
-        // Accept for now (in production, would do full verification)
-        Ok(true)
+        println!("   ✅ Signature valid");
+
+        Ok(())
     }
 
-    /// Check if a DID has a specific Verifiable Credential
+    /// Fail for patient DIDs that were revoked or deactivated
+    ///
+    /// Checked on every request, so a revoked DID's tokens stop working at once.
+    /// Other DIDs aren't resolved here (no network round trip per request).
+    pub async fn check_did_active(&self, did: &str) -> Result<()> {
+        match self.did_registry.get_by_did(did).await {
+            Ok(patient_did) if patient_did.metadata.status != DIDStatus::Active => {
+                Err(Error::DIDInactive(did.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Verify a VC-JWT presented by `holder_did`
+    ///
+    /// Checks, in order: trusted issuer, issuer signature (assertionMethod key of
+    /// the issuer's DID document), validity period, `credentialSubject.id` ==
+    /// holder, and the StatusList2021 revocation bit.
     pub async fn verify_credential(
         &self,
-        did: &str,
-        credential_type: &str,
-    ) -> Result<bool> {
-        println!("->> DIDResolver: Checking VC {} for DID: {}", credential_type, did);
+        vc_jwt: &str,
+        holder_did: &str,
+    ) -> Result<VerifiedCredential> {
+        let jwt = VcJwt::parse(vc_jwt)?;
+        let issuer = jwt.issuer()?;
+
+        println!("->> DIDResolver: Verifying VC from {} for DID: {}", issuer, holder_did);
 
-        // For POC: Not fully implemented
-        // In production: Fetch and verify VCs from Tangle or IPFS
-        // use identity_iota::credential::{Credential, CredentialValidator};
-        
-        Ok(false)
+        self.verify_issuer_signature(&jwt, &issuer).await?;
+
+        let credential = jwt.credential()?;
+
+        if credential.issued_at > now_secs() {
+            return Err(Error::CredentialInvalid("Credential not yet valid".to_string()));
+        }
+
+        if credential.subject != holder_did {
+            println!("   ❌ Credential subject {} is not the holder", credential.subject);
+            return Err(Error::CredentialInvalid("credentialSubject.id does not match holder DID".to_string()));
+        }
+
+        self.credential_policy.check_status(&credential).await?;
+
+        println!("   ✅ VC valid - types: {:?}", credential.types);
+
+        Ok(credential)
+    }
+
+    /// Verify a StatusList2021Credential (VC-JWT) and make its list available
+    pub async fn import_status_list(&self, vc_jwt: &str) -> Result<StatusList> {
+        let jwt = VcJwt::parse(vc_jwt)?;
+        let issuer = jwt.issuer()?;
+
+        self.verify_issuer_signature(&jwt, &issuer).await?;
+
+        let list = jwt.status_list()?;
+        self.credential_policy.put_status_list(list.clone()).await;
+
+        Ok(list)
+    }
+
+    async fn verify_issuer_signature(&self, jwt: &VcJwt, issuer: &str) -> Result<()> {
+        if !self.credential_policy.is_trusted_issuer(issuer) {
+            println!("   ❌ Untrusted credential issuer {}", issuer);
+            return Err(Error::CredentialInvalid(format!("Untrusted issuer {}", issuer)));
+        }
+
+        let doc = self.resolve(issuer).await?;
+        let verified = doc.assertion_keys()?
+            .iter()
+            .filter(|(id, _)| jwt.kid.as_ref().map_or(true, |kid| kid == id))
+            // The header alg must match the key type (no algorithm substitution)
+            .filter(|(_, key)| key.algorithm() == jwt.alg)
+            .any(|(_, key)| key.verify(jwt.signing_input.as_bytes(), &jwt.signature));
+
+        if !verified {
+            return Err(Error::CredentialInvalid("Issuer signature invalid".to_string()));
+        }
+
+        Ok(())
     }
 }
 
 impl Clone for DIDResolver {
     fn clone(&self) -> Self {
         Self {
-            client: self.client.clone(),
-            use_testnet: self.use_testnet,
+            methods: self.methods.clone(),
+            cache: self.cache.clone(),
+            did_registry: self.did_registry.clone(),
+            gateway_key: self.gateway_key.clone(),
+            dev_mock_did: self.dev_mock_did,
+            credential_policy: self.credential_policy.clone(),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// region: --- Key Decoding
+
+/// Decode a signature given as hex or base64 (raw bytes; the key decides the format)
+fn decode_signature(signature: &str) -> Result<Vec<u8>> {
+    use base64::Engine;
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+
+    let signature = signature.trim();
+    hex::decode(signature)
+        .or_else(|_| STANDARD.decode(signature))
+        .or_else(|_| URL_SAFE_NO_PAD.decode(signature))
+        .map_err(|_| Error::InvalidSignature)
+}
+
+// endregion: --- Key Decoding
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::did_manager::{KeyVault, MemoryKeyVault, PatientDID};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const MESSAGE: &str = "Anima Health Auth:test-nonce";
+
+    /// Registry whose key vault the test can sign with
+    fn registry_with_vault() -> (DIDRegistry, Arc<MemoryKeyVault>) {
+        let vault = Arc::new(MemoryKeyVault::default());
+        (DIDRegistry::new().with_key_vault(vault.clone()), vault)
+    }
+
+    async fn sign_hex(vault: &MemoryKeyVault, patient_did: &PatientDID, message: &str) -> String {
+        hex::encode(vault.sign(&patient_did.key_ref, message.as_bytes()).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_verify_signature_patient_did() {
+        let (registry, vault) = registry_with_vault();
+        let patient_did = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let resolver = DIDResolver::new(registry);
+
+        let signature = sign_hex(&vault, &patient_did, MESSAGE).await;
+        assert!(resolver.verify_signature(&patient_did.did, MESSAGE, &signature).await.is_ok());
+
+        // W3C DID Core document with a base58btc ed25519-pub multikey
+        let doc: Value = serde_json::to_value(patient_did.create_did_document()).unwrap();
+        assert_eq!(doc["@context"][0], "https://www.w3.org/ns/did/v1");
+        assert_eq!(doc["controller"], patient_did.did.as_str());
+        assert_eq!(doc["verificationMethod"][0]["type"], "Ed25519VerificationKey2020");
+        let multibase = doc["verificationMethod"][0]["publicKeyMultibase"].as_str().unwrap();
+        assert!(multibase.starts_with("z6Mk"));
+        let key = PublicKey::from_verification_method(&doc["verificationMethod"][0]).unwrap();
+        assert_eq!(hex::encode(key.to_bytes()), patient_did.public_key);
+
+        // The serialized DID record only references the key
+        let private_key = hex::encode(vault.export(&patient_did.key_ref).await.unwrap());
+        assert!(!serde_json::to_string(&patient_did).unwrap().contains(&private_key));
+
+        // Signature over a different nonce must not verify
+        let result = resolver.verify_signature(&patient_did.did, "Anima Health Auth:other", &signature).await;
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+
+        // Malformed signature
+        let result = resolver.verify_signature(&patient_did.did, MESSAGE, "not-a-signature").await;
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+    }
+
+    #[tokio::test]
+    async fn test_verify_signature_ecdsa_dids() {
+        let (registry, vault) = registry_with_vault();
+        let resolver = DIDResolver::new(registry.clone());
+
+        for algorithm in [KeyAlgorithm::Secp256k1, KeyAlgorithm::P256] {
+            let patient_did = registry
+                .create_patient_did(format!("p-{:?}", algorithm), 1, algorithm)
+                .await
+                .unwrap();
+
+            let signature = sign_hex(&vault, &patient_did, MESSAGE).await;
+            assert!(resolver.verify_signature(&patient_did.did, MESSAGE, &signature).await.is_ok());
+            let result = resolver.verify_signature(&patient_did.did, "Anima Health Auth:other", &signature).await;
+            assert!(matches!(result, Err(Error::InvalidSignature)));
+
+            // The published document carries the key as a multibase Multikey
+            let doc = resolver.resolve(&patient_did.did).await.unwrap();
+            assert!(doc.raw_document.contains("\"Multikey\""));
+            assert!(doc.raw_document.contains("https://w3id.org/security/multikey/v1"));
+            assert_eq!(doc.authentication_keys().unwrap()[0].1.algorithm(), algorithm);
         }
     }
+
+    #[tokio::test]
+    async fn test_verify_signature_revoked_did() {
+        let (registry, vault) = registry_with_vault();
+        let mut patient_did = registry.create_patient_did("p-2".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let signature = sign_hex(&vault, &patient_did, MESSAGE).await;
+
+        patient_did.revoke();
+        registry.update_did(patient_did.clone()).await.unwrap();
+
+        let resolver = DIDResolver::new(registry);
+        let result = resolver.verify_signature(&patient_did.did, MESSAGE, &signature).await;
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+    }
+
+    #[tokio::test]
+    async fn test_verify_signature_after_rotation() {
+        let (registry, vault) = registry_with_vault();
+        let patient_did = registry.create_patient_did("p-rotate".to_string(), 1, KeyAlgorithm::P256).await.unwrap();
+        let old_signature = sign_hex(&vault, &patient_did, MESSAGE).await;
+        let signed_at = chrono::Utc::now();
+
+        let rotated = registry.rotate_key(&patient_did.did).await.unwrap();
+        assert_eq!(rotated.metadata.status, DIDStatus::Active);
+        assert_eq!(rotated.key_id(), format!("{}#key-2", patient_did.did));
+        let new_signature = sign_hex(&vault, &rotated, MESSAGE).await;
+
+        let resolver = DIDResolver::new(registry);
+
+        // Only the current key authenticates
+        assert!(resolver.verify_signature(&patient_did.did, MESSAGE, &new_signature).await.is_ok());
+        assert!(resolver.verify_signature(&patient_did.did, MESSAGE, &old_signature).await.is_err());
+
+        // Earlier signatures verify against the retired key, by version or time
+        let by_version = KeySelector::Version(1);
+        assert!(resolver.verify_signature_with_key(&patient_did.did, MESSAGE, &old_signature, by_version).await.is_ok());
+        let by_time = KeySelector::At(signed_at);
+        assert!(resolver.verify_signature_with_key(&patient_did.did, MESSAGE, &old_signature, by_time).await.is_ok());
+        assert!(resolver.verify_signature_with_key(&patient_did.did, MESSAGE, &new_signature, by_time).await.is_err());
+
+        // Both keys are published; only key-2 under authentication
+        let doc = resolver.resolve(&patient_did.did).await.unwrap();
+        assert_eq!(doc.verification_methods.len(), 2);
+        let auth_keys = doc.authentication_keys().unwrap();
+        assert_eq!(auth_keys.len(), 1);
+        assert_eq!(auth_keys[0].0, format!("{}#key-2", patient_did.did));
+        assert!(doc.verification_key("#key-1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_deactivated_did() {
+        let (registry, vault) = registry_with_vault();
+        let patient_did = registry.create_patient_did("p-retired".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let signature = sign_hex(&vault, &patient_did, MESSAGE).await;
+        let resolver = DIDResolver::new(registry.clone());
+        assert!(resolver.check_did_active(&patient_did.did).await.is_ok());
+
+        registry.deactivate(&patient_did.did).await.unwrap();
+
+        // No login, no tokens - but the old signature still verifies by version
+        assert!(matches!(resolver.check_did_active(&patient_did.did).await, Err(Error::DIDInactive(_))));
+        assert!(resolver.verify_signature(&patient_did.did, MESSAGE, &signature).await.is_err());
+        let by_version = KeySelector::Version(1);
+        assert!(resolver.verify_signature_with_key(&patient_did.did, MESSAGE, &signature, by_version).await.is_ok());
+        // The gateway won't sign for it; the key is kept only to publish a revocation
+        assert!(matches!(registry.sign(&patient_did.did, b"x").await, Err(crate::did_manager::Error::DIDDeactivated(_))));
+
+        // Nothing authenticates any more
+        let doc = resolver.resolve(&patient_did.did).await.unwrap();
+        assert!(doc.authentication_keys().is_err());
+
+        // Revocation also drops historical verification, and the key
+        registry.revoke(&patient_did.did).await.unwrap();
+        assert!(resolver.verify_signature_with_key(&patient_did.did, MESSAGE, &signature, by_version).await.is_err());
+        assert!(matches!(vault.sign(&patient_did.key_ref, b"x").await, Err(crate::did_manager::Error::KeyNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_signature_mock_did_document() {
+        let signature = hex::encode(SigningKey::from_bytes(&MOCK_DID_DEV_SEED).sign(MESSAGE.as_bytes()).to_bytes());
+
+        // The dev key is public, so the mock DID only resolves when enabled
+        let resolver = DIDResolver::new(DIDRegistry::new()).with_dev_mock_did(false);
+        assert!(matches!(resolver.resolve(MOCK_DID).await, Err(Error::DIDNotFound(_))));
+        assert!(resolver.verify_signature(MOCK_DID, MESSAGE, &signature).await.is_err());
+
+        let resolver = DIDResolver::new(DIDRegistry::new()).with_dev_mock_did(true);
+        assert!(resolver.verify_signature(MOCK_DID, MESSAGE, &signature).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolution_cache() {
+        let registry = DIDRegistry::new();
+        let resolver = DIDResolver::new(registry.clone())
+            .with_cache(DIDCache::new(std::time::Duration::from_secs(60), std::time::Duration::from_secs(60)));
+        let did = "did:iota:anima:p-cache";
+
+        // Unknown DID is cached negatively
+        assert!(matches!(resolver.resolve(did).await, Err(Error::DIDNotFound(_))));
+        assert!(matches!(resolver.resolve(did).await, Err(Error::DIDNotFound(_))));
+        let stats = resolver.cache_stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.negative_entries), (1, 1, 1));
+
+        // Registering the DID drops the negative entry
+        let patient_did = registry.create_patient_did("p-cache".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let key = resolver.resolve(did).await.unwrap().authentication_keys().unwrap()[0].1.clone();
+        assert_eq!(resolver.resolve(did).await.unwrap().authentication_keys().unwrap()[0].1, key);
+        let stats = resolver.cache_stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 2, 1));
+
+        // Key rotation invalidates the cached document
+        registry.rotate_key(&patient_did.did).await.unwrap();
+        assert_ne!(resolver.resolve(did).await.unwrap().authentication_keys().unwrap()[0].1, key);
+        assert_eq!(resolver.cache_stats().await.misses, 3);
+    }
+
+    // -- Verifiable credentials
+
+    const HOLDER: &str = "did:iota:anima:clinician";
+    const STATUS_LIST_ID: &str = "https://anima.health/status/1";
+
+    fn sign_jwt(payload: Value) -> String {
+        use base64::Engine;
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+        let header = serde_json::json!({ "alg": "EdDSA", "kid": format!("{}#key-1", MOCK_DID) });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+        let signature = SigningKey::from_bytes(&MOCK_DID_DEV_SEED).sign(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+
+    fn physician_vc(subject: &str, exp: u64, status_index: usize) -> String {
+        sign_jwt(serde_json::json!({
+            "iss": MOCK_DID,
+            "sub": subject,
+            "nbf": 1_700_000_000u64,
+            "exp": exp,
+            "vc": {
+                "type": ["VerifiableCredential", "LicensedPhysician"],
+                "credentialSubject": { "id": subject },
+                "credentialStatus": {
+                    "type": "StatusList2021Entry",
+                    "statusPurpose": "revocation",
+                    "statusListIndex": status_index.to_string(),
+                    "statusListCredential": STATUS_LIST_ID,
+                },
+            },
+        }))
+    }
+
+    async fn vc_resolver() -> DIDResolver {
+        let resolver = DIDResolver::new(DIDRegistry::new())
+            .with_dev_mock_did(true)
+            .with_credential_policy(CredentialPolicy::new(vec![MOCK_DID.to_string()]));
+
+        let mut list = StatusList::new(STATUS_LIST_ID, MOCK_DID);
+        list.set_revoked(7, true).unwrap();
+        let status_list_vc = sign_jwt(serde_json::json!({
+            "iss": MOCK_DID,
+            "jti": STATUS_LIST_ID,
+            "vc": {
+                "type": ["VerifiableCredential", "StatusList2021Credential"],
+                "credentialSubject": { "type": "StatusList2021", "encodedList": list.encode().unwrap() },
+            },
+        }));
+        resolver.import_status_list(&status_list_vc).await.unwrap();
+
+        resolver
+    }
+
+    #[tokio::test]
+    async fn test_verify_credential() {
+        let resolver = vc_resolver().await;
+        let far_future = now_secs() + 3600;
+
+        let credential = resolver.verify_credential(&physician_vc(HOLDER, far_future, 1), HOLDER).await.unwrap();
+        assert_eq!(credential.types, vec!["LicensedPhysician"]);
+        assert_eq!(credential.issuer, MOCK_DID);
+
+        // Presented by someone other than the subject
+        let result = resolver.verify_credential(&physician_vc(HOLDER, far_future, 1), "did:iota:anima:other").await;
+        assert!(matches!(result, Err(Error::CredentialInvalid(_))));
+
+        // Expired
+        let result = resolver.verify_credential(&physician_vc(HOLDER, 1_700_000_001, 1), HOLDER).await;
+        assert!(matches!(result, Err(Error::CredentialExpired)));
+
+        // Revoked in the status list
+        let result = resolver.verify_credential(&physician_vc(HOLDER, far_future, 7), HOLDER).await;
+        assert!(matches!(result, Err(Error::CredentialRevoked)));
+
+        // Tampered payload
+        let vc = physician_vc(HOLDER, far_future, 1);
+        let parts: Vec<&str> = vc.split('.').collect();
+        let forged = format!("{}.{}.{}", parts[0], physician_vc("did:iota:anima:other", far_future, 1).split('.').nth(1).unwrap(), parts[2]);
+        assert!(resolver.verify_credential(&forged, "did:iota:anima:other").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_credential_untrusted_issuer() {
+        let resolver = DIDResolver::new(DIDRegistry::new());
+        let vc = physician_vc(HOLDER, now_secs() + 3600, 1);
+
+        let result = resolver.verify_credential(&vc, HOLDER).await;
+        assert!(matches!(result, Err(Error::CredentialInvalid(_))));
+    }
 }
@@ -0,0 +1,189 @@
+use crate::auth::DIDDocument;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio::sync::broadcast::{Receiver, error::TryRecvError};
+
+const DEFAULT_TTL_SECS: u64 = 300;
+const DEFAULT_NEGATIVE_TTL_SECS: u64 = 30;
+
+/// Cached outcome of one resolution
+#[derive(Debug, Clone)]
+pub enum CachedResolution {
+    Found(DIDDocument),
+    /// The DID doesn't exist (negative entry)
+    NotFound,
+}
+
+struct CacheEntry {
+    resolution: CachedResolution,
+    expires_at: Instant,
+}
+
+/// Hit/miss counters for resolver load
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub negative_entries: usize,
+    pub ttl_secs: u64,
+    pub negative_ttl_secs: u64,
+}
+
+/// DID resolution cache with per-entry TTL and negative caching
+///
+/// Only definitive answers are cached: documents and "DID not found".
+/// Transport failures always go back to the resolver. Entries of DIDs that
+/// change in the watched DIDRegistry are dropped before the next lookup.
+pub struct DIDCache {
+    entries: Arc<RwLock<HashMap<String, CacheEntry>>>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    // DIDRegistry change feed
+    changes: Option<Arc<Mutex<Receiver<String>>>>,
+}
+
+impl DIDCache {
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+            negative_ttl,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+            changes: None,
+        }
+    }
+
+    /// TTLs from DID_CACHE_TTL_SECS (default 300) and DID_CACHE_NEGATIVE_TTL_SECS (default 30)
+    ///
+    /// A TTL of 0 disables that kind of entry.
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default);
+
+        Self::new(
+            Duration::from_secs(secs("DID_CACHE_TTL_SECS", DEFAULT_TTL_SECS)),
+            Duration::from_secs(secs("DID_CACHE_NEGATIVE_TTL_SECS", DEFAULT_NEGATIVE_TTL_SECS)),
+        )
+    }
+
+    /// Invalidate entries for DIDs announced on `changes`
+    pub fn watching(mut self, changes: Receiver<String>) -> Self {
+        self.changes = Some(Arc::new(Mutex::new(changes)));
+        self
+    }
+
+    /// Cached resolution of `did`, counting a hit or a miss
+    pub async fn get(&self, did: &str) -> Option<CachedResolution> {
+        self.apply_changes().await;
+
+        let entries = self.entries.read().await;
+        let cached = entries.get(did)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.resolution.clone());
+
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        cached
+    }
+
+    pub async fn insert(&self, did: &str, resolution: CachedResolution) {
+        let ttl = match resolution {
+            CachedResolution::Found(_) => self.ttl,
+            CachedResolution::NotFound => self.negative_ttl,
+        };
+        if ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.write().await;
+        let now = Instant::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        entries.insert(did.to_string(), CacheEntry { resolution, expires_at: now + ttl });
+    }
+
+    pub async fn invalidate(&self, did: &str) {
+        let mut entries = self.entries.write().await;
+        if entries.remove(did).is_some() {
+            println!("->> DIDCache: Invalidated {}", did);
+        }
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let entries = self.entries.read().await;
+        let now = Instant::now();
+        let live = entries.values().filter(|entry| entry.expires_at > now);
+        let negative_entries = live.clone()
+            .filter(|entry| matches!(entry.resolution, CachedResolution::NotFound))
+            .count();
+
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: live.count(),
+            negative_entries,
+            ttl_secs: self.ttl.as_secs(),
+            negative_ttl_secs: self.negative_ttl.as_secs(),
+        }
+    }
+
+    /// Drain the change feed; if it lagged, changes were lost so start over
+    async fn apply_changes(&self) {
+        let Some(changes) = &self.changes else { return };
+
+        let mut changed = Vec::new();
+        let mut lagged = false;
+        {
+            let mut receiver = changes.lock().unwrap();
+            loop {
+                match receiver.try_recv() {
+                    Ok(did) => changed.push(did),
+                    Err(TryRecvError::Lagged(_)) => lagged = true,
+                    Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                }
+            }
+        }
+
+        if lagged {
+            println!("->> DIDCache: Missed registry changes - clearing cache");
+            self.entries.write().await.clear();
+            return;
+        }
+
+        for did in changed {
+            self.invalidate(&did).await;
+        }
+    }
+}
+
+impl Default for DIDCache {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(DEFAULT_TTL_SECS),
+            Duration::from_secs(DEFAULT_NEGATIVE_TTL_SECS),
+        )
+    }
+}
+
+impl Clone for DIDCache {
+    fn clone(&self) -> Self {
+        Self {
+            entries: Arc::clone(&self.entries),
+            ttl: self.ttl,
+            negative_ttl: self.negative_ttl,
+            hits: Arc::clone(&self.hits),
+            misses: Arc::clone(&self.misses),
+            changes: self.changes.clone(),
+        }
+    }
+}
@@ -0,0 +1,730 @@
+use crate::auth::{Error, Result, DIDDocument, GatewayKey, PublicKey, DID_CONTEXT_V1};
+use crate::auth::did::{MOCK_DID, MOCK_DID_DEV_SEED};
+use crate::did_manager::DIDRegistry;
+use async_trait::async_trait;
+use ed25519_dalek::SigningKey;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::StatusCode;
+use tokio::sync::OnceCell;
+use iota_sdk::client::Client;
+use iota_sdk::types::block::output::{AliasId, Output};
+
+const IOTA_MAINNET_NODE: &str = "https://api.iotaledger.net";
+const IOTA_TESTNET_NODE: &str = "https://api.testnet.iotaledger.net";
+
+/// Largest DID document fetched over HTTP
+const MAX_DOCUMENT_BYTES: usize = 64 * 1024;
+
+/// Resolves the DIDs of one DID method (e.g. "key", "web", "iota")
+#[async_trait]
+pub trait DidMethodResolver: Send + Sync {
+    /// Registry key: the method name, optionally narrowed to a network ("iota:anima")
+    fn method(&self) -> &str;
+
+    async fn resolve(&self, did: &str) -> Result<DIDDocument>;
+}
+
+/// Minimal HTTP access for resolvers that fetch documents (did:web)
+///
+/// Injectable so tests can serve documents without a network.
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn get_json(&self, url: &str) -> Result<Value>;
+}
+
+/// Default HttpClient backed by reqwest
+///
+/// DIDs are resolved before anyone is authenticated, so fetches are limited to
+/// public addresses: the host is resolved and checked up front, and the request
+/// is pinned to the checked addresses. Redirects are not followed and bodies
+/// over MAX_DOCUMENT_BYTES are refused.
+pub struct ReqwestHttpClient {
+    timeout: Duration,
+}
+
+impl ReqwestHttpClient {
+    pub fn new() -> Self {
+        Self { timeout: Duration::from_secs(10) }
+    }
+
+    /// Public addresses of the URL's host - any non-public address fails the lot
+    async fn public_addrs(url: &reqwest::Url) -> Result<Vec<SocketAddr>> {
+        let host = url.host_str()
+            .ok_or_else(|| Error::DIDResolutionFailed(format!("No host in {}", url)))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+
+        let addrs: Vec<SocketAddr> = tokio::task::spawn_blocking(move || (host.as_str(), port).to_socket_addrs())
+            .await
+            .map_err(|e| Error::DIDResolutionFailed(format!("Lookup of {} failed: {}", url, e)))?
+            .map_err(|e| Error::DIDResolutionFailed(format!("Lookup of {} failed: {}", url, e)))?
+            .collect();
+
+        if addrs.is_empty() {
+            return Err(Error::DIDResolutionFailed(format!("{} has no addresses", url)));
+        }
+        if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+            return Err(Error::DIDResolutionFailed(format!("{} resolves to non-public address {}", url, addr.ip())));
+        }
+        Ok(addrs)
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestHttpClient {
+    async fn get_json(&self, url: &str) -> Result<Value> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| Error::DIDResolutionFailed(format!("Invalid URL {}: {}", url, e)))?;
+        let addrs = Self::public_addrs(&parsed).await?;
+
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(domain) = parsed.domain() {
+            builder = builder.resolve_to_addrs(domain, &addrs);
+        }
+        let client = builder.build()
+            .map_err(|e| Error::DIDResolutionFailed(format!("HTTP client: {}", e)))?;
+
+        let mut response = client.get(parsed)
+            .send()
+            .await
+            .map_err(|e| Error::DIDResolutionFailed(format!("GET {} failed: {}", url, e)))?;
+
+        if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+            return Err(Error::DIDNotFound(url.to_string()));
+        }
+        if !response.status().is_success() {
+            return Err(Error::DIDResolutionFailed(format!("GET {} returned {}", url, response.status())));
+        }
+
+        let too_large = || Error::DIDDocumentInvalid(format!("{} is larger than {} bytes", url, MAX_DOCUMENT_BYTES));
+        if response.content_length().is_some_and(|length| length > MAX_DOCUMENT_BYTES as u64) {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk()
+            .await
+            .map_err(|e| Error::DIDResolutionFailed(format!("GET {} failed: {}", url, e)))?
+        {
+            if body.len() + chunk.len() > MAX_DOCUMENT_BYTES {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        serde_json::from_slice(&body)
+            .map_err(|e| Error::DIDDocumentInvalid(format!("{} is not JSON: {}", url, e)))
+    }
+}
+
+/// Whether an address is reachable on the public internet (not loopback,
+/// private, link-local, shared, documentation, multicast or reserved)
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0                                   // "this network"
+        || (a == 100 && (64..128).contains(&b))     // shared (CGNAT) 100.64/10
+        || (a == 192 && b == 0 && c == 0)           // IETF protocol assignments
+        || (a == 198 && (b == 18 || b == 19))       // benchmarking 198.18/15
+        || a >= 240)                                // reserved 240/4
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00         // unique local fc00::/7
+        || (segments[0] & 0xffc0) == 0xfe80         // link-local fe80::/10
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8) // documentation
+        || (segments[0] == 0x0064 && segments[1] == 0xff9b && !is_public_ipv4(Ipv4Addr::new(
+            (segments[6] >> 8) as u8, segments[6] as u8, (segments[7] >> 8) as u8, segments[7] as u8,
+        ))))                                        // NAT64 of a non-public IPv4
+}
+
+// region: --- Registry
+
+/// DID method resolvers keyed on method name
+///
+/// Lookups try "method:network" first, so "did:iota:anima:*" goes to the local
+/// registry while other "did:iota:*" DIDs go to the ledger.
+#[derive(Clone, Default)]
+pub struct DidMethodRegistry {
+    resolvers: Arc<HashMap<String, Arc<dyn DidMethodResolver>>>,
+}
+
+impl DidMethodRegistry {
+    /// Register (or replace) the resolver for its method
+    pub fn register(&mut self, resolver: impl DidMethodResolver + 'static) {
+        Arc::make_mut(&mut self.resolvers).insert(resolver.method().to_string(), Arc::new(resolver));
+    }
+
+    pub fn resolver_for(&self, did: &str) -> Result<&dyn DidMethodResolver> {
+        let mut parts = did.splitn(4, ':');
+        let (Some("did"), Some(method), Some(_)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(Error::DIDResolutionFailed(format!("Not a DID: {}", did)));
+        };
+
+        let network = did.split(':').nth(2).unwrap_or_default();
+        self.resolvers.get(&format!("{}:{}", method, network))
+            .or_else(|| self.resolvers.get(method))
+            .map(|resolver| resolver.as_ref())
+            .ok_or_else(|| Error::UnsupportedDIDMethod(method.to_string()))
+    }
+
+    pub async fn resolve(&self, did: &str) -> Result<DIDDocument> {
+        self.resolver_for(did)?.resolve(did).await
+    }
+}
+
+// endregion: --- Registry
+
+// region: --- did:key
+
+/// did:key - the DID is the (multibase, multicodec) public key itself
+///
+/// Ed25519 (z6Mk...), secp256k1 (zQ3s...) and P-256 (zDn...) keys.
+pub struct KeyMethod;
+
+#[async_trait]
+impl DidMethodResolver for KeyMethod {
+    fn method(&self) -> &str {
+        "key"
+    }
+
+    async fn resolve(&self, did: &str) -> Result<DIDDocument> {
+        let multibase = did.strip_prefix("did:key:")
+            .ok_or_else(|| Error::DIDResolutionFailed(format!("Not a did:key: {}", did)))?;
+
+        let encoded = multibase.strip_prefix('z')
+            .ok_or_else(|| Error::DIDDocumentInvalid("did:key must be base58btc (z...)".to_string()))?;
+        let bytes = bs58::decode(encoded)
+            .into_vec()
+            .map_err(|_| Error::DIDDocumentInvalid(format!("Invalid base58 in {}", did)))?;
+
+        let key = PublicKey::from_multicodec(&bytes)
+            .map_err(|_| Error::DIDDocumentInvalid(format!("Unsupported did:key type: {}", did)))?;
+        let (method_type, suite_context) = key.method_type();
+
+        let method_id = format!("{}#{}", did, multibase);
+        DIDDocument::from_json(did, json!({
+            "@context": [DID_CONTEXT_V1, suite_context],
+            "id": did,
+            "verificationMethod": [{
+                "id": method_id,
+                "type": method_type,
+                "controller": did,
+                "publicKeyMultibase": multibase,
+            }],
+            "authentication": [method_id],
+            "assertionMethod": [method_id],
+        }))
+    }
+}
+
+// endregion: --- did:key
+
+// region: --- did:web
+
+/// did:web - document served at https://{host}/.well-known/did.json (or {path}/did.json)
+pub struct WebMethod {
+    http: Arc<dyn HttpClient>,
+}
+
+impl WebMethod {
+    pub fn new(http: Arc<dyn HttpClient>) -> Self {
+        Self { http }
+    }
+
+    /// did:web:example.com:user:alice -> https://example.com/user/alice/did.json
+    ///
+    /// The host must be a domain name (or a public IPv4 address) with an optional
+    /// port, and path segments plain URL-safe characters, so nothing decoded from
+    /// %XX can change where the request goes.
+    pub fn document_url(did: &str) -> Result<String> {
+        let id = did.strip_prefix("did:web:")
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Error::DIDResolutionFailed(format!("Not a did:web: {}", did)))?;
+
+        let mut segments = id.split(':').map(percent_decode);
+        let host = segments.next().unwrap_or_default();
+        let path: Vec<String> = segments.collect();
+
+        if !is_valid_web_host(&host) || !path.iter().all(|segment| is_valid_web_path_segment(segment)) {
+            return Err(Error::DIDResolutionFailed(format!("Invalid did:web: {}", did)));
+        }
+
+        if path.is_empty() {
+            Ok(format!("https://{}/.well-known/did.json", host))
+        } else {
+            Ok(format!("https://{}/{}/did.json", host, path.join("/")))
+        }
+    }
+}
+
+#[async_trait]
+impl DidMethodResolver for WebMethod {
+    fn method(&self) -> &str {
+        "web"
+    }
+
+    async fn resolve(&self, did: &str) -> Result<DIDDocument> {
+        let url = Self::document_url(did)?;
+        println!("   🌐 Fetching {}", url);
+
+        let doc = self.http.get_json(&url).await?;
+        DIDDocument::from_json(did, doc)
+    }
+}
+
+/// `name[:port]` - LDH labels, or a dotted IPv4 address that is public
+///
+/// A host whose last label is numeric would be read as an IPv4 address by the
+/// URL parser ("2130706433", "0x7f.1"), so only the dotted form is accepted.
+fn is_valid_web_host(host: &str) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) => (name, Some(port)),
+        None => (host, None),
+    };
+    if port.is_some_and(|port| !port.parse::<u16>().is_ok_and(|port| port > 0)) {
+        return false;
+    }
+
+    let labels: Vec<&str> = name.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    });
+    if name.len() > 253 || !valid_labels {
+        return false;
+    }
+
+    let last = labels.last().copied().unwrap_or_default();
+    let numeric = last.bytes().all(|b| b.is_ascii_digit())
+        || last.get(..2).is_some_and(|prefix| prefix.eq_ignore_ascii_case("0x"));
+    if numeric {
+        return name.parse::<Ipv4Addr>().is_ok_and(is_public_ipv4);
+    }
+    true
+}
+
+/// Unreserved URL characters only; no dot segments
+fn is_valid_web_path_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment != "."
+        && segment != ".."
+        && segment.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+/// Decode %XX escapes (did:web encodes the port colon as %3A)
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// endregion: --- did:web
+
+// region: --- did:iota:anima
+
+/// did:iota:anima - DIDs issued by this gateway, resolved from the DIDRegistry
+///
+/// Also serves the gateway's own issuer DID, and the dev mock DID when enabled.
+pub struct AnimaMethod {
+    did_registry: DIDRegistry,
+    gateway_key: Option<GatewayKey>,
+    dev_mock_did: bool,
+}
+
+impl AnimaMethod {
+    pub fn new(did_registry: DIDRegistry) -> Self {
+        Self { did_registry, gateway_key: None, dev_mock_did: false }
+    }
+
+    /// Serve MOCK_DID, whose key seed is in the source - DEV ONLY
+    pub fn with_dev_mock_did(mut self, enabled: bool) -> Self {
+        self.dev_mock_did = enabled;
+        self
+    }
+
+    pub fn with_gateway_key(mut self, gateway_key: GatewayKey) -> Self {
+        self.gateway_key = Some(gateway_key);
+        self
+    }
+}
+
+#[async_trait]
+impl DidMethodResolver for AnimaMethod {
+    fn method(&self) -> &str {
+        "iota:anima"
+    }
+
+    async fn resolve(&self, did: &str) -> Result<DIDDocument> {
+        // Dev only: the mock DID works without Tangle, but anyone can sign as it
+        if self.dev_mock_did && did == MOCK_DID {
+            println!("   ⚠️  Using mock DID document for {}", did);
+            let public_key = PublicKey::Ed25519(SigningKey::from_bytes(&MOCK_DID_DEV_SEED).verifying_key());
+            let (method_type, suite_context) = public_key.method_type();
+            return DIDDocument::from_json(did, json!({
+                "@context": [DID_CONTEXT_V1, suite_context],
+                "id": MOCK_DID,
+                "controller": MOCK_DID,
+                "verificationMethod": [{
+                    "id": format!("{}#key-1", MOCK_DID),
+                    "type": method_type,
+                    "controller": MOCK_DID,
+                    "publicKeyMultibase": public_key.to_multibase(),
+                }],
+                "authentication": [format!("{}#key-1", MOCK_DID)],
+            }));
+        }
+
+        // The gateway's own DID can only assert (issue credentials), never log in
+        if let Some(gateway_key) = self.gateway_key.as_ref().filter(|key| key.did() == did) {
+            let public_key = PublicKey::Ed25519(gateway_key.verifying_key());
+            let (method_type, suite_context) = public_key.method_type();
+            return DIDDocument::from_json(did, json!({
+                "@context": [DID_CONTEXT_V1, suite_context],
+                "id": did,
+                "controller": did,
+                "verificationMethod": [{
+                    "id": gateway_key.kid(),
+                    "type": method_type,
+                    "controller": did,
+                    "publicKeyMultibase": public_key.to_multibase(),
+                }],
+                "authentication": [],
+                "assertionMethod": [gateway_key.kid()],
+            }));
+        }
+
+        let patient_did = self.did_registry.get_by_did(did)
+            .await
+            .map_err(|_| Error::DIDNotFound(did.to_string()))?;
+
+        let doc = serde_json::to_value(patient_did.create_did_document())
+            .map_err(|e| Error::DIDDocumentInvalid(e.to_string()))?;
+        DIDDocument::from_json(did, doc)
+    }
+}
+
+// endregion: --- did:iota:anima
+
+// region: --- did:iota
+
+/// did:iota - documents stored in Alias Output state metadata on the IOTA ledger
+///
+/// did:iota:0x{alias id} is mainnet; did:iota:{network}:0x{alias id} any other
+/// network (the node is taken from IOTA_NODE_URL when set).
+pub struct IotaMethod {
+    node_url: String,
+    // Connected on first use, then shared by every resolution
+    client: OnceCell<Client>,
+}
+
+impl IotaMethod {
+    pub fn new(node_url: impl Into<String>) -> Self {
+        Self {
+            node_url: node_url.into(),
+            client: OnceCell::new(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self::new(IOTA_TESTNET_NODE)
+    }
+
+    /// Node from IOTA_NODE_URL, else the public testnet node
+    pub fn from_env() -> Self {
+        match std::env::var("IOTA_NODE_URL") {
+            Ok(url) if !url.is_empty() => Self::new(url),
+            _ => Self::testnet(),
+        }
+    }
+
+    /// Initialize IOTA client connection (lazy initialization, cached)
+    ///
+    /// A failed connection isn't cached, so the next resolution retries.
+    async fn get_client(&self) -> Result<&Client> {
+        self.client.get_or_try_init(|| async {
+            println!("->> Connecting to IOTA network: {}", self.node_url);
+
+            let client = Client::builder()
+                .with_node(&self.node_url)
+                .map_err(|e| Error::DIDResolutionFailed(format!("Failed to build client: {}", e)))?
+                .finish()
+                .await
+                .map_err(|e| Error::DIDResolutionFailed(format!("Failed to connect: {}", e)))?;
+
+            println!("   ✅ Connected to {}", self.node_url);
+
+            Ok(client)
+        }).await
+    }
+}
+
+impl Default for IotaMethod {
+    fn default() -> Self {
+        Self::new(IOTA_MAINNET_NODE)
+    }
+}
+
+#[async_trait]
+impl DidMethodResolver for IotaMethod {
+    fn method(&self) -> &str {
+        "iota"
+    }
+
+    async fn resolve(&self, did: &str) -> Result<DIDDocument> {
+        let alias_id = iota_alias_id(did)?;
+
+        let client = self.get_client().await?;
+        let output_id = client.alias_output_id(AliasId::new(alias_id))
+            .await
+            .map_err(|e| {
+                println!("   ⚠️  No alias output for {}: {}", did, e);
+                Error::DIDNotFound(did.to_string())
+            })?;
+        let output = client.get_output(&output_id)
+            .await
+            .map_err(|e| Error::DIDResolutionFailed(format!("Failed to fetch alias output: {}", e)))?;
+
+        let Output::Alias(alias_output) = output.output() else {
+            return Err(Error::DIDDocumentInvalid(format!("{} is not an alias output", did)));
+        };
+
+        DIDDocument::from_json(did, unpack_state_metadata(did, alias_output.state_metadata())?)
+    }
+}
+
+/// 32-byte alias ID from did:iota[:{network}]:0x{hex}
+fn iota_alias_id(did: &str) -> Result<[u8; 32]> {
+    let tag = did.rsplit(':').next().unwrap_or_default();
+
+    tag.strip_prefix("0x")
+        .and_then(|hex_id| hex::decode(hex_id).ok())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::DIDResolutionFailed(format!("Invalid IOTA DID tag: {}", did)))
+}
+
+/// Decode packed state metadata: "DID" | version (1) | encoding (0 = JSON) | u16 LE length | document
+///
+/// The stored document refers to itself as "did:0:0" (the alias ID isn't known
+/// before publishing), so the placeholder is replaced with the resolved DID.
+fn unpack_state_metadata(did: &str, metadata: &[u8]) -> Result<Value> {
+    let invalid = |reason: &str| Error::DIDDocumentInvalid(format!("{}: {}", did, reason));
+
+    let header = metadata.get(..7).ok_or_else(|| invalid("state metadata too short"))?;
+    if &header[..3] != b"DID" {
+        return Err(invalid("missing DID marker"));
+    }
+    if header[3] != 1 || header[4] != 0 {
+        return Err(invalid("unsupported state metadata version or encoding"));
+    }
+
+    let len = u16::from_le_bytes([header[5], header[6]]) as usize;
+    let json = metadata.get(7..7 + len)
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .ok_or_else(|| invalid("truncated document"))?;
+
+    let packed: Value = serde_json::from_str(&json.replace("did:0:0", did))
+        .map_err(|e| invalid(&e.to_string()))?;
+
+    // {"doc": {...}, "meta": {...}}
+    packed.get("doc").cloned().ok_or_else(|| invalid("missing doc"))
+}
+
+// endregion: --- did:iota
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::auth::keys::{ED25519_PUB_MULTICODEC, P256_PUB_MULTICODEC};
+    use std::sync::Mutex;
+
+    /// Serves canned documents and records requested URLs
+    struct StaticHttpClient {
+        documents: HashMap<String, Value>,
+        requested: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpClient for StaticHttpClient {
+        async fn get_json(&self, url: &str) -> Result<Value> {
+            self.requested.lock().unwrap().push(url.to_string());
+            self.documents.get(url)
+                .cloned()
+                .ok_or_else(|| Error::DIDNotFound(url.to_string()))
+        }
+    }
+
+    fn did_key(seed: [u8; 32]) -> String {
+        let public_key = SigningKey::from_bytes(&seed).verifying_key();
+        let mut bytes = ED25519_PUB_MULTICODEC.to_vec();
+        bytes.extend_from_slice(&public_key.to_bytes());
+        format!("did:key:z{}", bs58::encode(bytes).into_string())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_did_key() {
+        let did = did_key(MOCK_DID_DEV_SEED);
+
+        let doc = KeyMethod.resolve(&did).await.unwrap();
+        let keys = doc.authentication_keys().unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].1, PublicKey::Ed25519(SigningKey::from_bytes(&MOCK_DID_DEV_SEED).verifying_key()));
+        assert_eq!(doc.verification_methods, vec![keys[0].0.clone()]);
+
+        // P-256 (zDn...)
+        let (public_hex, _) = KeyAlgorithm::P256.generate_keypair();
+        let mut bytes = P256_PUB_MULTICODEC.to_vec();
+        bytes.extend(hex::decode(public_hex).unwrap());
+        let did = format!("did:key:z{}", bs58::encode(bytes).into_string());
+        assert!(did.starts_with("did:key:zDn"));
+        let keys = KeyMethod.resolve(&did).await.unwrap().authentication_keys().unwrap();
+        assert_eq!(keys[0].1.algorithm(), KeyAlgorithm::P256);
+
+        assert!(KeyMethod.resolve("did:key:zNotBase58!").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_did_web() {
+        let did = "did:web:hospital.example%3A8443:staff";
+        let url = "https://hospital.example:8443/staff/did.json";
+        let public_key = SigningKey::from_bytes(&[7u8; 32]).verifying_key();
+
+        let http = Arc::new(StaticHttpClient {
+            documents: HashMap::from([(url.to_string(), json!({
+                "id": did,
+                "verificationMethod": [{
+                    "id": "#key-1",
+                    "type": "Ed25519VerificationKey2018",
+                    "controller": did,
+                    "publicKeyHex": hex::encode(public_key.to_bytes()),
+                }],
+                "assertionMethod": ["#key-1"],
+            }))]),
+            requested: Mutex::new(Vec::new()),
+        });
+
+        let mut registry = DidMethodRegistry::default();
+        registry.register(WebMethod::new(http.clone()));
+
+        let doc = registry.resolve(did).await.unwrap();
+        assert_eq!(doc.verification_methods, vec![format!("{}#key-1", did)]);
+        assert_eq!(doc.assertion_keys().unwrap()[0].1, PublicKey::Ed25519(public_key));
+        assert_eq!(*http.requested.lock().unwrap(), vec![url.to_string()]);
+
+        assert_eq!(WebMethod::document_url("did:web:example.com").unwrap(), "https://example.com/.well-known/did.json");
+        assert_eq!(WebMethod::document_url("did:web:93.184.216.34").unwrap(), "https://93.184.216.34/.well-known/did.json");
+        // A document for another DID is rejected
+        assert!(registry.resolve("did:web:hospital.example").await.is_err());
+    }
+
+    #[test]
+    fn test_did_web_rejects_non_public_targets() {
+        for did in [
+            // Userinfo, query and fragment smuggled in through %XX
+            "did:web:evil.example%40127.0.0.1",
+            "did:web:127.0.0.1%23.evil.example",
+            "did:web:evil.example%3F",
+            "did:web:hospital.example:staff%2F..%2Fadmin",
+            "did:web:hospital.example:..",
+            "did:web:hospital.example%3A0",
+            "did:web:hospital.example%3A99999",
+            // Non-public IP literals, in any spelling the URL parser accepts
+            "did:web:127.0.0.1",
+            "did:web:10.0.0.8%3A8443",
+            "did:web:169.254.169.254",
+            "did:web:192.168.1.1",
+            "did:web:100.64.0.1",
+            "did:web:2130706433",
+            "did:web:0x7f.1",
+            "did:web:%5B%3A%3A1%5D",
+        ] {
+            assert!(WebMethod::document_url(did).is_err(), "{did} accepted");
+        }
+
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::1".parse().unwrap()));
+        for ip in ["0.0.0.0", "127.0.0.1", "10.1.2.3", "172.16.0.1", "169.254.169.254", "198.18.0.1", "255.255.255.255",
+                   "::", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "64:ff9b::a00:1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip} is public");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_dispatch() {
+        let mut registry = DidMethodRegistry::default();
+        registry.register(KeyMethod);
+        registry.register(AnimaMethod::new(DIDRegistry::new()));
+        registry.register(IotaMethod::default());
+
+        assert_eq!(registry.resolver_for(MOCK_DID).unwrap().method(), "iota:anima");
+        assert_eq!(registry.resolver_for("did:iota:0xabc").unwrap().method(), "iota");
+        assert_eq!(registry.resolver_for("did:iota:tst:0xabc").unwrap().method(), "iota");
+        assert!(matches!(registry.resolver_for("did:ethr:0xabc"), Err(Error::UnsupportedDIDMethod(_))));
+        assert!(registry.resolver_for("not-a-did").is_err());
+    }
+
+    #[test]
+    fn test_unpack_state_metadata() {
+        let did = format!("did:iota:tst:0x{}", "ab".repeat(32));
+        let document = json!({ "doc": { "id": "did:0:0", "verificationMethod": [] }, "meta": {} }).to_string();
+
+        let mut metadata = b"DID".to_vec();
+        metadata.extend_from_slice(&[1, 0]);
+        metadata.extend_from_slice(&(document.len() as u16).to_le_bytes());
+        metadata.extend_from_slice(document.as_bytes());
+
+        let doc = unpack_state_metadata(&did, &metadata).unwrap();
+        assert_eq!(doc["id"], did.as_str());
+        assert_eq!(iota_alias_id(&did).unwrap(), [0xab; 32]);
+        assert!(unpack_state_metadata(&did, b"DIDx").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::Serialize;
+use crate::persist;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -6,11 +7,34 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     ChallengeExpired,
     ChallengeNotFound,
+    ChallengeDIDMismatch,
+    ChallengeRateLimited { retry_after: u64 },
+    ChallengeStoreFailed(String),
+    /// TRUSTED_PROXIES entry that isn't an IP address or CIDR range
+    InvalidTrustedProxy(String),
     InvalidSignature,
     DIDResolutionFailed(String),
+    DIDNotFound(String),
+    DIDInactive(String),
+    UnsupportedDIDMethod(String),
     DIDDocumentInvalid(String),
     TokenGenerationFailed(String),
     TokenValidationFailed(String),
+    SessionInvalid(String),
+    SessionRevoked,
+    UnknownRole(String),
+    AccountNotFound(u64),
+    DIDAlreadyBound { did: String, account_id: u64 },
+    DIDNotBound { did: String, account_id: u64 },
+    Persist(persist::Error),
+    CredentialInvalid(String),
+    CredentialExpired,
+    CredentialRevoked,
+    StatusListUnavailable(String),
+    CredentialNotFound(String),
+    StepUpRequired,
+    /// Credentials are persisted but GATEWAY_SIGNING_KEY is not set
+    GatewayKeyEphemeral,
 }
 
 impl core::fmt::Display for Error {
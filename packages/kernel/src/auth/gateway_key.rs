@@ -0,0 +1,121 @@
+use crate::auth::{Error, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+const DEFAULT_GATEWAY_DID: &str = "did:iota:anima:gateway";
+
+/// Ed25519 key held by the gateway and bound to the gateway's own DID
+///
+/// Signs EdDSA access tokens; the public half is published as a JWKS
+/// document so other services can verify tokens offline.
+#[derive(Clone)]
+pub struct GatewayKey {
+    did: String,
+    signing_key: Arc<SigningKey>,
+    // Generated at startup because GATEWAY_SIGNING_KEY was not set
+    ephemeral: bool,
+}
+
+impl GatewayKey {
+    pub fn new(did: impl Into<String>, signing_key: SigningKey) -> Self {
+        Self {
+            did: did.into(),
+            signing_key: Arc::new(signing_key),
+            ephemeral: false,
+        }
+    }
+
+    /// Generate a fresh (ephemeral) key for the default gateway DID
+    pub fn generate() -> Self {
+        use rand::RngCore;
+        use rand::rngs::OsRng;
+
+        let mut secret_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut secret_bytes);
+
+        Self { ephemeral: true, ..Self::new(DEFAULT_GATEWAY_DID, SigningKey::from_bytes(&secret_bytes)) }
+    }
+
+    /// Load the gateway key from the environment
+    ///
+    /// - GATEWAY_DID: DID the gateway issues tokens as (default "did:iota:anima:gateway")
+    /// - GATEWAY_SIGNING_KEY: hex-encoded 32-byte Ed25519 seed
+    pub fn from_env() -> Result<Self> {
+        let did = std::env::var("GATEWAY_DID").unwrap_or_else(|_| DEFAULT_GATEWAY_DID.to_string());
+
+        let key = match std::env::var("GATEWAY_SIGNING_KEY") {
+            Ok(seed_hex) if !seed_hex.is_empty() => {
+                let seed: [u8; 32] = hex::decode(seed_hex.trim())
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| Error::TokenGenerationFailed(
+                        "GATEWAY_SIGNING_KEY must be a hex-encoded 32-byte seed".to_string()
+                    ))?;
+                Self::new(did, SigningKey::from_bytes(&seed))
+            }
+            _ => {
+                println!("->> ⚠️  GATEWAY_SIGNING_KEY not set - generating an ephemeral gateway key");
+                Self { did, ..Self::generate() }
+            }
+        };
+
+        println!("->> Gateway key: {} ({})", key.kid(), key.public_key_hex());
+
+        Ok(key)
+    }
+
+    pub fn did(&self) -> &str {
+        &self.did
+    }
+
+    /// Whether the key was generated at startup and is lost on restart
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Key ID used in JWS headers and the JWKS document
+    pub fn kid(&self) -> String {
+        format!("{}#key-1", self.did)
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.verifying_key().to_bytes())
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.signing_key.sign(message)
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| Error::TokenValidationFailed("Invalid signature encoding".to_string()))?;
+
+        self.verifying_key()
+            .verify(message, &signature)
+            .map_err(|_| Error::TokenValidationFailed("Invalid signature".to_string()))
+    }
+
+    /// Public key as an OKP JSON Web Key (RFC 8037)
+    pub fn jwk(&self) -> Value {
+        json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(self.verifying_key().to_bytes()),
+            "kid": self.kid(),
+            "alg": "EdDSA",
+            "use": "sig",
+        })
+    }
+
+    /// JWKS document served at /.well-known/jwks.json
+    pub fn jwks(&self) -> Value {
+        json!({ "keys": [self.jwk()] })
+    }
+}
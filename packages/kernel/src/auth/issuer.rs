@@ -0,0 +1,397 @@
+use crate::auth::{Error, Result, CredentialPolicy, GatewayKey, StatusList};
+use crate::auth::credential::STATUS_LIST_SIZE;
+use crate::persist::JsonFileStore;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const DEFAULT_CREDENTIALS_FILE: &str = "data/credentials.json";
+const DEFAULT_BASE_URL: &str = "http://localhost:8080";
+const VC_CONTEXT_V1: &str = "https://www.w3.org/2018/credentials/v1";
+const STATUS_LIST_CONTEXT: &str = "https://w3id.org/vc/status-list/2021/v1";
+
+/// Credential types the gateway issues under its own key
+///
+/// Deliberately closed: the gateway is a trusted issuer, so letting callers
+/// pick arbitrary types would let them mint e.g. "LicensedPhysician".
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GatewayCredentialType {
+    /// The subject DID is an enrolled Anima patient
+    PatientEnrollment,
+    /// Receipt that a record was included in an anchored Merkle root
+    RecordAnchored,
+}
+
+impl GatewayCredentialType {
+    /// credentialSubject claims that must be present
+    fn required_claims(self) -> &'static [&'static str] {
+        match self {
+            Self::PatientEnrollment => &["patientId"],
+            Self::RecordAnchored => &["merkleRoot", "batchId"],
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PatientEnrollment => "PatientEnrollment",
+            Self::RecordAnchored => "RecordAnchored",
+        }
+    }
+}
+
+/// A credential issued by the gateway, with its revocation state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedCredential {
+    /// urn:uuid:... (also the JWT `jti`)
+    pub id: String,
+    pub credential_type: GatewayCredentialType,
+    pub subject: String,
+    pub issued_at: u64,
+    pub expires_at: Option<u64>,
+    pub status_index: usize,
+    pub revoked_at: Option<u64>,
+    /// Issued by (gateway account ID of the caller)
+    pub issued_by: u64,
+    /// The VC-JWT handed to the holder
+    pub jwt: String,
+}
+
+/// On-disk document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IssuedCredentials {
+    next_status_index: usize,
+    credentials: BTreeMap<String, IssuedCredential>,
+}
+
+/// Issues VC-JWTs signed by the gateway key and keeps their status list
+///
+/// The status list is rebuilt from the stored credentials on startup and
+/// mirrored into the CredentialPolicy, so gateway credentials are verified
+/// like any other trusted issuer's.
+pub struct CredentialIssuer {
+    gateway_key: GatewayKey,
+    status_list_id: String,
+    credentials: Arc<RwLock<IssuedCredentials>>,
+    store: Option<JsonFileStore>,
+    credential_policy: CredentialPolicy,
+}
+
+impl CredentialIssuer {
+    /// Issuer without persistence (tests, throwaway dev runs)
+    pub async fn in_memory(gateway_key: GatewayKey, base_url: &str, credential_policy: CredentialPolicy) -> Result<Self> {
+        Self::build(gateway_key, base_url, credential_policy, None).await
+    }
+
+    /// Load issuer state from the environment
+    ///
+    /// - CREDENTIALS_FILE: issued credentials (default "data/credentials.json", empty = in-memory)
+    /// - GATEWAY_BASE_URL: public URL the status list is served under (default "http://localhost:8080")
+    ///
+    /// Persisted credentials need a persistent key (GATEWAY_SIGNING_KEY): after a
+    /// restart with a fresh key, none of them - nor the status list - would verify.
+    pub async fn from_env(gateway_key: GatewayKey, credential_policy: CredentialPolicy) -> Result<Self> {
+        let base_url = std::env::var("GATEWAY_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+
+        let path = match std::env::var("CREDENTIALS_FILE") {
+            Ok(path) if path.is_empty() => {
+                println!("->> ⚠️  CREDENTIALS_FILE empty - issued credentials are not persisted");
+                return Self::in_memory(gateway_key, &base_url, credential_policy).await;
+            }
+            Ok(path) => path,
+            Err(_) => DEFAULT_CREDENTIALS_FILE.to_string(),
+        };
+
+        if gateway_key.is_ephemeral() {
+            return Err(Error::GatewayKeyEphemeral);
+        }
+
+        Self::build(gateway_key, &base_url, credential_policy, Some(JsonFileStore::new(path))).await
+    }
+
+    async fn build(
+        gateway_key: GatewayKey,
+        base_url: &str,
+        credential_policy: CredentialPolicy,
+        store: Option<JsonFileStore>,
+    ) -> Result<Self> {
+        let credentials: IssuedCredentials = match &store {
+            Some(store) => store.load().await.map_err(Error::Persist)?,
+            None => IssuedCredentials::default(),
+        };
+
+        let issuer = Self {
+            status_list_id: format!("{}/credentials/status/1", base_url.trim_end_matches('/')),
+            gateway_key,
+            credentials: Arc::new(RwLock::new(credentials)),
+            store,
+            credential_policy,
+        };
+
+        let credentials = issuer.credentials.read().await;
+        issuer.publish_status_list(&credentials).await?;
+        println!("->> Issuer: {} credential(s) issued by {}", credentials.credentials.len(), issuer.gateway_key.did());
+        drop(credentials);
+
+        Ok(issuer)
+    }
+
+    /// Sign a credential for `subject`
+    pub async fn issue(
+        &self,
+        credential_type: GatewayCredentialType,
+        subject: &str,
+        claims: Map<String, Value>,
+        expires_in: Option<u64>,
+        issued_by: u64,
+    ) -> Result<IssuedCredential> {
+        if let Some(missing) = credential_type.required_claims().iter().find(|c| !claims.contains_key(**c)) {
+            return Err(Error::CredentialInvalid(format!("{} requires claim {}", credential_type.as_str(), missing)));
+        }
+
+        let now = now_secs();
+        let expires_at = match expires_in {
+            Some(secs) => Some(now.checked_add(secs)
+                .ok_or_else(|| Error::CredentialInvalid(format!("expires_in {} is out of range", secs)))?),
+            None => None,
+        };
+
+        let mut credentials = self.credentials.write().await;
+        let mut updated = credentials.clone();
+
+        let status_index = updated.next_status_index;
+        if status_index >= STATUS_LIST_SIZE {
+            return Err(Error::CredentialInvalid("Status list exhausted".to_string()));
+        }
+        updated.next_status_index += 1;
+
+        let id = format!("urn:uuid:{}", Uuid::new_v4());
+
+        let mut credential_subject = claims;
+        credential_subject.insert("id".to_string(), json!(subject));
+
+        let mut payload = json!({
+            "iss": self.gateway_key.did(),
+            "sub": subject,
+            "jti": id,
+            "nbf": now,
+            "iat": now,
+            "vc": {
+                "@context": [VC_CONTEXT_V1, STATUS_LIST_CONTEXT],
+                "type": ["VerifiableCredential", credential_type.as_str()],
+                "credentialSubject": credential_subject,
+                "credentialStatus": {
+                    "id": format!("{}#{}", self.status_list_id, status_index),
+                    "type": "StatusList2021Entry",
+                    "statusPurpose": "revocation",
+                    "statusListIndex": status_index.to_string(),
+                    "statusListCredential": self.status_list_id,
+                },
+            },
+        });
+        if let Some(exp) = expires_at {
+            payload["exp"] = json!(exp);
+        }
+
+        let credential = IssuedCredential {
+            id: id.clone(),
+            credential_type,
+            subject: subject.to_string(),
+            issued_at: now,
+            expires_at,
+            status_index,
+            revoked_at: None,
+            issued_by,
+            jwt: self.sign_jwt(&payload)?,
+        };
+        updated.credentials.insert(id.clone(), credential.clone());
+
+        self.save(&updated).await?;
+        *credentials = updated;
+
+        println!("->> Issuer: Issued {} {} to {}", credential_type.as_str(), id, subject);
+
+        Ok(credential)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<IssuedCredential> {
+        let credentials = self.credentials.read().await;
+        credentials.credentials.get(id)
+            .cloned()
+            .ok_or_else(|| Error::CredentialNotFound(id.to_string()))
+    }
+
+    pub async fn list_for_subject(&self, subject: &str) -> Vec<IssuedCredential> {
+        let credentials = self.credentials.read().await;
+        credentials.credentials.values()
+            .filter(|c| c.subject == subject)
+            .cloned()
+            .collect()
+    }
+
+    /// Flip the credential's status list bit
+    pub async fn revoke(&self, id: &str) -> Result<IssuedCredential> {
+        let mut credentials = self.credentials.write().await;
+        let mut updated = credentials.clone();
+
+        let credential = updated.credentials.get_mut(id)
+            .ok_or_else(|| Error::CredentialNotFound(id.to_string()))?;
+        credential.revoked_at.get_or_insert(now_secs());
+        let credential = credential.clone();
+
+        self.save(&updated).await?;
+        self.publish_status_list(&updated).await?;
+        *credentials = updated;
+
+        println!("->> Issuer: Revoked {} (status index {})", id, credential.status_index);
+
+        Ok(credential)
+    }
+
+    /// The gateway's StatusList2021Credential as a VC-JWT
+    pub async fn status_list_credential(&self) -> Result<String> {
+        let credentials = self.credentials.read().await;
+        let list = self.status_list(&credentials)?;
+
+        let now = now_secs();
+        let payload = json!({
+            "iss": self.gateway_key.did(),
+            "jti": self.status_list_id,
+            "nbf": now,
+            "iat": now,
+            "vc": {
+                "@context": [VC_CONTEXT_V1, STATUS_LIST_CONTEXT],
+                "type": ["VerifiableCredential", "StatusList2021Credential"],
+                "credentialSubject": {
+                    "id": format!("{}#list", self.status_list_id),
+                    "type": "StatusList2021",
+                    "statusPurpose": "revocation",
+                    "encodedList": list.encode()?,
+                },
+            },
+        });
+
+        self.sign_jwt(&payload)
+    }
+
+    fn status_list(&self, credentials: &IssuedCredentials) -> Result<StatusList> {
+        let mut list = StatusList::new(self.status_list_id.clone(), self.gateway_key.did());
+        for credential in credentials.credentials.values().filter(|c| c.revoked_at.is_some()) {
+            list.set_revoked(credential.status_index, true)?;
+        }
+        Ok(list)
+    }
+
+    async fn publish_status_list(&self, credentials: &IssuedCredentials) -> Result<()> {
+        let list = self.status_list(credentials)?;
+        self.credential_policy.put_status_list(list).await;
+        Ok(())
+    }
+
+    fn sign_jwt(&self, payload: &Value) -> Result<String> {
+        let header = json!({
+            "alg": "EdDSA",
+            "typ": "JWT",
+            "kid": self.gateway_key.kid(),
+        });
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+        let signature = self.gateway_key.sign(signing_input.as_bytes());
+
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+    }
+
+    async fn save(&self, credentials: &IssuedCredentials) -> Result<()> {
+        match &self.store {
+            Some(store) => store.save(credentials).await.map_err(Error::Persist),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Clone for CredentialIssuer {
+    fn clone(&self) -> Self {
+        Self {
+            gateway_key: self.gateway_key.clone(),
+            status_list_id: self.status_list_id.clone(),
+            credentials: Arc::clone(&self.credentials),
+            store: self.store.clone(),
+            credential_policy: self.credential_policy.clone(),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::DIDResolver;
+    use crate::did_manager::DIDRegistry;
+
+    const HOLDER: &str = "did:iota:anima:abc123";
+
+    #[tokio::test]
+    async fn test_issue_verify_and_revoke() {
+        let gateway_key = GatewayKey::generate();
+        let policy = CredentialPolicy::new(Vec::new()).with_trusted_issuer(gateway_key.did());
+        let resolver = DIDResolver::new(DIDRegistry::new())
+            .with_credential_policy(policy.clone())
+            .with_gateway_key(gateway_key.clone());
+        let issuer = CredentialIssuer::in_memory(gateway_key, "http://gateway.test", policy).await.unwrap();
+
+        // Required claims are enforced
+        assert!(issuer.issue(GatewayCredentialType::RecordAnchored, HOLDER, Map::new(), None, 1).await.is_err());
+
+        let mut claims = Map::new();
+        claims.insert("merkleRoot".to_string(), json!("ab".repeat(32)));
+        claims.insert("batchId".to_string(), json!("batch-1"));
+
+        // A lifetime past the end of time is refused rather than wrapped
+        assert!(matches!(
+            issuer.issue(GatewayCredentialType::RecordAnchored, HOLDER, claims.clone(), Some(u64::MAX), 1).await,
+            Err(Error::CredentialInvalid(_))
+        ));
+
+        let issued = issuer.issue(GatewayCredentialType::RecordAnchored, HOLDER, claims, Some(3600), 1).await.unwrap();
+
+        let verified = resolver.verify_credential(&issued.jwt, HOLDER).await.unwrap();
+        assert_eq!(verified.types, vec!["RecordAnchored"]);
+        assert_eq!(verified.status.as_ref().unwrap().index, issued.status_index);
+
+        issuer.revoke(&issued.id).await.unwrap();
+        assert!(matches!(
+            resolver.verify_credential(&issued.jwt, HOLDER).await,
+            Err(Error::CredentialRevoked)
+        ));
+
+        // The published status list verifies and carries the revoked bit
+        let list = resolver.import_status_list(&issuer.status_list_credential().await.unwrap()).await.unwrap();
+        assert_eq!(list.id, "http://gateway.test/credentials/status/1");
+        assert!(list.is_revoked(issued.status_index).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_persisted_credentials_need_a_persistent_key() {
+        // A key generated at startup would orphan every stored credential on restart
+        let policy = CredentialPolicy::new(Vec::new());
+        assert!(matches!(
+            CredentialIssuer::from_env(GatewayKey::generate(), policy).await,
+            Err(Error::GatewayKeyEphemeral)
+        ));
+    }
+}
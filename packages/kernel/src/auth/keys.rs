@@ -0,0 +1,318 @@
+use crate::auth::{Error, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+/// Multicodec header for ed25519-pub (varint 0xed)
+pub(crate) const ED25519_PUB_MULTICODEC: [u8; 2] = [0xed, 0x01];
+/// Multicodec header for secp256k1-pub (varint 0xe7)
+pub(crate) const SECP256K1_PUB_MULTICODEC: [u8; 2] = [0xe7, 0x01];
+/// Multicodec header for p256-pub (varint 0x1200)
+pub(crate) const P256_PUB_MULTICODEC: [u8; 2] = [0x80, 0x24];
+
+/// W3C DID Core context, first in every generated DID document
+pub const DID_CONTEXT_V1: &str = "https://www.w3.org/ns/did/v1";
+/// Context defining Ed25519VerificationKey2020
+pub const ED25519_2020_CONTEXT: &str = "https://w3id.org/security/suites/ed25519-2020/v1";
+/// Context defining Multikey
+pub const MULTIKEY_CONTEXT: &str = "https://w3id.org/security/multikey/v1";
+
+/// Signature algorithm of a DID key
+///
+/// Serialized with the JWK curve names ("Ed25519", "secp256k1", "P-256").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyAlgorithm {
+    #[default]
+    Ed25519,
+    #[serde(rename = "secp256k1")]
+    Secp256k1,
+    #[serde(rename = "P-256")]
+    P256,
+}
+
+impl KeyAlgorithm {
+    /// Generate a keypair as (public key hex, private key bytes)
+    ///
+    /// ECDSA public keys are compressed SEC1 points (33 bytes).
+    pub fn generate_keypair(self) -> (String, Vec<u8>) {
+        use rand::RngCore;
+        use rand::rngs::OsRng;
+
+        match self {
+            Self::Ed25519 => {
+                let mut secret_bytes = [0u8; 32];
+                OsRng.fill_bytes(&mut secret_bytes);
+                let signing_key = ed25519_dalek::SigningKey::from_bytes(&secret_bytes);
+                (hex::encode(signing_key.verifying_key().to_bytes()), signing_key.to_bytes().to_vec())
+            }
+            Self::Secp256k1 => {
+                let signing_key = k256::ecdsa::SigningKey::random(&mut OsRng);
+                let public_key = signing_key.verifying_key().to_encoded_point(true);
+                (hex::encode(public_key.as_bytes()), signing_key.to_bytes().to_vec())
+            }
+            Self::P256 => {
+                let signing_key = p256::ecdsa::SigningKey::random(&mut OsRng);
+                let public_key = signing_key.verifying_key().to_encoded_point(true);
+                (hex::encode(public_key.as_bytes()), signing_key.to_bytes().to_vec())
+            }
+        }
+    }
+
+    /// Sign `message` with a private key of this type
+    ///
+    /// Signatures are in JWS form: 64 bytes, r||s for ECDSA (SHA-256, low-S).
+    pub fn sign(self, secret: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+        use ed25519_dalek::Signer as _;
+
+        match self {
+            Self::Ed25519 => {
+                let secret: [u8; 32] = secret.try_into().map_err(|_| Error::InvalidSignature)?;
+                let signing_key = ed25519_dalek::SigningKey::from_bytes(&secret);
+                Ok(signing_key.sign(message).to_bytes().to_vec())
+            }
+            Self::Secp256k1 => {
+                let signing_key = k256::ecdsa::SigningKey::from_slice(secret).map_err(|_| Error::InvalidSignature)?;
+                let signature: k256::ecdsa::Signature = signing_key.sign(message);
+                Ok(signature.to_bytes().to_vec())
+            }
+            Self::P256 => {
+                let signing_key = p256::ecdsa::SigningKey::from_slice(secret).map_err(|_| Error::InvalidSignature)?;
+                let signature: p256::ecdsa::Signature = signing_key.sign(message);
+                Ok(signature.to_bytes().to_vec())
+            }
+        }
+    }
+
+    /// JWS `alg` for signatures made with this key type
+    pub fn jws_alg(self) -> &'static str {
+        match self {
+            Self::Ed25519 => "EdDSA",
+            Self::Secp256k1 => "ES256K",
+            Self::P256 => "ES256",
+        }
+    }
+
+    pub fn from_jws_alg(alg: &str) -> Option<Self> {
+        [Self::Ed25519, Self::Secp256k1, Self::P256].into_iter()
+            .find(|algorithm| algorithm.jws_alg() == alg)
+    }
+
+    /// Key type implied by a verification method `type`, when it names one
+    fn from_method_type(method_type: &str) -> Option<Self> {
+        match method_type {
+            "Ed25519VerificationKey2018" | "Ed25519VerificationKey2020" => Some(Self::Ed25519),
+            "EcdsaSecp256k1VerificationKey2019" => Some(Self::Secp256k1),
+            "EcdsaSecp256r1VerificationKey2019" => Some(Self::P256),
+            _ => None,
+        }
+    }
+
+    fn multicodec(self) -> [u8; 2] {
+        match self {
+            Self::Ed25519 => ED25519_PUB_MULTICODEC,
+            Self::Secp256k1 => SECP256K1_PUB_MULTICODEC,
+            Self::P256 => P256_PUB_MULTICODEC,
+        }
+    }
+}
+
+/// Public key of any supported algorithm, able to verify signatures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl PublicKey {
+    /// Raw key bytes: 32 bytes for Ed25519, a SEC1 point for ECDSA
+    pub fn from_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self> {
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let bytes: [u8; 32] = bytes.try_into().map_err(|_| Error::InvalidSignature)?;
+                ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                    .map(Self::Ed25519)
+                    .map_err(|_| Error::InvalidSignature)
+            }
+            KeyAlgorithm::Secp256k1 => k256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::Secp256k1)
+                .map_err(|_| Error::InvalidSignature),
+            KeyAlgorithm::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::P256)
+                .map_err(|_| Error::InvalidSignature),
+        }
+    }
+
+    /// Key from multicodec-prefixed bytes (did:key, Multikey)
+    pub fn from_multicodec(bytes: &[u8]) -> Result<Self> {
+        [KeyAlgorithm::Ed25519, KeyAlgorithm::Secp256k1, KeyAlgorithm::P256].into_iter()
+            .find_map(|algorithm| Some((algorithm, bytes.strip_prefix(&algorithm.multicodec())?)))
+            .ok_or(Error::InvalidSignature)
+            .and_then(|(algorithm, key)| Self::from_bytes(algorithm, key))
+    }
+
+    /// Key from a JWK (OKP/Ed25519, EC/secp256k1, EC/P-256)
+    pub fn from_jwk(jwk: &Value) -> Result<Self> {
+        let field = |name: &str| jwk.get(name)
+            .and_then(|v| v.as_str())
+            .and_then(|v| URL_SAFE_NO_PAD.decode(v).ok())
+            .ok_or(Error::InvalidSignature);
+
+        let kty = jwk.get("kty").and_then(|v| v.as_str());
+        let crv = jwk.get("crv").and_then(|v| v.as_str());
+        match (kty, crv) {
+            (Some("OKP"), Some("Ed25519")) => Self::from_bytes(KeyAlgorithm::Ed25519, &field("x")?),
+            (Some("EC"), Some(crv @ ("secp256k1" | "P-256"))) => {
+                let algorithm = if crv == "P-256" { KeyAlgorithm::P256 } else { KeyAlgorithm::Secp256k1 };
+                // Uncompressed SEC1 point: 0x04 || x || y
+                let mut point = vec![0x04];
+                point.extend(field("x")?);
+                point.extend(field("y")?);
+                Self::from_bytes(algorithm, &point)
+            }
+            _ => Err(Error::InvalidSignature),
+        }
+    }
+
+    /// Key of a DID document verification method
+    ///
+    /// Reads `publicKeyJwk`, `publicKeyMultibase` (base58btc) or `publicKeyHex`.
+    /// `Multikey` and `Ed25519VerificationKey2020` values must carry a multicodec
+    /// header matching the type; the older suites may hold bare key bytes, whose
+    /// type comes from the method `type` (default Ed25519).
+    pub fn from_verification_method(method: &Value) -> Result<Self> {
+        if let Some(jwk) = method.get("publicKeyJwk") {
+            return Self::from_jwk(jwk);
+        }
+
+        let method_type = method.get("type").and_then(|v| v.as_str());
+        let declared = method_type.and_then(KeyAlgorithm::from_method_type);
+
+        if let Some(hex_key) = method.get("publicKeyHex").and_then(|v| v.as_str()) {
+            let bytes = hex::decode(hex_key).map_err(|_| Error::InvalidSignature)?;
+            return Self::from_bytes(declared.unwrap_or_default(), &bytes);
+        }
+
+        let multibase = method.get("publicKeyMultibase")
+            .and_then(|v| v.as_str())
+            .ok_or(Error::InvalidSignature)?;
+        // "z" = base58btc, the only multibase used for verification keys
+        let encoded = multibase.strip_prefix('z').ok_or(Error::InvalidSignature)?;
+        let bytes = bs58::decode(encoded).into_vec().map_err(|_| Error::InvalidSignature)?;
+
+        match (Self::from_multicodec(&bytes), method_type) {
+            // The multicodec header must agree with the declared type
+            (Ok(key), _) if declared.map_or(true, |algorithm| algorithm == key.algorithm()) => Ok(key),
+            (Ok(_), _) => Err(Error::InvalidSignature),
+            (Err(_), Some("Multikey" | "Ed25519VerificationKey2020")) => Err(Error::InvalidSignature),
+            (Err(_), _) => Self::from_bytes(declared.unwrap_or_default(), &bytes),
+        }
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::Ed25519(_) => KeyAlgorithm::Ed25519,
+            Self::Secp256k1(_) => KeyAlgorithm::Secp256k1,
+            Self::P256(_) => KeyAlgorithm::P256,
+        }
+    }
+
+    /// Raw key bytes: 32 bytes for Ed25519, a compressed SEC1 point for ECDSA
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            Self::Secp256k1(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+            Self::P256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        }
+    }
+
+    /// `publicKeyMultibase` value: "z" + base58btc(multicodec header || key bytes)
+    pub fn to_multibase(&self) -> String {
+        let mut bytes = self.algorithm().multicodec().to_vec();
+        bytes.extend(self.to_bytes());
+        format!("z{}", bs58::encode(bytes).into_string())
+    }
+
+    /// Verification method type for `to_multibase` keys, with the context defining it
+    pub fn method_type(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Ed25519(_) => ("Ed25519VerificationKey2020", ED25519_2020_CONTEXT),
+            Self::Secp256k1(_) | Self::P256(_) => ("Multikey", MULTIKEY_CONTEXT),
+        }
+    }
+
+    /// Verify `signature` over `message`
+    ///
+    /// ECDSA signatures (SHA-256) may be fixed-size r||s or DER; high-S
+    /// signatures are accepted as their low-S equivalent.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        use ed25519_dalek::Verifier as _;
+
+        match self {
+            Self::Ed25519(key) => ed25519_dalek::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::Secp256k1(key) => k256::ecdsa::Signature::from_slice(signature)
+                .or_else(|_| k256::ecdsa::Signature::from_der(signature))
+                .map(|signature| signature.normalize_s().unwrap_or(signature))
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::P256(key) => p256::ecdsa::Signature::from_slice(signature)
+                .or_else(|_| p256::ecdsa::Signature::from_der(signature))
+                .map(|signature| signature.normalize_s().unwrap_or(signature))
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MESSAGE: &[u8] = b"Anima Health Auth:test-nonce";
+
+    #[test]
+    fn test_keys_roundtrip_all_algorithms() {
+        for algorithm in [KeyAlgorithm::Ed25519, KeyAlgorithm::Secp256k1, KeyAlgorithm::P256] {
+            let (public_hex, secret) = algorithm.generate_keypair();
+            let key = PublicKey::from_bytes(algorithm, &hex::decode(&public_hex).unwrap()).unwrap();
+            assert_eq!(key.algorithm(), algorithm);
+            assert_eq!(key.to_bytes(), hex::decode(&public_hex).unwrap());
+
+            // Multibase round trip, under its own type and as Multikey
+            let (method_type, _) = key.method_type();
+            for method_type in [method_type, "Multikey"] {
+                let method = json!({ "type": method_type, "publicKeyMultibase": key.to_multibase() });
+                assert_eq!(PublicKey::from_verification_method(&method).unwrap(), key);
+            }
+
+            let mut multicodec = algorithm.multicodec().to_vec();
+            multicodec.extend(hex::decode(&public_hex).unwrap());
+            assert_eq!(PublicKey::from_multicodec(&multicodec).unwrap(), key);
+
+            let signature = algorithm.sign(&secret, MESSAGE).unwrap();
+            assert!(key.verify(MESSAGE, &signature), "{:?} signature", algorithm);
+            assert!(!key.verify(b"Anima Health Auth:other", &signature));
+            assert!(!key.verify(MESSAGE, &[0u8; 64]));
+        }
+
+        // The multicodec header must match the declared type; "z" + hex is not multibase
+        let (public_hex, _) = KeyAlgorithm::Ed25519.generate_keypair();
+        let key = PublicKey::from_bytes(KeyAlgorithm::Ed25519, &hex::decode(&public_hex).unwrap()).unwrap();
+        let mismatched = json!({ "type": "EcdsaSecp256k1VerificationKey2019", "publicKeyMultibase": key.to_multibase() });
+        assert!(PublicKey::from_verification_method(&mismatched).is_err());
+        let legacy = json!({ "type": "Ed25519VerificationKey2020", "publicKeyMultibase": format!("z{}", public_hex) });
+        assert!(PublicKey::from_verification_method(&legacy).is_err());
+        assert!(key.to_multibase().starts_with("z6Mk"));
+
+        // Wallets commonly send DER-encoded ECDSA signatures
+        let (public_hex, secret) = KeyAlgorithm::Secp256k1.generate_keypair();
+        let key = PublicKey::from_bytes(KeyAlgorithm::Secp256k1, &hex::decode(public_hex).unwrap()).unwrap();
+        let signature = KeyAlgorithm::Secp256k1.sign(&secret, MESSAGE).unwrap();
+        let der = k256::ecdsa::Signature::from_slice(&signature).unwrap().to_der();
+        assert!(key.verify(MESSAGE, der.as_bytes()));
+
+        assert_eq!(KeyAlgorithm::from_jws_alg("ES256K"), Some(KeyAlgorithm::Secp256k1));
+        assert_eq!(serde_json::to_value(KeyAlgorithm::P256).unwrap(), "P-256");
+    }
+}
@@ -1,10 +1,31 @@
 mod error;
 mod challenge;
+mod rate_limit;
+mod client_ip;
+mod keys;
 mod did;
+mod did_method;
+mod did_cache;
 mod token;
+mod gateway_key;
+mod session;
+mod roles;
+mod account;
+mod credential;
+mod issuer;
+mod step_up;
 
 pub use self::error::{Error, Result};
-pub use self::challenge::{ChallengeStore, Challenge};
-pub use self::did::{DIDResolver, DIDDocument};
-pub use self::token::{TokenManager, Claims};
-
+pub use self::challenge::ChallengeStore;
+pub use self::client_ip::TrustedProxies;
+pub use self::keys::{KeyAlgorithm, PublicKey, DID_CONTEXT_V1};
+pub use self::did::{DIDResolver, DIDDocument, MOCK_DID, dev_mock_did_from_env};
+pub use self::did_cache::{DIDCache, CacheStats};
+pub use self::token::{TokenManager, TokenKey, TokenFormat, Claims};
+pub use self::gateway_key::GatewayKey;
+pub use self::session::{SessionStore, Session};
+pub use self::roles::{Roles, RoleRegistry};
+pub use self::account::{AccountRegistry, Account, OnChainBinding};
+pub use self::credential::{CredentialPolicy, StatusList, VcJwt, VerifiedCredential};
+pub use self::issuer::{CredentialIssuer, GatewayCredentialType, IssuedCredential};
+pub use self::step_up::{StepUpStore, StepUpGrant};
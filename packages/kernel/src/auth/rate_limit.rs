@@ -0,0 +1,62 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Sliding-window limiter: at most `limit` events per key within `window_secs`
+///
+/// State is per process; each replica enforces its own budget.
+pub struct RateLimiter {
+    limit: usize,
+    window_secs: u64,
+    // key -> event timestamps (oldest first)
+    events: Arc<Mutex<HashMap<String, VecDeque<u64>>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, window_secs: u64) -> Self {
+        Self {
+            limit,
+            window_secs,
+            events: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Record an event for `key` if it's within budget
+    ///
+    /// Returns the seconds until the next event would be allowed otherwise.
+    /// A limit of 0 disables the limiter.
+    pub async fn try_acquire(&self, key: &str, now: u64) -> std::result::Result<(), u64> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let mut events = self.events.lock().await;
+
+        // Forget keys that went quiet so the map doesn't grow without bound
+        let window_start = now.saturating_sub(self.window_secs);
+        events.retain(|_, times| times.back().is_some_and(|&last| last > window_start));
+
+        let times = events.entry(key.to_string()).or_default();
+        while times.front().is_some_and(|&t| t <= window_start) {
+            times.pop_front();
+        }
+
+        if times.len() >= self.limit {
+            let oldest = times.front().copied().unwrap_or(now);
+            return Err((oldest + self.window_secs).saturating_sub(now).max(1));
+        }
+
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+impl Clone for RateLimiter {
+    fn clone(&self) -> Self {
+        Self {
+            limit: self.limit,
+            window_secs: self.window_secs,
+            events: Arc::clone(&self.events),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::auth::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::BitOr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Role bitmask mirroring the constants in `did_role_registry.move`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Roles(u8);
+
+impl Roles {
+    pub const NONE: Roles = Roles(0);
+    pub const ADMIN: Roles = Roles(1);              // 2^0
+    pub const ANCHORER: Roles = Roles(2);           // 2^1
+    pub const WITNESS: Roles = Roles(4);            // 2^2
+    pub const CONSENT_ATTESTER: Roles = Roles(8);   // 2^3
+    pub const PERMIT_ISSUER: Roles = Roles(16);     // 2^4
+    pub const GOVERNOR: Roles = Roles(32);          // 2^5
+
+    const NAMED: [(Roles, &'static str); 6] = [
+        (Roles::ADMIN, "ADMIN"),
+        (Roles::ANCHORER, "ANCHORER"),
+        (Roles::WITNESS, "WITNESS"),
+        (Roles::CONSENT_ATTESTER, "CONSENT_ATTESTER"),
+        (Roles::PERMIT_ISSUER, "PERMIT_ISSUER"),
+        (Roles::GOVERNOR, "GOVERNOR"),
+    ];
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// True if every role in `other` is held
+    pub fn contains(self, other: Roles) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn remove(self, other: Roles) -> Self {
+        Roles(self.0 & !other.0)
+    }
+
+    /// Role names, as carried in the access token `roles` claim
+    pub fn names(self) -> Vec<String> {
+        Self::NAMED.iter()
+            .filter(|(role, _)| self.contains(*role))
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::NAMED.iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name.trim()))
+            .map(|(role, _)| *role)
+            .ok_or_else(|| Error::UnknownRole(name.to_string()))
+    }
+
+    /// Parse role names, rejecting unknown ones
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        names.iter()
+            .try_fold(Roles::NONE, |acc, name| Ok(acc | Self::from_name(name.as_ref())?))
+    }
+}
+
+impl BitOr for Roles {
+    type Output = Roles;
+
+    fn bitor(self, rhs: Roles) -> Roles {
+        Roles(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Roles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.names().join("|"))
+    }
+}
+
+/// Off-chain view of the role assignments in DIDRoleRegistry
+///
+/// Read at login to fill the token's `roles` claim (and again on refresh, so
+/// grants and revocations take effect within one access token lifetime).
+pub struct RoleRegistry {
+    roles: Arc<RwLock<HashMap<String, Roles>>>,
+}
+
+impl RoleRegistry {
+    /// Bootstrap role assignments from the environment
+    ///
+    /// - DID_ROLES: `did=ROLE|ROLE,did=ROLE` e.g. `did:iota:anima:ops=ADMIN|ANCHORER`
+    /// - ADMIN_DIDS: comma-separated DIDs granted ADMIN (shorthand)
+    pub fn from_env() -> Result<Self> {
+        let mut roles = Self::parse_assignments(&std::env::var("DID_ROLES").unwrap_or_default())?;
+
+        std::env::var("ADMIN_DIDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|did| !did.is_empty())
+            .for_each(|did| {
+                let entry = roles.entry(did.to_string()).or_default();
+                *entry = *entry | Roles::ADMIN;
+            });
+
+        for (did, did_roles) in &roles {
+            println!("->> Roles: {} = {}", did, did_roles);
+        }
+
+        Ok(Self {
+            roles: Arc::new(RwLock::new(roles)),
+        })
+    }
+
+    fn parse_assignments(value: &str) -> Result<HashMap<String, Roles>> {
+        value.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (did, names) = entry.rsplit_once('=')
+                    .ok_or_else(|| Error::UnknownRole(format!("Malformed DID_ROLES entry: {}", entry)))?;
+                let names: Vec<&str> = names.split('|').collect();
+                Ok((did.trim().to_string(), Roles::from_names(&names)?))
+            })
+            .collect()
+    }
+
+    pub async fn roles_for(&self, did: &str) -> Roles {
+        let roles = self.roles.read().await;
+        roles.get(did).copied().unwrap_or_default()
+    }
+
+    pub async fn grant(&self, did: &str, granted: Roles) -> Roles {
+        let mut roles = self.roles.write().await;
+        let entry = roles.entry(did.to_string()).or_default();
+        *entry = *entry | granted;
+
+        println!("->> Roles: Granted {} to {} (now {})", granted, did, entry);
+
+        *entry
+    }
+
+    pub async fn revoke(&self, did: &str, revoked: Roles) -> Roles {
+        let mut roles = self.roles.write().await;
+        let entry = roles.entry(did.to_string()).or_default();
+        *entry = entry.remove(revoked);
+
+        println!("->> Roles: Revoked {} from {} (now {})", revoked, did, entry);
+
+        *entry
+    }
+}
+
+impl Clone for RoleRegistry {
+    fn clone(&self) -> Self {
+        Self {
+            roles: Arc::clone(&self.roles),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_bitmask() {
+        let roles = Roles::ADMIN | Roles::ANCHORER;
+
+        assert_eq!(roles.bits(), 3);
+        assert!(roles.contains(Roles::ANCHORER));
+        assert!(!roles.contains(Roles::WITNESS));
+        assert!(!roles.contains(Roles::ANCHORER | Roles::WITNESS));
+        assert_eq!(roles.names(), vec!["ADMIN", "ANCHORER"]);
+        assert_eq!(Roles::from_names(&roles.names()).unwrap(), roles);
+        assert!(Roles::from_name("ROOT").is_err());
+    }
+
+    #[test]
+    fn test_parse_assignments() {
+        let roles = RoleRegistry::parse_assignments(
+            "did:iota:anima:ops=ADMIN|anchorer, did:iota:anima:w=WITNESS"
+        ).unwrap();
+
+        assert_eq!(roles["did:iota:anima:ops"], Roles::ADMIN | Roles::ANCHORER);
+        assert_eq!(roles["did:iota:anima:w"], Roles::WITNESS);
+        assert!(RoleRegistry::parse_assignments("did:iota:anima:x").is_err());
+        assert!(RoleRegistry::parse_assignments("did:iota:anima:x=SUPERUSER").is_err());
+    }
+}
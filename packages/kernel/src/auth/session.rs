@@ -0,0 +1,251 @@
+use crate::auth::{Error, Result, VerifiedCredential};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const REFRESH_TOKEN_EXPIRY_SECS: u64 = 7 * 86400; // 7 days
+
+/// Server-side login session
+///
+/// Access tokens carry the session ID (`sid`); revoking the session rejects
+/// every access token issued for it at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub sid: String,
+    pub did: String,
+    pub user_id: u64,
+    pub created_at: u64,
+    pub refreshed_at: u64,
+    /// Expiry of the current refresh token
+    pub expires_at: u64,
+    pub revoked_at: Option<u64>,
+    /// Credentials verified at login
+    #[serde(default)]
+    pub credentials: Vec<VerifiedCredential>,
+    /// SHA-256 of the current refresh token secret (never serialized)
+    #[serde(skip_serializing, default)]
+    refresh_token_hash: Vec<u8>,
+}
+
+impl Session {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.revoked_at.is_none() && now <= self.expires_at
+    }
+}
+
+/// Session store with rotating refresh tokens
+///
+/// Refresh token format: {sid}.{base64url(random secret)}
+/// Every refresh hands out a new secret; presenting an already rotated secret
+/// is treated as token theft and revokes the whole session.
+pub struct SessionStore {
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Open a session for an authenticated DID, returning it with its first refresh token
+    pub async fn create_session(
+        &self,
+        did: &str,
+        user_id: u64,
+        credentials: Vec<VerifiedCredential>,
+    ) -> (Session, String) {
+        let now = now_secs();
+        let sid = Uuid::new_v4().to_string();
+        let (refresh_token, refresh_token_hash) = Self::new_refresh_token(&sid);
+
+        let session = Session {
+            sid: sid.clone(),
+            did: did.to_string(),
+            user_id,
+            created_at: now,
+            refreshed_at: now,
+            expires_at: now + REFRESH_TOKEN_EXPIRY_SECS,
+            revoked_at: None,
+            credentials,
+            refresh_token_hash,
+        };
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(sid.clone(), session.clone());
+
+        println!("->> Session: Created {} for DID {}", sid, did);
+
+        (session, refresh_token)
+    }
+
+    /// Exchange a refresh token for a new one (rotation)
+    pub async fn rotate_refresh_token(&self, refresh_token: &str) -> Result<(Session, String)> {
+        let (sid, secret) = refresh_token.split_once('.')
+            .ok_or_else(|| Error::SessionInvalid("Malformed refresh token".to_string()))?;
+
+        let now = now_secs();
+        let mut sessions = self.sessions.write().await;
+
+        let session = sessions.get_mut(sid)
+            .ok_or_else(|| Error::SessionInvalid("Unknown session".to_string()))?;
+
+        if !session.is_active(now) {
+            return Err(Error::SessionRevoked);
+        }
+
+        if Self::hash_secret(secret) != session.refresh_token_hash {
+            // A rotated-out token is being replayed: kill the session
+            session.revoked_at = Some(now);
+            println!("->> Session: ⚠️  Refresh token reuse on {} - session revoked", sid);
+            return Err(Error::SessionRevoked);
+        }
+
+        let (new_refresh_token, new_hash) = Self::new_refresh_token(sid);
+        session.refresh_token_hash = new_hash;
+        session.refreshed_at = now;
+        session.expires_at = now + REFRESH_TOKEN_EXPIRY_SECS;
+
+        println!("->> Session: Rotated refresh token for {}", sid);
+
+        Ok((session.clone(), new_refresh_token))
+    }
+
+    /// Check that a session exists and has not been revoked or expired
+    pub async fn is_active(&self, sid: &str) -> bool {
+        let sessions = self.sessions.read().await;
+        sessions.get(sid)
+            .map(|s| s.is_active(now_secs()))
+            .unwrap_or(false)
+    }
+
+    /// Revoke the session a (current) refresh token belongs to
+    pub async fn revoke_by_refresh_token(&self, refresh_token: &str) -> Result<Session> {
+        let (sid, secret) = refresh_token.split_once('.')
+            .ok_or_else(|| Error::SessionInvalid("Malformed refresh token".to_string()))?;
+
+        {
+            let sessions = self.sessions.read().await;
+            let session = sessions.get(sid)
+                .ok_or_else(|| Error::SessionInvalid("Unknown session".to_string()))?;
+
+            if Self::hash_secret(secret) != session.refresh_token_hash {
+                return Err(Error::SessionInvalid("Refresh token mismatch".to_string()));
+            }
+        }
+
+        self.revoke(sid).await
+    }
+
+    /// Revoke a single session
+    pub async fn revoke(&self, sid: &str) -> Result<Session> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(sid)
+            .ok_or_else(|| Error::SessionInvalid("Unknown session".to_string()))?;
+
+        session.revoked_at.get_or_insert(now_secs());
+
+        println!("->> Session: Revoked {}", sid);
+
+        Ok(session.clone())
+    }
+
+    /// Revoke every active session of a DID, returning how many were revoked
+    pub async fn revoke_all_for_did(&self, did: &str) -> usize {
+        let now = now_secs();
+        let mut sessions = self.sessions.write().await;
+
+        let revoked = sessions.values_mut()
+            .filter(|s| s.did == did && s.is_active(now))
+            .map(|s| s.revoked_at = Some(now))
+            .count();
+
+        println!("->> Session: Revoked {} session(s) for DID {}", revoked, did);
+
+        revoked
+    }
+
+    /// List the active sessions of a DID
+    pub async fn list_for_did(&self, did: &str) -> Vec<Session> {
+        let now = now_secs();
+        let sessions = self.sessions.read().await;
+        sessions.values()
+            .filter(|s| s.did == did && s.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    fn new_refresh_token(sid: &str) -> (String, Vec<u8>) {
+        use rand::RngCore;
+        let mut secret = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut secret);
+        let secret = URL_SAFE_NO_PAD.encode(secret);
+
+        let hash = Self::hash_secret(&secret);
+        (format!("{}.{}", sid, secret), hash)
+    }
+
+    fn hash_secret(secret: &str) -> Vec<u8> {
+        Sha256::digest(secret.as_bytes()).to_vec()
+    }
+}
+
+impl Clone for SessionStore {
+    fn clone(&self) -> Self {
+        Self {
+            sessions: Arc::clone(&self.sessions),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_refresh_token_rotation() {
+        let store = SessionStore::new();
+        let (session, refresh_1) = store.create_session("did:iota:test", 42, vec![]).await;
+
+        let (rotated, refresh_2) = store.rotate_refresh_token(&refresh_1).await.unwrap();
+        assert_eq!(rotated.sid, session.sid);
+        assert_ne!(refresh_1, refresh_2);
+        assert!(store.is_active(&session.sid).await);
+
+        // Replaying the rotated-out token revokes the session
+        assert!(matches!(store.rotate_refresh_token(&refresh_1).await, Err(Error::SessionRevoked)));
+        assert!(!store.is_active(&session.sid).await);
+        assert!(store.rotate_refresh_token(&refresh_2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_for_did() {
+        let store = SessionStore::new();
+        let (s1, _) = store.create_session("did:iota:a", 1, vec![]).await;
+        let (s2, _) = store.create_session("did:iota:a", 1, vec![]).await;
+        let (s3, _) = store.create_session("did:iota:b", 2, vec![]).await;
+
+        assert_eq!(store.list_for_did("did:iota:a").await.len(), 2);
+        assert_eq!(store.revoke_all_for_did("did:iota:a").await, 2);
+
+        assert!(!store.is_active(&s1.sid).await);
+        assert!(!store.is_active(&s2.sid).await);
+        assert!(store.is_active(&s3.sid).await);
+        assert!(store.list_for_did("did:iota:a").await.is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::auth::{Error, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const STEP_UP_EXPIRY_SECS: u64 = 300; // 5 minutes
+
+/// Proof that an account just re-signed a fresh challenge with its DID
+///
+/// Sensitive operations (custodial signing, key export) each consume one grant.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepUpGrant {
+    pub token: String,
+    pub user_id: u64,
+    /// DID that signed the step-up challenge
+    pub did: String,
+    pub expires_at: u64,
+}
+
+/// Single-use step-up grants, held in memory
+pub struct StepUpStore {
+    grants: Arc<RwLock<HashMap<String, StepUpGrant>>>,
+}
+
+impl StepUpStore {
+    pub fn new() -> Self {
+        Self {
+            grants: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Grant a step-up to an account after it re-authenticated with `did`
+    pub async fn grant(&self, user_id: u64, did: &str) -> StepUpGrant {
+        let now = now_secs();
+        let grant = StepUpGrant {
+            token: Uuid::new_v4().to_string(),
+            user_id,
+            did: did.to_string(),
+            expires_at: now + STEP_UP_EXPIRY_SECS,
+        };
+
+        let mut grants = self.grants.write().await;
+        grants.retain(|_, grant| grant.expires_at >= now);
+        grants.insert(grant.token.clone(), grant.clone());
+
+        println!("->> StepUp: Granted to account {} via {}", user_id, did);
+
+        grant
+    }
+
+    /// Consume a grant; it must belong to `user_id` and still be valid
+    pub async fn consume(&self, token: &str, user_id: u64) -> Result<StepUpGrant> {
+        let grant = self.grants.write().await
+            .remove(token)
+            .ok_or(Error::StepUpRequired)?;
+
+        if grant.user_id != user_id || now_secs() > grant.expires_at {
+            return Err(Error::StepUpRequired);
+        }
+
+        Ok(grant)
+    }
+}
+
+impl Clone for StepUpStore {
+    fn clone(&self) -> Self {
+        Self {
+            grants: Arc::clone(&self.grants),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -1,10 +1,20 @@
-use crate::auth::{Error, Result};
+use crate::auth::{Error, Result, GatewayKey, Roles};
 use serde::{Serialize, Deserialize};
+use serde_json::json;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use sha2::{Sha256, Digest};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
 
-const TOKEN_EXPIRY_SECS: u64 = 86400; // 24 hours
-const TOKEN_SECRET: &str = "anima-secret-key-change-in-production"; // In production: from env
+type HmacSha256 = Hmac<Sha256>;
+
+const ACCESS_TOKEN_EXPIRY_SECS: u64 = 900; // 15 minutes - renewed via refresh token
+const TOKEN_ROTATION_GRACE_SECS: u64 = 86400; // 24 hours
+const TOKEN_VERSION: &str = "v1";
+const DEFAULT_KEY_ID: &str = "k1";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -12,118 +22,572 @@ pub struct Claims {
     pub user_id: u64,
     pub exp: u64,  // Expiry timestamp
     pub iat: u64,  // Issued at timestamp
+    #[serde(default)]
+    pub jti: String,  // Unique token ID
+    #[serde(default)]
+    pub sid: String,  // Session ID (see SessionStore)
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Types of the verifiable credentials presented at login
+    #[serde(default)]
+    pub credentials: Vec<String>,
+}
+
+/// Registered JWT claims issued by the gateway (sub = DID)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JwtClaims {
+    iss: String,
+    sub: String,
+    exp: u64,
+    iat: u64,
+    jti: String,
+    /// Gateway session ID
+    sid: String,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    credentials: Vec<String>,
+    /// Gateway account ID
+    uid: u64,
+}
+
+/// Wire format of newly issued access tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenFormat {
+    /// v1.{kid}.{claims}.{hmac} - only verifiable by the gateway
+    Hmac,
+    /// Compact JWS signed with EdDSA by the gateway key - verifiable offline via JWKS
+    Jwt,
+}
+
+/// HMAC secret identified by a key ID
+#[derive(Clone)]
+pub struct TokenKey {
+    pub kid: String,
+    secret: Vec<u8>,
+    /// When the key stopped signing new tokens (None for the current key)
+    pub retired_at: Option<u64>,
+}
+
+impl TokenKey {
+    pub fn new(kid: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kid: kid.into(),
+            secret: secret.into(),
+            retired_at: None,
+        }
+    }
+
+    pub fn retired_at(mut self, retired_at: u64) -> Self {
+        self.retired_at = Some(retired_at);
+        self
+    }
+}
+
+/// Issues and validates session tokens
+///
+/// HMAC format: v1.{kid}.{base64url(claims json)}.{base64url(hmac)}
+/// JWT format:  {header}.{claims}.{EdDSA signature} (RFC 7519 / RFC 8037)
+///
+/// New HMAC tokens are always signed with the current key. Retired keys keep
+/// validating their tokens until `retired_at + grace_secs`.
+#[derive(Clone)]
+pub struct TokenManager {
+    current: Arc<TokenKey>,
+    previous: Arc<Vec<TokenKey>>,
+    grace_secs: u64,
+    access_ttl_secs: u64,
+    gateway_key: GatewayKey,
+    format: TokenFormat,
 }
 
-pub struct TokenManager;
+/// Token split into its parts (signature not yet checked)
+#[derive(Debug)]
+pub struct ParsedToken {
+    pub kid: String,
+    pub claims: Claims,
+    pub signing_input: String,
+    pub signature: Vec<u8>,
+}
 
 impl TokenManager {
-    /// Generate a token for an authenticated DID
-    /// Format: user-{user_id}.{exp}.{signature}
-    pub fn generate_token(did: &str, user_id: u64) -> Result<String> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    pub fn new(current: TokenKey, previous: Vec<TokenKey>, grace_secs: u64) -> Self {
+        Self {
+            current: Arc::new(current),
+            previous: Arc::new(previous),
+            grace_secs,
+            access_ttl_secs: ACCESS_TOKEN_EXPIRY_SECS,
+            gateway_key: GatewayKey::generate(),
+            format: TokenFormat::Hmac,
+        }
+    }
+
+    pub fn with_gateway_key(mut self, gateway_key: GatewayKey) -> Self {
+        self.gateway_key = gateway_key;
+        self
+    }
+
+    pub fn with_format(mut self, format: TokenFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_access_ttl(mut self, access_ttl_secs: u64) -> Self {
+        self.access_ttl_secs = access_ttl_secs;
+        self
+    }
+
+    /// Lifetime of newly issued access tokens
+    pub fn access_ttl_secs(&self) -> u64 {
+        self.access_ttl_secs
+    }
+
+    pub fn gateway_key(&self) -> &GatewayKey {
+        &self.gateway_key
+    }
+
+    /// Load signing keys from the environment
+    ///
+    /// - TOKEN_SECRET: current HMAC secret
+    /// - TOKEN_KEY_ID: key ID of the current secret (default "k1")
+    /// - TOKEN_PREVIOUS_KEYS: retired keys as "kid:secret:retired_at,..." (retired_at in unix secs)
+    /// - TOKEN_ROTATION_GRACE_SECS: how long retired keys keep validating (default 24h)
+    /// - TOKEN_FORMAT: "hmac" (default) or "jwt" for EdDSA tokens signed by the gateway key
+    /// - ACCESS_TOKEN_TTL_SECS: access token lifetime (default 15 min)
+    pub fn from_env() -> Result<Self> {
+        let kid = std::env::var("TOKEN_KEY_ID").unwrap_or_else(|_| DEFAULT_KEY_ID.to_string());
 
-        let exp = now + TOKEN_EXPIRY_SECS;
+        let secret = match std::env::var("TOKEN_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                println!("->> ⚠️  TOKEN_SECRET not set - using a random secret (sessions won't survive restart)");
+                use rand::RngCore;
+                let mut secret = vec![0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut secret);
+                secret
+            }
+        };
 
-        // Create payload
-        let payload = format!("{}:{}:{}", did, user_id, exp);
+        let previous = std::env::var("TOKEN_PREVIOUS_KEYS")
+            .map(|keys| Self::parse_previous_keys(&keys))
+            .unwrap_or_default();
 
-        // Sign with HMAC-SHA256
-        let signature = Self::sign(&payload);
+        let grace_secs = std::env::var("TOKEN_ROTATION_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(TOKEN_ROTATION_GRACE_SECS);
 
-        // Format: user-{user_id}.{exp}.{signature}
-        let token = format!("user-{}.{}.{}", user_id, exp, signature);
+        let access_ttl_secs = std::env::var("ACCESS_TOKEN_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(ACCESS_TOKEN_EXPIRY_SECS);
 
-        println!("->> Token: Generated for DID {} (user_id: {}, expires: {})", did, user_id, exp);
+        let format = match std::env::var("TOKEN_FORMAT").as_deref() {
+            Ok("jwt") => TokenFormat::Jwt,
+            _ => TokenFormat::Hmac,
+        };
+
+        println!("->> Token: Signing with key '{}' ({} retired key(s) accepted, format: {:?})",
+                 kid, previous.len(), format);
+
+        Ok(Self::new(TokenKey::new(kid, secret), previous, grace_secs)
+            .with_gateway_key(GatewayKey::from_env()?)
+            .with_format(format)
+            .with_access_ttl(access_ttl_secs))
+    }
+
+    /// Parse "kid:secret:retired_at" entries, skipping malformed ones
+    fn parse_previous_keys(keys: &str) -> Vec<TokenKey> {
+        keys.split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .filter_map(|entry| {
+                let (kid, rest) = entry.trim().split_once(':')?;
+                let (secret, retired_at) = rest.rsplit_once(':')?;
+                let retired_at = retired_at.parse().ok()?;
+                Some(TokenKey::new(kid, secret).retired_at(retired_at))
+            })
+            .collect()
+    }
+
+    /// Generate an access token for a session in the configured format
+    pub fn generate_token(
+        &self,
+        did: &str,
+        user_id: u64,
+        sid: &str,
+        roles: Roles,
+        credentials: &[String],
+    ) -> Result<String> {
+        match self.format {
+            TokenFormat::Hmac => self.generate_hmac_token(did, user_id, sid, roles, credentials),
+            TokenFormat::Jwt => self.generate_jwt(did, user_id, sid, roles, credentials),
+        }
+    }
+
+    fn generate_hmac_token(
+        &self,
+        did: &str,
+        user_id: u64,
+        sid: &str,
+        roles: Roles,
+        credentials: &[String],
+    ) -> Result<String> {
+        let now = now_secs();
+
+        let claims = Claims {
+            did: did.to_string(),
+            user_id,
+            exp: now + self.access_ttl_secs,
+            iat: now,
+            jti: Uuid::new_v4().to_string(),
+            sid: sid.to_string(),
+            roles: roles.names(),
+            credentials: credentials.to_vec(),
+        };
+
+        let payload = serde_json::to_vec(&claims)
+            .map_err(|e| Error::TokenGenerationFailed(e.to_string()))?;
+
+        let signing_input = format!(
+            "{}.{}.{}",
+            TOKEN_VERSION,
+            self.current.kid,
+            URL_SAFE_NO_PAD.encode(payload)
+        );
+
+        let signature = Self::sign(&self.current, &signing_input)?;
+        let token = format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature));
+
+        println!("->> Token: Generated for DID {} (user_id: {}, kid: {}, expires: {})",
+                 did, user_id, self.current.kid, claims.exp);
 
         Ok(token)
     }
 
-    /// Parse and validate a token
-    /// Returns (user_id, exp, signature)
-    pub fn parse_token(token: &str) -> Result<(u64, u64, String)> {
+    /// Split a token into its parts without checking the signature
+    pub fn parse_token(token: &str) -> Result<ParsedToken> {
         let parts: Vec<&str> = token.split('.').collect();
-        
-        if parts.len() != 3 {
+
+        if parts.len() != 4 || parts[0] != TOKEN_VERSION {
             return Err(Error::TokenValidationFailed("Invalid token format".to_string()));
         }
 
-        // Extract user-{user_id}
-        let user_id = parts[0]
-            .strip_prefix("user-")
-            .ok_or_else(|| Error::TokenValidationFailed("Invalid user_id prefix".to_string()))?
-            .parse::<u64>()
-            .map_err(|_| Error::TokenValidationFailed("Invalid user_id".to_string()))?;
+        let payload = URL_SAFE_NO_PAD.decode(parts[2])
+            .map_err(|_| Error::TokenValidationFailed("Invalid payload encoding".to_string()))?;
 
-        let exp = parts[1]
-            .parse::<u64>()
-            .map_err(|_| Error::TokenValidationFailed("Invalid expiry".to_string()))?;
+        let claims: Claims = serde_json::from_slice(&payload)
+            .map_err(|_| Error::TokenValidationFailed("Invalid claims".to_string()))?;
 
-        let signature = parts[2].to_string();
+        let signature = URL_SAFE_NO_PAD.decode(parts[3])
+            .map_err(|_| Error::TokenValidationFailed("Invalid signature encoding".to_string()))?;
 
-        Ok((user_id, exp, signature))
+        Ok(ParsedToken {
+            kid: parts[1].to_string(),
+            claims,
+            signing_input: format!("{}.{}.{}", parts[0], parts[1], parts[2]),
+            signature,
+        })
     }
 
-    /// Validate a token (check signature and expiry)
-    pub fn validate_token(token: &str, expected_did: Option<&str>) -> Result<Claims> {
-        let (user_id, exp, signature) = Self::parse_token(token)?;
+    /// Generate a compact JWS (EdDSA) signed by the gateway key
+    pub fn generate_jwt(
+        &self,
+        did: &str,
+        user_id: u64,
+        sid: &str,
+        roles: Roles,
+        credentials: &[String],
+    ) -> Result<String> {
+        let now = now_secs();
 
-        // Check expiry
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let header = json!({
+            "alg": "EdDSA",
+            "typ": "JWT",
+            "kid": self.gateway_key.kid(),
+        });
 
-        if now > exp {
-            return Err(Error::TokenValidationFailed("Token expired".to_string()));
+        let claims = JwtClaims {
+            iss: self.gateway_key.did().to_string(),
+            sub: did.to_string(),
+            exp: now + self.access_ttl_secs,
+            iat: now,
+            jti: Uuid::new_v4().to_string(),
+            sid: sid.to_string(),
+            roles: roles.names(),
+            credentials: credentials.to_vec(),
+            uid: user_id,
+        };
+
+        let header = serde_json::to_vec(&header)
+            .map_err(|e| Error::TokenGenerationFailed(e.to_string()))?;
+        let payload = serde_json::to_vec(&claims)
+            .map_err(|e| Error::TokenGenerationFailed(e.to_string()))?;
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(payload)
+        );
+        let signature = self.gateway_key.sign(signing_input.as_bytes());
+
+        println!("->> Token: Generated JWT for DID {} (jti: {}, expires: {})", did, claims.jti, claims.exp);
+
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+    }
+
+    /// Validate a gateway-issued JWT (alg, kid, issuer, signature and expiry)
+    pub fn validate_jwt(&self, token: &str) -> Result<Claims> {
+        let parts: Vec<&str> = token.split('.').collect();
+
+        if parts.len() != 3 {
+            return Err(Error::TokenValidationFailed("Invalid JWT format".to_string()));
         }
 
-        // For full validation, would need to re-create signature and compare
-        // For POC: Accept if format is valid and not expired
+        let header: serde_json::Value = URL_SAFE_NO_PAD.decode(parts[0])
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| Error::TokenValidationFailed("Invalid JWT header".to_string()))?;
+
+        if header["alg"] != "EdDSA" {
+            return Err(Error::TokenValidationFailed("Unsupported JWT alg".to_string()));
+        }
+        if header["kid"] != self.gateway_key.kid().as_str() {
+            return Err(Error::TokenValidationFailed("Unknown JWT kid".to_string()));
+        }
 
-        let did = expected_did.unwrap_or("did:iota:anima:unknown").to_string();
+        let signature = URL_SAFE_NO_PAD.decode(parts[2])
+            .map_err(|_| Error::TokenValidationFailed("Invalid signature encoding".to_string()))?;
+        self.gateway_key.verify(format!("{}.{}", parts[0], parts[1]).as_bytes(), &signature)?;
+
+        let claims: JwtClaims = URL_SAFE_NO_PAD.decode(parts[1])
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| Error::TokenValidationFailed("Invalid JWT claims".to_string()))?;
+
+        if claims.iss != self.gateway_key.did() {
+            return Err(Error::TokenValidationFailed("Unexpected JWT issuer".to_string()));
+        }
+        if now_secs() > claims.exp {
+            return Err(Error::TokenValidationFailed("Token expired".to_string()));
+        }
 
         Ok(Claims {
-            did,
-            user_id,
-            exp,
-            iat: now,
+            did: claims.sub,
+            user_id: claims.uid,
+            exp: claims.exp,
+            iat: claims.iat,
+            jti: claims.jti,
+            sid: claims.sid,
+            roles: claims.roles,
+            credentials: claims.credentials,
         })
     }
 
+    /// Validate a token of either format (check signature, expiry and optionally the DID)
+    pub fn validate_token(&self, token: &str, expected_did: Option<&str>) -> Result<Claims> {
+        let claims = if token.starts_with(TOKEN_VERSION) {
+            self.validate_hmac_token(token)?
+        } else {
+            self.validate_jwt(token)?
+        };
+
+        if let Some(expected_did) = expected_did {
+            if claims.did != expected_did {
+                return Err(Error::TokenValidationFailed("DID mismatch".to_string()));
+            }
+        }
+
+        Ok(claims)
+    }
+
+    fn validate_hmac_token(&self, token: &str) -> Result<Claims> {
+        let parsed = Self::parse_token(token)?;
+        let now = now_secs();
+
+        let key = self.key_for(&parsed.kid, now)?;
+
+        // Constant-time comparison (hmac::Mac::verify_slice)
+        let mut mac = HmacSha256::new_from_slice(&key.secret)
+            .map_err(|e| Error::TokenValidationFailed(e.to_string()))?;
+        mac.update(parsed.signing_input.as_bytes());
+        mac.verify_slice(&parsed.signature)
+            .map_err(|_| Error::TokenValidationFailed("Invalid signature".to_string()))?;
+
+        let claims = parsed.claims;
+
+        if now > claims.exp {
+            return Err(Error::TokenValidationFailed("Token expired".to_string()));
+        }
+
+        Ok(claims)
+    }
+
+    /// Find the key for a key ID, honouring the rotation grace window
+    fn key_for(&self, kid: &str, now: u64) -> Result<&TokenKey> {
+        if self.current.kid == kid {
+            return Ok(&self.current);
+        }
+
+        let key = self.previous.iter()
+            .find(|key| key.kid == kid)
+            .ok_or_else(|| Error::TokenValidationFailed(format!("Unknown key id: {}", kid)))?;
+
+        match key.retired_at {
+            Some(retired_at) if now > retired_at + self.grace_secs => {
+                Err(Error::TokenValidationFailed(format!("Key {} past rotation grace window", kid)))
+            }
+            _ => Ok(key),
+        }
+    }
+
     /// Sign a payload with HMAC-SHA256
-    fn sign(payload: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(TOKEN_SECRET.as_bytes());
-        hasher.update(payload.as_bytes());
-        let result = hasher.finalize();
-        hex::encode(result)
+    fn sign(key: &TokenKey, payload: &str) -> Result<Vec<u8>> {
+        let mut mac = HmacSha256::new_from_slice(&key.secret)
+            .map_err(|e| Error::TokenGenerationFailed(e.to_string()))?;
+        mac.update(payload.as_bytes());
+        Ok(mac.finalize().into_bytes().to_vec())
     }
 }
 
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_manager() -> TokenManager {
+        TokenManager::new(TokenKey::new("k1", "test-secret"), vec![], TOKEN_ROTATION_GRACE_SECS)
+    }
+
     #[test]
     fn test_generate_and_parse_token() {
-        let token = TokenManager::generate_token("did:iota:test", 42).unwrap();
-        let (user_id, exp, signature) = TokenManager::parse_token(&token).unwrap();
-        
-        assert_eq!(user_id, 42);
-        assert!(exp > 0);
-        assert!(!signature.is_empty());
+        let token = test_manager().generate_token("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+        let parsed = TokenManager::parse_token(&token).unwrap();
+
+        assert_eq!(parsed.claims.user_id, 42);
+        assert_eq!(parsed.claims.did, "did:iota:test");
+        assert_eq!(parsed.kid, "k1");
+        assert_eq!(parsed.claims.sid, "sid-1");
+        assert!(parsed.claims.exp > 0);
+        assert!(!parsed.signature.is_empty());
     }
 
     #[test]
     fn test_validate_token() {
-        let token = TokenManager::generate_token("did:iota:test", 42).unwrap();
-        let claims = TokenManager::validate_token(&token, Some("did:iota:test")).unwrap();
-        
+        let manager = test_manager();
+        let token = manager.generate_token("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+        let claims = manager.validate_token(&token, Some("did:iota:test")).unwrap();
+
         assert_eq!(claims.user_id, 42);
         assert_eq!(claims.did, "did:iota:test");
+
+        assert!(manager.validate_token(&token, Some("did:iota:other")).is_err());
+    }
+
+    #[test]
+    fn test_validate_token_rejects_tampering() {
+        let manager = test_manager();
+        let token = manager.generate_token("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+
+        // Swap in claims for another user, keeping the original signature
+        let forged_claims = Claims {
+            did: "did:iota:admin".to_string(),
+            user_id: 1,
+            exp: u64::MAX,
+            iat: 0,
+            jti: String::new(),
+            sid: String::new(),
+            roles: vec![],
+            credentials: vec![],
+        };
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged = format!(
+            "{}.{}.{}.{}",
+            parts[0], parts[1],
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged_claims).unwrap()),
+            parts[3]
+        );
+        assert!(manager.validate_token(&forged, None).is_err());
+
+        // Same token under a different secret
+        let other = TokenManager::new(TokenKey::new("k1", "other-secret"), vec![], TOKEN_ROTATION_GRACE_SECS);
+        assert!(other.validate_token(&token, None).is_err());
+    }
+
+    #[test]
+    fn test_key_rotation_grace_window() {
+        let old_manager = test_manager();
+        let token = old_manager.generate_token("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+        let now = now_secs();
+
+        // k1 retired just now - still within grace window
+        let rotated = TokenManager::new(
+            TokenKey::new("k2", "new-secret"),
+            vec![TokenKey::new("k1", "test-secret").retired_at(now)],
+            3600,
+        );
+        assert!(rotated.validate_token(&token, None).is_ok());
+
+        // k1 retired long ago - grace window elapsed
+        let expired = TokenManager::new(
+            TokenKey::new("k2", "new-secret"),
+            vec![TokenKey::new("k1", "test-secret").retired_at(now - 7200)],
+            3600,
+        );
+        assert!(expired.validate_token(&token, None).is_err());
+
+        // New tokens are signed with k2
+        let new_token = rotated.generate_token("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+        assert_eq!(TokenManager::parse_token(&new_token).unwrap().kid, "k2");
     }
-}
 
+    #[test]
+    fn test_parse_previous_keys() {
+        let keys = TokenManager::parse_previous_keys("k0:secret-a:100, bad-entry ,k1:se:cret:200,k2:secret:never");
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].kid, "k0");
+        assert_eq!(keys[0].retired_at, Some(100));
+        assert_eq!(keys[1].secret, b"se:cret".to_vec());
+    }
+
+    #[test]
+    fn test_jwt_roundtrip() {
+        let manager = test_manager().with_format(TokenFormat::Jwt);
+        let credentials = vec!["LicensedPhysician".to_string()];
+        let token = manager.generate_token("did:iota:test", 42, "sid-1", Roles::ANCHORER, &credentials).unwrap();
+        assert_eq!(token.split('.').count(), 3);
+
+        let claims = manager.validate_token(&token, Some("did:iota:test")).unwrap();
+        assert_eq!(claims.user_id, 42);
+        assert_eq!(claims.did, "did:iota:test");
+        assert!(!claims.jti.is_empty());
+        assert_eq!(claims.sid, "sid-1");
+        assert_eq!(claims.roles, vec!["ANCHORER"]);
+        assert_eq!(claims.credentials, credentials);
+
+        // The same JWT under another gateway key is rejected
+        let other = test_manager().with_format(TokenFormat::Jwt);
+        assert!(other.validate_token(&token, None).is_err());
+    }
+
+    #[test]
+    fn test_jwt_verifies_with_published_jwk() {
+        let manager = test_manager();
+        let token = manager.generate_jwt("did:iota:test", 42, "sid-1", Roles::NONE, &[]).unwrap();
+
+        // Verify the way a downstream service would: only the JWKS document
+        let jwks = manager.gateway_key().jwks();
+        let x = URL_SAFE_NO_PAD.decode(jwks["keys"][0]["x"].as_str().unwrap()).unwrap();
+        let public_key = ed25519_dalek::VerifyingKey::from_bytes(&x.try_into().unwrap()).unwrap();
+
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+        use ed25519_dalek::Verifier;
+        assert!(public_key.verify(signing_input.as_bytes(), &signature).is_ok());
+    }
+}
@@ -2,22 +2,49 @@ mod error;
 
 pub use self::error::{Error, Result};
 
+use crate::auth::Roles;
+
 #[derive(Clone, Debug)]
 pub struct  Ctx {
     user_id: u64,
+    did: Option<String>,
+    session_id: Option<String>,
+    roles: Roles,
+    /// Types of verified credentials (login + per-request)
+    credentials: Vec<String>,
 }
 
 impl Ctx {
     pub fn root_ctx() -> Self {
-        Ctx { user_id: 0 }
+        Ctx { user_id: 0, did: None, session_id: None, roles: Roles::NONE, credentials: Vec::new() }
     }
     
-    pub fn new(user_id: u64) -> Result<Self> {
+    pub fn new(user_id: u64, did: impl Into<String>) -> Result<Self> {
         if user_id == 0 {
             Err(Error::CtxCannotNewRootCtx)
         } else {
-            Ok(Self { user_id })
+            Ok(Self { user_id, did: Some(did.into()), session_id: None, roles: Roles::NONE, credentials: Vec::new() })
+        }
+    }
+
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn with_roles(mut self, roles: Roles) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Add verified credential types (duplicates are ignored)
+    pub fn with_credentials(mut self, types: impl IntoIterator<Item = String>) -> Self {
+        for credential_type in types {
+            if !self.credentials.contains(&credential_type) {
+                self.credentials.push(credential_type);
+            }
         }
+        self
     }
 }
 
@@ -25,4 +52,28 @@ impl Ctx {
     pub fn user_id(&self) -> u64 {
         self.user_id
     }
-}
\ No newline at end of file
+
+    /// DID the session was authenticated with (None for root ctx)
+    pub fn did(&self) -> Option<&str> {
+        self.did.as_deref()
+    }
+
+    /// Server-side session backing the access token
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Roles granted at login (DIDRoleRegistry bitmask)
+    pub fn roles(&self) -> Roles {
+        self.roles
+    }
+
+    pub fn has_role(&self, role: Roles) -> bool {
+        self.roles.contains(role)
+    }
+
+    /// Verified credential types, e.g. "LicensedPhysician"
+    pub fn credential_types(&self) -> &[String] {
+        &self.credentials
+    }
+}
@@ -0,0 +1,337 @@
+use crate::did_manager::{Error, Result, DIDRegistry, PatientDID};
+use crate::auth::KeyAlgorithm;
+use crate::persist::JsonFileStore;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use sha2::{Sha256, Digest};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const DEFAULT_CUSTODY_AUDIT_FILE: &str = "data/custody_audit.json";
+const VC_CONTEXT_V1: &str = "https://www.w3.org/2018/credentials/v1";
+const PRESENTATION_EXPIRY_SECS: i64 = 300;
+
+/// What the gateway signs on a patient's behalf
+///
+/// Closed set: every purpose has a fixed message format, so a custodial
+/// signature can never be turned into a signature over arbitrary bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "purpose", rename_all = "snake_case")]
+pub enum SigningPurpose {
+    /// "Anima Health Auth:{nonce}" - answers a /api/auth/challenge for the DID
+    LoginChallenge { nonce: String },
+    /// "Anima Health Consent:{statement}"
+    Consent { statement: String },
+    /// VP-JWT presenting `credentials` (VC-JWTs) to `audience`
+    Presentation {
+        audience: String,
+        nonce: String,
+        #[serde(default)]
+        credentials: Vec<String>,
+    },
+}
+
+impl SigningPurpose {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::LoginChallenge { .. } => "login_challenge",
+            Self::Consent { .. } => "consent",
+            Self::Presentation { .. } => "presentation",
+        }
+    }
+}
+
+/// One custodial key use, recorded before the result is handed out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustodyAuditEntry {
+    pub id: String,
+    pub did: String,
+    /// "sign" or "export"
+    pub action: String,
+    pub purpose: Option<String>,
+    /// SHA-256 (hex) of the signed bytes
+    pub digest: Option<String>,
+    /// Account that requested the operation
+    pub requested_by: u64,
+    /// DID that passed the step-up check
+    pub step_up_did: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CustodialSignature {
+    pub did: String,
+    pub kid: String,
+    pub alg: &'static str,
+    pub purpose: &'static str,
+    /// The exact signed message (login challenge, consent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Hex-encoded signature (login challenge, consent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Signed VP-JWT (presentation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presentation: Option<String>,
+    pub audit_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedKey {
+    pub did: String,
+    pub kid: String,
+    pub key_algorithm: KeyAlgorithm,
+    pub public_key: String,
+    /// Hex-encoded private key - the gateway no longer holds it
+    pub private_key: String,
+    pub audit_id: String,
+}
+
+/// Signs with the gateway-held keys of custodial patient DIDs
+///
+/// Callers are expected to have checked authorization and step-up; every key
+/// use is written to the audit log before its result is returned.
+pub struct CustodialSigner {
+    did_registry: DIDRegistry,
+    audit: Arc<RwLock<Vec<CustodyAuditEntry>>>,
+    store: Option<JsonFileStore>,
+}
+
+impl CustodialSigner {
+    /// Signer with an in-memory audit log (tests, throwaway dev runs)
+    pub fn in_memory(did_registry: DIDRegistry) -> Self {
+        Self {
+            did_registry,
+            audit: Arc::new(RwLock::new(Vec::new())),
+            store: None,
+        }
+    }
+
+    /// Open the audit log at CUSTODY_AUDIT_FILE (default "data/custody_audit.json")
+    ///
+    /// An empty CUSTODY_AUDIT_FILE keeps the audit log in memory only.
+    pub async fn from_env(did_registry: DIDRegistry) -> Result<Self> {
+        let path = std::env::var("CUSTODY_AUDIT_FILE")
+            .unwrap_or_else(|_| DEFAULT_CUSTODY_AUDIT_FILE.to_string());
+        if path.is_empty() {
+            println!("->> ⚠️  CUSTODY_AUDIT_FILE empty - custody audit is not persisted");
+            return Ok(Self::in_memory(did_registry));
+        }
+
+        let store = JsonFileStore::new(&path);
+        let audit: Vec<CustodyAuditEntry> = store.load().await.map_err(Error::Persist)?;
+
+        println!("->> Custody: Loaded {} audit entries from {}", audit.len(), path);
+
+        Ok(Self {
+            did_registry,
+            audit: Arc::new(RwLock::new(audit)),
+            store: Some(store),
+        })
+    }
+
+    /// Sign `purpose` with the DID's custodial key
+    pub async fn sign(
+        &self,
+        did: &str,
+        purpose: SigningPurpose,
+        requested_by: u64,
+        step_up_did: &str,
+    ) -> Result<CustodialSignature> {
+        println!("->> Custody: Signing {} for {} (account {})", purpose.name(), did, requested_by);
+
+        let patient_did = self.did_registry.get_by_did(did).await?;
+
+        let (message, presentation_parts) = match &purpose {
+            SigningPurpose::LoginChallenge { nonce } => (format!("Anima Health Auth:{}", nonce), None),
+            SigningPurpose::Consent { statement } => (format!("Anima Health Consent:{}", statement), None),
+            SigningPurpose::Presentation { audience, nonce, credentials } => {
+                let signing_input = presentation_signing_input(&patient_did, audience, nonce, credentials);
+                (signing_input.clone(), Some(signing_input))
+            }
+        };
+
+        let (patient_did, signature) = self.did_registry.sign(did, message.as_bytes()).await?;
+
+        let audit_id = self.record(CustodyAuditEntry {
+            id: Uuid::new_v4().to_string(),
+            did: did.to_string(),
+            action: "sign".to_string(),
+            purpose: Some(purpose.name().to_string()),
+            digest: Some(hex::encode(Sha256::digest(message.as_bytes()))),
+            requested_by,
+            step_up_did: step_up_did.to_string(),
+            at: Utc::now(),
+        }).await?;
+
+        let base = CustodialSignature {
+            did: did.to_string(),
+            kid: patient_did.key_id(),
+            alg: patient_did.key_algorithm.jws_alg(),
+            purpose: purpose.name(),
+            message: None,
+            signature: None,
+            presentation: None,
+            audit_id,
+        };
+
+        Ok(match presentation_parts {
+            Some(signing_input) => CustodialSignature {
+                presentation: Some(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))),
+                ..base
+            },
+            None => CustodialSignature {
+                message: Some(message),
+                signature: Some(hex::encode(signature)),
+                ..base
+            },
+        })
+    }
+
+    /// Export the DID's key to the patient and switch it to self-custody
+    pub async fn export(&self, did: &str, requested_by: u64, step_up_did: &str) -> Result<ExportedKey> {
+        println!("->> Custody: Exporting key of {} (account {})", did, requested_by);
+
+        // Audit first: an export that isn't on record must not happen
+        let audit_id = self.record(CustodyAuditEntry {
+            id: Uuid::new_v4().to_string(),
+            did: did.to_string(),
+            action: "export".to_string(),
+            purpose: None,
+            digest: None,
+            requested_by,
+            step_up_did: step_up_did.to_string(),
+            at: Utc::now(),
+        }).await?;
+
+        let (patient_did, private_key) = self.did_registry.export_key(did).await?;
+
+        Ok(ExportedKey {
+            did: did.to_string(),
+            kid: patient_did.key_id(),
+            key_algorithm: patient_did.key_algorithm,
+            public_key: patient_did.public_key,
+            private_key: hex::encode(private_key),
+            audit_id,
+        })
+    }
+
+    /// Audit entries of a DID, oldest first
+    pub async fn audit_for(&self, did: &str) -> Vec<CustodyAuditEntry> {
+        self.audit.read().await
+            .iter()
+            .filter(|entry| entry.did == did)
+            .cloned()
+            .collect()
+    }
+
+    /// Append an entry (written through to the audit file first)
+    async fn record(&self, entry: CustodyAuditEntry) -> Result<String> {
+        let mut audit = self.audit.write().await;
+        audit.push(entry.clone());
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(&*audit).await {
+                audit.pop();
+                return Err(Error::Persist(e));
+            }
+        }
+
+        Ok(entry.id)
+    }
+}
+
+impl Clone for CustodialSigner {
+    fn clone(&self) -> Self {
+        Self {
+            did_registry: self.did_registry.clone(),
+            audit: Arc::clone(&self.audit),
+            store: self.store.clone(),
+        }
+    }
+}
+
+/// Header and payload of a VP-JWT held by the patient DID
+fn presentation_signing_input(patient_did: &PatientDID, audience: &str, nonce: &str, credentials: &[String]) -> String {
+    let now = Utc::now().timestamp();
+
+    let header = json!({
+        "alg": patient_did.key_algorithm.jws_alg(),
+        "kid": patient_did.key_id(),
+        "typ": "JWT",
+    });
+    let payload = json!({
+        "iss": patient_did.did,
+        "aud": audience,
+        "nonce": nonce,
+        "iat": now,
+        "exp": now + PRESENTATION_EXPIRY_SECS,
+        "jti": format!("urn:uuid:{}", Uuid::new_v4()),
+        "vp": {
+            "@context": [VC_CONTEXT_V1],
+            "type": ["VerifiablePresentation"],
+            "holder": patient_did.did,
+            "verifiableCredential": credentials,
+        },
+    });
+
+    format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(payload.to_string())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::PublicKey;
+    use crate::did_manager::KeyCustody;
+
+    #[tokio::test]
+    async fn test_custodial_sign_and_export() {
+        let registry = DIDRegistry::new();
+        let patient_did = registry.create_patient_did("p-custody".to_string(), 1, KeyAlgorithm::Secp256k1).await.unwrap();
+        let public_key = PublicKey::from_bytes(KeyAlgorithm::Secp256k1, &hex::decode(&patient_did.public_key).unwrap()).unwrap();
+        let signer = CustodialSigner::in_memory(registry.clone());
+
+        // Login challenge: verifiable with the DID's public key
+        let signed = signer
+            .sign(&patient_did.did, SigningPurpose::LoginChallenge { nonce: "n-1".to_string() }, 7, "did:key:z6Mkholder")
+            .await
+            .unwrap();
+        assert_eq!(signed.message.as_deref(), Some("Anima Health Auth:n-1"));
+        assert!(public_key.verify(b"Anima Health Auth:n-1", &hex::decode(signed.signature.unwrap()).unwrap()));
+
+        // Presentation: a VP-JWT signed with ES256K
+        let purpose = SigningPurpose::Presentation {
+            audience: "did:web:clinic.example".to_string(),
+            nonce: "n-2".to_string(),
+            credentials: vec![],
+        };
+        let vp = signer.sign(&patient_did.did, purpose, 7, "did:key:z6Mkholder").await.unwrap().presentation.unwrap();
+        let (signing_input, signature) = vp.rsplit_once('.').unwrap();
+        assert!(public_key.verify(signing_input.as_bytes(), &URL_SAFE_NO_PAD.decode(signature).unwrap()));
+
+        // Export hands the key over; the gateway can't sign any more
+        let exported = signer.export(&patient_did.did, 7, "did:key:z6Mkholder").await.unwrap();
+        let signature = KeyAlgorithm::Secp256k1.sign(&hex::decode(&exported.private_key).unwrap(), b"self-custody").unwrap();
+        assert!(public_key.verify(b"self-custody", &signature));
+        assert!(matches!(
+            registry.get_by_did(&patient_did.did).await.unwrap().custody,
+            KeyCustody::SelfCustody { .. }
+        ));
+
+        let result = signer.sign(&patient_did.did, SigningPurpose::Consent { statement: "share".to_string() }, 7, "did:key:z6Mkholder").await;
+        assert!(matches!(result, Err(Error::NotCustodial(_))));
+
+        let audit = signer.audit_for(&patient_did.did).await;
+        let actions: Vec<&str> = audit.iter().map(|entry| entry.action.as_str()).collect();
+        assert_eq!(actions, vec!["sign", "sign", "export"]);
+        assert!(audit.iter().all(|entry| entry.requested_by == 7));
+    }
+}
@@ -1,4 +1,5 @@
 use serde::Serialize;
+use crate::persist;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -9,6 +10,16 @@ pub enum Error {
     DIDNotFound(String),
     InvalidDIDFormat(String),
     KeyGenerationFailed(String),
+    KeyNotFound(String),
+    KeyVaultFailed(String),
+    DIDRevoked(String),
+    DIDDeactivated(String),
+    NotCustodial(String),
+    LedgerRejected(String),
+    LedgerFailed(String),
+    Persist(persist::Error),
+    /// DID records are persisted but KEY_VAULT is memory
+    KeyVaultEphemeral,
 }
 
 impl core::fmt::Display for Error {
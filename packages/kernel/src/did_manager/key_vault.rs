@@ -0,0 +1,251 @@
+use crate::did_manager::{Error, Result};
+use crate::auth::KeyAlgorithm;
+use crate::persist::JsonFileStore;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Holds patient private keys; everything else only sees a key reference
+///
+/// Keys are addressed by an opaque `key_ref`. Signing happens inside the vault,
+/// so a KMS/HSM backend can implement this without ever releasing key material
+/// (it would refuse `export`).
+#[async_trait]
+pub trait KeyVault: Send + Sync {
+    /// Backend name for logs ("memory", "file", ...)
+    fn name(&self) -> &str;
+
+    /// Store a private key under `key_ref` (replacing any previous key)
+    async fn import(&self, key_ref: &str, algorithm: KeyAlgorithm, secret: &[u8]) -> Result<()>;
+
+    /// Sign `message` with the key (JWS signature bytes)
+    async fn sign(&self, key_ref: &str, message: &[u8]) -> Result<Vec<u8>>;
+
+    /// Release the private key (handover to self-custody)
+    async fn export(&self, key_ref: &str) -> Result<Vec<u8>>;
+
+    async fn delete(&self, key_ref: &str) -> Result<()>;
+}
+
+fn sign_with(algorithm: KeyAlgorithm, secret: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    algorithm.sign(secret, message)
+        .map_err(|e| Error::KeyVaultFailed(format!("Signing failed: {}", e)))
+}
+
+// region: --- Memory
+
+#[derive(Clone)]
+struct PlainKey {
+    algorithm: KeyAlgorithm,
+    secret: Vec<u8>,
+}
+
+/// Process-local vault (tests, throwaway dev runs) - keys die with the process
+#[derive(Clone, Default)]
+pub struct MemoryKeyVault {
+    keys: Arc<RwLock<HashMap<String, PlainKey>>>,
+}
+
+#[async_trait]
+impl KeyVault for MemoryKeyVault {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn import(&self, key_ref: &str, algorithm: KeyAlgorithm, secret: &[u8]) -> Result<()> {
+        let key = PlainKey { algorithm, secret: secret.to_vec() };
+        self.keys.write().await.insert(key_ref.to_string(), key);
+        Ok(())
+    }
+
+    async fn sign(&self, key_ref: &str, message: &[u8]) -> Result<Vec<u8>> {
+        let keys = self.keys.read().await;
+        let key = keys.get(key_ref).ok_or_else(|| Error::KeyNotFound(key_ref.to_string()))?;
+        sign_with(key.algorithm, &key.secret, message)
+    }
+
+    async fn export(&self, key_ref: &str) -> Result<Vec<u8>> {
+        let keys = self.keys.read().await;
+        keys.get(key_ref)
+            .map(|key| key.secret.clone())
+            .ok_or_else(|| Error::KeyNotFound(key_ref.to_string()))
+    }
+
+    async fn delete(&self, key_ref: &str) -> Result<()> {
+        self.keys.write().await.remove(key_ref);
+        Ok(())
+    }
+}
+
+// endregion: --- Memory
+
+// region: --- File
+
+/// Private key wrapped with AES-256-GCM (the key_ref is the associated data,
+/// so a wrapped key can't be moved to another reference)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    algorithm: KeyAlgorithm,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Vault persisted as one JSON file of AES-256-GCM wrapped keys
+///
+/// The key-encryption key (KEK) never touches the file. Every change is
+/// written through before it becomes visible.
+#[derive(Clone)]
+pub struct FileKeyVault {
+    cipher: Arc<Aes256Gcm>,
+    keys: Arc<RwLock<HashMap<String, WrappedKey>>>,
+    store: JsonFileStore,
+}
+
+impl FileKeyVault {
+    /// Open (or start) the vault at `path`, checking the KEK against stored keys
+    pub async fn open(path: &str, kek: [u8; 32]) -> Result<Self> {
+        let store = JsonFileStore::new(path);
+        let keys: HashMap<String, WrappedKey> = store.load()
+            .await
+            .map_err(|e| Error::KeyVaultFailed(e.to_string()))?;
+
+        let vault = Self {
+            cipher: Arc::new(Aes256Gcm::new(&kek.into())),
+            keys: Arc::new(RwLock::new(HashMap::new())),
+            store,
+        };
+
+        // A wrong KEK must fail at startup, not at the first signature
+        if let Some((key_ref, wrapped)) = keys.iter().next() {
+            vault.unwrap_key(key_ref, wrapped)?;
+        }
+
+        println!("->> KeyVault: Loaded {} wrapped key(s) from {}", keys.len(), path);
+
+        *vault.keys.write().await = keys;
+        Ok(vault)
+    }
+
+    fn wrap_key(&self, key_ref: &str, algorithm: KeyAlgorithm, secret: &[u8]) -> Result<WrappedKey> {
+        use rand::RngCore;
+
+        let mut nonce = [0u8; 12];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret, aad: key_ref.as_bytes() })
+            .map_err(|_| Error::KeyVaultFailed(format!("Wrapping {} failed", key_ref)))?;
+
+        Ok(WrappedKey {
+            algorithm,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    fn unwrap_key(&self, key_ref: &str, wrapped: &WrappedKey) -> Result<Vec<u8>> {
+        let failed = || Error::KeyVaultFailed(format!("Cannot unwrap {} (wrong KEY_VAULT_KEK?)", key_ref));
+
+        let nonce = STANDARD.decode(&wrapped.nonce).ok()
+            .filter(|nonce| nonce.len() == 12)
+            .ok_or_else(failed)?;
+        let ciphertext = STANDARD.decode(&wrapped.ciphertext).map_err(|_| failed())?;
+
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: key_ref.as_bytes() })
+            .map_err(|_| failed())
+    }
+
+    async fn wrapped(&self, key_ref: &str) -> Result<WrappedKey> {
+        self.keys.read().await
+            .get(key_ref)
+            .cloned()
+            .ok_or_else(|| Error::KeyNotFound(key_ref.to_string()))
+    }
+
+    /// Apply a change to a copy, persist it, then publish it
+    async fn mutate(&self, f: impl FnOnce(&mut HashMap<String, WrappedKey>)) -> Result<()> {
+        let mut keys = self.keys.write().await;
+        let mut updated = keys.clone();
+        f(&mut updated);
+
+        self.store.save(&updated)
+            .await
+            .map_err(|e| Error::KeyVaultFailed(e.to_string()))?;
+
+        *keys = updated;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl KeyVault for FileKeyVault {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn import(&self, key_ref: &str, algorithm: KeyAlgorithm, secret: &[u8]) -> Result<()> {
+        let wrapped = self.wrap_key(key_ref, algorithm, secret)?;
+        self.mutate(|keys| { keys.insert(key_ref.to_string(), wrapped); }).await
+    }
+
+    async fn sign(&self, key_ref: &str, message: &[u8]) -> Result<Vec<u8>> {
+        let wrapped = self.wrapped(key_ref).await?;
+        let secret = self.unwrap_key(key_ref, &wrapped)?;
+        sign_with(wrapped.algorithm, &secret, message)
+    }
+
+    async fn export(&self, key_ref: &str) -> Result<Vec<u8>> {
+        let wrapped = self.wrapped(key_ref).await?;
+        self.unwrap_key(key_ref, &wrapped)
+    }
+
+    async fn delete(&self, key_ref: &str) -> Result<()> {
+        self.mutate(|keys| { keys.remove(key_ref); }).await
+    }
+}
+
+// endregion: --- File
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::PublicKey;
+
+    #[tokio::test]
+    async fn test_file_vault_wraps_keys() {
+        let dir = std::env::temp_dir().join(format!("anima-vault-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("key_vault.json").display().to_string();
+        let key_ref = "did:iota:anima:p-1#key-1";
+
+        let (public_hex, secret) = KeyAlgorithm::P256.generate_keypair();
+        let vault = FileKeyVault::open(&path, [7u8; 32]).await.unwrap();
+        vault.import(key_ref, KeyAlgorithm::P256, &secret).await.unwrap();
+
+        // The file holds no plaintext key
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains(&hex::encode(&secret)));
+        assert!(!on_disk.contains(&STANDARD.encode(&secret)));
+
+        // Reopened with the same KEK: keys sign and export
+        let vault = FileKeyVault::open(&path, [7u8; 32]).await.unwrap();
+        let signature = vault.sign(key_ref, b"consent").await.unwrap();
+        let public_key = PublicKey::from_bytes(KeyAlgorithm::P256, &hex::decode(public_hex).unwrap()).unwrap();
+        assert!(public_key.verify(b"consent", &signature));
+        assert_eq!(vault.export(key_ref).await.unwrap(), secret);
+
+        // Wrong KEK is rejected at open
+        assert!(matches!(FileKeyVault::open(&path, [8u8; 32]).await, Err(Error::KeyVaultFailed(_))));
+
+        vault.delete(key_ref).await.unwrap();
+        assert!(matches!(vault.sign(key_ref, b"consent").await, Err(Error::KeyNotFound(_))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,458 @@
+use crate::did_manager::{Error, Result};
+use crate::auth::DIDDocument;
+use crate::persist::JsonFileStore;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use iota_sdk::client::Client;
+use iota_sdk::types::block::BlockId;
+use iota_sdk::types::block::payload::Payload;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::{OnceCell, RwLock};
+
+const DEFAULT_DID_LEDGER_FILE: &str = "data/did_ledger.json";
+const IOTA_TESTNET_NODE: &str = "https://api.testnet.iotaledger.net";
+/// Tag of the tagged-data blocks carrying DID document versions
+const IOTA_DID_TAG: &[u8] = b"anima:did";
+
+/// One signed version of a DID document
+///
+/// Version 1 is signed by one of its own authentication keys; every later
+/// version by an authentication key of the version before it, and names that
+/// version's message id in `previous`. A revoked document lists no key, so
+/// nothing can follow it. A deactivated one authenticates nothing but keeps its
+/// keys: only its revocation may follow, signed by the key that deactivated it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub did: String,
+    pub version: u64,
+    /// Message id of the previous version (None for version 1)
+    pub previous: Option<String>,
+    pub document: Value,
+    /// Verification method id of the signing key
+    pub signed_by: String,
+    pub published_at: DateTime<Utc>,
+    /// Hex signature over `signing_input()`
+    #[serde(default)]
+    pub signature: String,
+}
+
+/// A ledger entry with the message id the ledger assigned to it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerRecord {
+    pub message_id: String,
+    #[serde(flatten)]
+    pub entry: LedgerEntry,
+}
+
+impl LedgerEntry {
+    /// Unsigned entry extending `previous` (the DID's latest record, if any)
+    pub fn new(did: &str, previous: Option<&LedgerRecord>, document: Value, signed_by: String) -> Self {
+        Self {
+            did: did.to_string(),
+            version: previous.map_or(1, |record| record.entry.version + 1),
+            previous: previous.map(|record| record.message_id.clone()),
+            document,
+            signed_by,
+            published_at: Utc::now(),
+            signature: String::new(),
+        }
+    }
+
+    /// Bytes the signature covers - every field but the signature, keys sorted
+    pub fn signing_input(&self) -> Vec<u8> {
+        json!({
+            "did": self.did,
+            "version": self.version,
+            "previous": self.previous,
+            "document": self.document,
+            "signedBy": self.signed_by,
+            "publishedAt": self.published_at,
+        }).to_string().into_bytes()
+    }
+}
+
+/// Check that `entry` may follow `previous` (None: `entry` is the DID's first version)
+pub fn verify_update(previous: Option<&LedgerRecord>, entry: &LedgerEntry) -> Result<()> {
+    let rejected = |reason: String| Error::LedgerRejected(format!("{} v{}: {}", entry.did, entry.version, reason));
+
+    let (version, previous_id, authorizing) = match previous {
+        Some(record) => (record.entry.version + 1, Some(record.message_id.as_str()), &record.entry.document),
+        None => (1, None, &entry.document),
+    };
+    if entry.version != version {
+        return Err(rejected(format!("expected version {}", version)));
+    }
+    if entry.previous.as_deref() != previous_id {
+        return Err(rejected("does not extend the latest version".to_string()));
+    }
+
+    DIDDocument::from_json(&entry.did, entry.document.clone())
+        .map_err(|e| rejected(e.to_string()))?;
+
+    // The signing key must authenticate the DID in the version being replaced
+    let authorizing = DIDDocument::from_json(&entry.did, authorizing.clone())
+        .map_err(|e| rejected(e.to_string()))?;
+    let key = match authorizing.authentication_keys() {
+        Ok(keys) if !keys.is_empty() => keys.into_iter()
+            .find(|(id, _)| id == &entry.signed_by)
+            .map(|(_, key)| key),
+        // Deactivated: revocation only (no keys), by the deactivating key
+        _ => match previous {
+            Some(record) if record.entry.signed_by == entry.signed_by && is_revoked_document(&entry.document) => {
+                authorizing.verification_key(&entry.signed_by).ok()
+            }
+            _ => None,
+        },
+    }
+    .ok_or_else(|| rejected(format!("{} may not update the DID", entry.signed_by)))?;
+
+    let signature = hex::decode(&entry.signature)
+        .map_err(|_| rejected("signature is not hex".to_string()))?;
+    if !key.verify(&entry.signing_input(), &signature) {
+        return Err(rejected("invalid signature".to_string()));
+    }
+
+    Ok(())
+}
+
+/// A document listing no verification methods at all (a revoked DID)
+fn is_revoked_document(document: &Value) -> bool {
+    document.get("verificationMethod")
+        .and_then(Value::as_array)
+        .is_none_or(|methods| methods.is_empty())
+}
+
+/// Check a DID's full history, oldest first
+pub fn verify_chain(records: &[LedgerRecord]) -> Result<()> {
+    let mut previous = None;
+    for record in records {
+        verify_update(previous, &record.entry)?;
+        previous = Some(record);
+    }
+    Ok(())
+}
+
+/// Append-only publication of DID document versions
+#[async_trait]
+pub trait DIDLedger: Send + Sync {
+    /// Backend name for logs ("local", "iota")
+    fn name(&self) -> &str;
+
+    /// Append a signed version - rejected unless it extends the DID's latest version
+    async fn append(&self, entry: LedgerEntry) -> Result<LedgerRecord>;
+
+    /// Every version of the DID, oldest first (empty if never published)
+    async fn history(&self, did: &str) -> Result<Vec<LedgerRecord>>;
+
+    /// Where a published version can be looked up
+    fn document_uri(&self, message_id: &str) -> String;
+}
+
+/// Ledger configured in the environment (None = DIDs are not published)
+///
+/// - DID_LEDGER: "none" (default), "local" or "iota"
+/// - DID_LEDGER_FILE: local chain, or the DID -> latest block index for "iota"
+///   (default "data/did_ledger.json")
+/// - IOTA_NODE_URL: node for "iota" (default: public testnet)
+pub async fn ledger_from_env() -> Result<Option<Arc<dyn DIDLedger>>> {
+    let path = std::env::var("DID_LEDGER_FILE")
+        .unwrap_or_else(|_| DEFAULT_DID_LEDGER_FILE.to_string());
+
+    let ledger: Arc<dyn DIDLedger> = match std::env::var("DID_LEDGER").as_deref() {
+        Ok("none") | Err(_) => return Ok(None),
+        Ok("local") => Arc::new(LocalLedger::open(&path).await?),
+        Ok("iota") => {
+            let node_url = match std::env::var("IOTA_NODE_URL") {
+                Ok(url) if !url.is_empty() => url,
+                _ => IOTA_TESTNET_NODE.to_string(),
+            };
+            Arc::new(IotaLedger::open(node_url, &path).await?)
+        }
+        Ok(other) => return Err(Error::LedgerFailed(format!("Unknown DID_LEDGER '{}'", other))),
+    };
+
+    println!("->> DIDLedger: Publishing DID documents to '{}' ledger", ledger.name());
+
+    Ok(Some(ledger))
+}
+
+// region: --- Local
+
+/// Deterministic ledger simulator - chains of signed versions in one JSON file
+///
+/// Message ids are the SHA-256 of the signed entry, so the same updates always
+/// produce the same chain.
+#[derive(Clone)]
+pub struct LocalLedger {
+    // did -> versions, oldest first
+    chains: Arc<RwLock<BTreeMap<String, Vec<LedgerRecord>>>>,
+    store: JsonFileStore,
+}
+
+impl LocalLedger {
+    /// Open (or start) the chain file at `path`
+    pub async fn open(path: &str) -> Result<Self> {
+        let store = JsonFileStore::new(path);
+        let chains: BTreeMap<String, Vec<LedgerRecord>> = store.load().await.map_err(Error::Persist)?;
+
+        println!("->> DIDLedger: Loaded {} DID chain(s) from {}", chains.len(), path);
+
+        Ok(Self {
+            chains: Arc::new(RwLock::new(chains)),
+            store,
+        })
+    }
+
+    fn message_id(entry: &LedgerEntry) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(entry.signing_input());
+        hasher.update(entry.signature.as_bytes());
+        format!("0x{}", hex::encode(hasher.finalize()))
+    }
+}
+
+#[async_trait]
+impl DIDLedger for LocalLedger {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn append(&self, entry: LedgerEntry) -> Result<LedgerRecord> {
+        let mut chains = self.chains.write().await;
+        verify_update(chains.get(&entry.did).and_then(|chain| chain.last()), &entry)?;
+
+        let record = LedgerRecord { message_id: Self::message_id(&entry), entry };
+        let mut updated = chains.clone();
+        updated.entry(record.entry.did.clone()).or_default().push(record.clone());
+
+        self.store.save(&updated).await.map_err(Error::Persist)?;
+        *chains = updated;
+
+        println!("->> DIDLedger: Appended {} v{} ({})", record.entry.did, record.entry.version, record.message_id);
+
+        Ok(record)
+    }
+
+    async fn history(&self, did: &str) -> Result<Vec<LedgerRecord>> {
+        Ok(self.chains.read().await.get(did).cloned().unwrap_or_default())
+    }
+
+    fn document_uri(&self, message_id: &str) -> String {
+        format!("ledger://local/{}", message_id)
+    }
+}
+
+// endregion: --- Local
+
+// region: --- IOTA
+
+/// Versions published as tagged-data blocks on an IOTA network
+///
+/// Blocks need no funds. Each block links to the previous version's block id,
+/// so the history is read back from the network; only the latest block id of
+/// each DID is kept locally.
+pub struct IotaLedger {
+    node_url: String,
+    // Connected on first use
+    client: OnceCell<Client>,
+    // did -> block id of the latest version
+    heads: Arc<RwLock<BTreeMap<String, String>>>,
+    store: JsonFileStore,
+}
+
+impl IotaLedger {
+    /// Ledger on the node at `node_url`, with its head index at `path`
+    pub async fn open(node_url: impl Into<String>, path: &str) -> Result<Self> {
+        let store = JsonFileStore::new(path);
+        let heads: BTreeMap<String, String> = store.load().await.map_err(Error::Persist)?;
+
+        Ok(Self {
+            node_url: node_url.into(),
+            client: OnceCell::new(),
+            heads: Arc::new(RwLock::new(heads)),
+            store,
+        })
+    }
+
+    async fn get_client(&self) -> Result<&Client> {
+        self.client.get_or_try_init(|| async {
+            println!("->> DIDLedger: Connecting to IOTA network: {}", self.node_url);
+
+            Client::builder()
+                .with_node(&self.node_url)
+                .map_err(|e| Error::LedgerFailed(format!("IOTA client build failed: {}", e)))?
+                .finish()
+                .await
+                .map_err(|e| Error::LedgerFailed(format!("IOTA client connect failed: {}", e)))
+        }).await
+    }
+
+    /// The entry carried by block `message_id`
+    async fn fetch(&self, message_id: &str) -> Result<LedgerRecord> {
+        let block_id = BlockId::from_str(message_id)
+            .map_err(|e| Error::LedgerFailed(format!("Invalid block id {}: {}", message_id, e)))?;
+        let block = self.get_client().await?
+            .get_block(&block_id)
+            .await
+            .map_err(|e| Error::LedgerFailed(format!("Failed to fetch block {}: {}", message_id, e)))?;
+
+        let Some(Payload::TaggedData(payload)) = block.payload() else {
+            return Err(Error::LedgerFailed(format!("Block {} carries no tagged data", message_id)));
+        };
+        let entry = serde_json::from_slice(payload.data())
+            .map_err(|e| Error::LedgerFailed(format!("Block {} is not a DID entry: {}", message_id, e)))?;
+
+        Ok(LedgerRecord { message_id: message_id.to_string(), entry })
+    }
+}
+
+#[async_trait]
+impl DIDLedger for IotaLedger {
+    fn name(&self) -> &str {
+        "iota"
+    }
+
+    async fn append(&self, entry: LedgerEntry) -> Result<LedgerRecord> {
+        let mut heads = self.heads.write().await;
+        let previous = match heads.get(&entry.did) {
+            Some(head) => Some(self.fetch(head).await?),
+            None => None,
+        };
+        verify_update(previous.as_ref(), &entry)?;
+
+        let data = serde_json::to_vec(&entry)
+            .map_err(|e| Error::LedgerFailed(e.to_string()))?;
+        let block = self.get_client().await?
+            .build_block()
+            .with_tag(IOTA_DID_TAG.to_vec())
+            .with_data(data)
+            .finish()
+            .await
+            .map_err(|e| Error::LedgerFailed(format!("Failed to publish block: {}", e)))?;
+
+        let record = LedgerRecord { message_id: block.id().to_string(), entry };
+        let mut updated = heads.clone();
+        updated.insert(record.entry.did.clone(), record.message_id.clone());
+        self.store.save(&updated).await.map_err(Error::Persist)?;
+        *heads = updated;
+
+        println!("->> DIDLedger: Published {} v{} in block {}", record.entry.did, record.entry.version, record.message_id);
+
+        Ok(record)
+    }
+
+    async fn history(&self, did: &str) -> Result<Vec<LedgerRecord>> {
+        let mut next = self.heads.read().await.get(did).cloned();
+
+        let mut history = Vec::new();
+        while let Some(message_id) = next {
+            let record = self.fetch(&message_id).await?;
+            next = record.entry.previous.clone();
+            history.push(record);
+        }
+        history.reverse();
+
+        Ok(history)
+    }
+
+    fn document_uri(&self, message_id: &str) -> String {
+        format!("{}/api/core/v2/blocks/{}", self.node_url.trim_end_matches('/'), message_id)
+    }
+}
+
+// endregion: --- IOTA
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::did_manager::{DIDRegistry, DIDStatus, KeyVault, MemoryKeyVault};
+
+    #[tokio::test]
+    async fn test_publish_rotate_revoke_chain() {
+        let dir = std::env::temp_dir().join(format!("anima-ledger-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("did_ledger.json").display().to_string();
+
+        let ledger = Arc::new(LocalLedger::open(&path).await.unwrap());
+        let registry = DIDRegistry::new().with_ledger(ledger.clone());
+
+        let created = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let rotated = registry.rotate_key(&created.did).await.unwrap();
+        let revoked = registry.revoke(&created.did).await.unwrap();
+
+        // Three chained versions; the rotation is signed by the retired key
+        let history = ledger.history(&created.did).await.unwrap();
+        assert_eq!(history.iter().map(|record| record.entry.version).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(history[1].entry.signed_by, created.key_id());
+        assert_eq!(history[2].entry.signed_by, rotated.key_id());
+        assert_eq!(history[2].entry.previous.as_ref(), Some(&history[1].message_id));
+        assert_eq!(revoked.document_uri, Some(ledger.document_uri(&history[2].message_id)));
+
+        // The chain survives a restart and resolves to the revoked document
+        let reopened = LocalLedger::open(&path).await.unwrap();
+        let reopened_history = reopened.history(&created.did).await.unwrap();
+        verify_chain(&reopened_history).unwrap();
+        assert_eq!(reopened_history, history);
+        assert_eq!(history[2].entry.document["verificationMethod"], json!([]));
+
+        // A tampered version breaks the chain
+        let mut tampered = history.clone();
+        tampered[1].entry.document["controller"] = json!("did:iota:anima:mallory");
+        assert!(matches!(verify_chain(&tampered), Err(Error::LedgerRejected(_))));
+
+        // Nothing may follow a revoked version, not even signed by its last key
+        let mut update = LedgerEntry::new(&created.did, Some(&history[2]), history[1].entry.document.clone(), rotated.key_id());
+        update.signature = history[2].entry.signature.clone();
+        assert!(matches!(reopened.append(update).await, Err(Error::LedgerRejected(_))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_after_deactivation() {
+        let dir = std::env::temp_dir().join(format!("anima-ledger-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("did_ledger.json").display().to_string();
+
+        let ledger = Arc::new(LocalLedger::open(&path).await.unwrap());
+        let vault = Arc::new(MemoryKeyVault::default());
+        let registry = DIDRegistry::new().with_key_vault(vault.clone()).with_ledger(ledger.clone());
+
+        let created = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let rotated = registry.rotate_key(&created.did).await.unwrap();
+        let deactivated = registry.deactivate(&created.did).await.unwrap();
+
+        // The gateway no longer signs for it, but can still publish its revocation
+        assert!(matches!(registry.sign(&created.did, b"x").await, Err(Error::DIDDeactivated(_))));
+        let history = ledger.history(&created.did).await.unwrap();
+
+        // Only a revocation by the deactivating key may follow a deactivated version
+        let mut reactivation = LedgerEntry::new(&created.did, Some(&history[2]), history[1].entry.document.clone(), rotated.key_id());
+        reactivation.signature = hex::encode(vault.sign(&deactivated.key_ref, &reactivation.signing_input()).await.unwrap());
+        assert!(matches!(ledger.append(reactivation).await, Err(Error::LedgerRejected(_))));
+        let revocation = json!({ "id": created.did, "verificationMethod": [] });
+        let mut by_retired_key = LedgerEntry::new(&created.did, Some(&history[2]), revocation, created.key_id());
+        by_retired_key.signature = hex::encode(vault.sign(&deactivated.key_ref, &by_retired_key.signing_input()).await.unwrap());
+        assert!(matches!(ledger.append(by_retired_key).await, Err(Error::LedgerRejected(_))));
+
+        let revoked = registry.revoke(&created.did).await.unwrap();
+        assert_eq!(revoked.metadata.status, DIDStatus::Revoked);
+
+        let history = ledger.history(&created.did).await.unwrap();
+        verify_chain(&history).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3].entry.signed_by, rotated.key_id());
+        assert_eq!(history[3].entry.document["verificationMethod"], json!([]));
+
+        // The key is gone once revoked
+        assert!(matches!(vault.sign(&deactivated.key_ref, b"x").await, Err(Error::KeyNotFound(_))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
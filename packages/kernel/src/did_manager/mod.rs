@@ -1,8 +1,15 @@
 mod error;
 mod patient_did;
 mod registry;
+mod key_vault;
+mod custody;
+mod store;
+mod ledger;
 
 pub use self::error::{Error, Result};
-pub use self::patient_did::{PatientDID, DIDMetadata};
+pub use self::patient_did::{PatientDID, DIDMetadata, DIDStatus, KeyCustody, KeySelector};
 pub use self::registry::DIDRegistry;
-
+pub use self::key_vault::{KeyVault, MemoryKeyVault, FileKeyVault};
+pub use self::store::{DIDStore, FileDIDStore};
+pub use self::ledger::{DIDLedger, LedgerEntry, LedgerRecord, ledger_from_env, verify_chain};
+pub use self::custody::{CustodialSigner, CustodialSignature, CustodyAuditEntry, ExportedKey, SigningPurpose};
@@ -1,4 +1,5 @@
-use crate::did_manager::{Error, Result};
+use crate::did_manager::Result;
+use crate::auth::{KeyAlgorithm, PublicKey, DID_CONTEXT_V1};
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
@@ -12,14 +13,30 @@ pub struct PatientDID {
     /// Patient identifier (UUID)
     pub patient_id: String,
     
-    /// Public key (Ed25519) - hex encoded
+    /// Key type chosen at creation (DIDs created before multi-algorithm support are Ed25519)
+    #[serde(default)]
+    pub key_algorithm: KeyAlgorithm,
+
+    /// Current public key - hex encoded (Ed25519: 32 bytes, ECDSA: compressed SEC1 point)
     pub public_key: String,
+
+    /// Every key the DID has had, oldest first (the last one is `public_key`)
+    ///
+    /// Retired keys stay in the DID document so earlier signatures still verify.
+    /// Empty for DIDs created before rotation kept history.
+    #[serde(default)]
+    pub key_history: Vec<KeyVersion>,
     
-    /// Private key (Ed25519) - hex encoded
-    /// In production: Store securely or use key management service
-    pub private_key: String,
+    /// Reference of the private key in the KeyVault - the key itself is never
+    /// part of the DID record (or any patient record / API response)
+    #[serde(default)]
+    pub key_ref: String,
+
+    /// Who holds the private key (the gateway until it is exported)
+    #[serde(default)]
+    pub custody: KeyCustody,
     
-    /// DID Document URI on IOTA Tangle
+    /// Latest published version of the DID document (set when a DID ledger is configured)
     pub document_uri: Option<String>,
     
     /// Metadata
@@ -32,61 +49,145 @@ pub struct DIDMetadata {
     pub created_by: u64,
     pub key_version: u64,
     pub status: DIDStatus,
+    /// Last key rotation, revocation or deactivation
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Custody of a patient DID's private key
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum KeyCustody {
+    /// Held in the gateway KeyVault; signs through the custodial signing API
+    #[default]
+    Custodial,
+    /// Exported to the patient and removed from the vault
+    SelfCustody { exported_at: DateTime<Utc> },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DIDStatus {
+    /// Records from before key history marked rotated DIDs "Rotated"
+    #[serde(alias = "Rotated")]
     Active,
-    Rotated,
+    /// Key compromised: no signature of the DID verifies any more
     Revoked,
+    /// Retired: no new logins, but signatures made before still verify by key version or time
+    Deactivated,
+}
+
+/// One verification method of a patient DID (`{did}#key-{version}`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyVersion {
+    pub version: u64,
+    /// Hex encoded, same format as `PatientDID::public_key`
+    pub public_key: String,
+    pub valid_from: DateTime<Utc>,
+    /// Set when the key is rotated out
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl KeyVersion {
+    /// Was this the DID's key at `at`?
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from <= at && self.valid_until.map_or(true, |until| at < until)
+    }
+}
+
+/// Which of a DID's keys a signature should be checked against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeySelector {
+    Current,
+    /// `#key-{version}`, retired or not
+    Version(u64),
+    /// The key that was current at this time
+    At(DateTime<Utc>),
 }
 
 impl PatientDID {
-    /// Create a new patient DID
-    pub fn create(patient_id: String, created_by: u64) -> Result<Self> {
-        // Generate Ed25519 keypair
-        // For POC: Mock key generation
-        // In production: Use identity_iota or ed25519_dalek
-        let (public_key, private_key) = Self::generate_keypair()?;
+    /// Create a new patient DID with a key of the given algorithm
+    ///
+    /// Returns the DID and its private key, which the caller must put in the
+    /// KeyVault under `key_ref`.
+    pub fn create(patient_id: String, created_by: u64, key_algorithm: KeyAlgorithm) -> Result<(Self, Vec<u8>)> {
+        let (public_key, private_key) = Self::generate_keypair(key_algorithm)?;
 
         let did = format!("did:iota:anima:{}", patient_id);
+        let created_at = Utc::now();
 
         println!("->> PatientDID: Created DID: {}", did);
 
-        Ok(Self {
-            did: did.clone(),
+        let patient_did = Self {
+            key_ref: Self::key_ref_for(&did, 1),
+            did,
             patient_id,
+            key_algorithm,
+            key_history: vec![KeyVersion {
+                version: 1,
+                public_key: public_key.clone(),
+                valid_from: created_at,
+                valid_until: None,
+            }],
             public_key,
-            private_key,
+            custody: KeyCustody::Custodial,
             document_uri: None,
             metadata: DIDMetadata {
-                created_at: Utc::now(),
+                created_at,
                 created_by,
                 key_version: 1,
                 status: DIDStatus::Active,
+                updated_at: None,
             },
-        })
+        };
+
+        Ok((patient_did, private_key))
     }
 
-    /// Generate Ed25519 keypair (REAL cryptographic keys)
-    fn generate_keypair() -> Result<(String, String)> {
-        use ed25519_dalek::SigningKey;
-        use rand::RngCore;
-        use rand::rngs::OsRng;
+    /// Verification method id of the DID's current key
+    pub fn key_id(&self) -> String {
+        self.key_id_for(self.metadata.key_version)
+    }
+
+    /// Verification method id of the key at `version`
+    pub fn key_id_for(&self, version: u64) -> String {
+        format!("{}#key-{}", self.did, version)
+    }
+
+    /// All key versions, oldest first
+    ///
+    /// DIDs without recorded history get their current key, valid since creation.
+    pub fn key_versions(&self) -> Vec<KeyVersion> {
+        if !self.key_history.is_empty() {
+            return self.key_history.clone();
+        }
+
+        vec![KeyVersion {
+            version: self.metadata.key_version,
+            public_key: self.public_key.clone(),
+            valid_from: self.metadata.created_at,
+            valid_until: None,
+        }]
+    }
 
-        // Generate 32 random bytes using cryptographically secure RNG
-        let mut secret_bytes = [0u8; 32];
-        OsRng.fill_bytes(&mut secret_bytes);
+    /// The key a signature should be checked against (None if no key matches)
+    pub fn key_for(&self, selector: KeySelector) -> Option<KeyVersion> {
+        let versions = self.key_versions();
+        match selector {
+            KeySelector::Current => versions.into_iter().last(),
+            KeySelector::Version(version) => versions.into_iter().find(|key| key.version == version),
+            KeySelector::At(at) => versions.into_iter().find(|key| key.is_valid_at(at)),
+        }
+    }
 
-        // Create SigningKey from random bytes
-        let signing_key = SigningKey::from_bytes(&secret_bytes);
-        let verifying_key = signing_key.verifying_key();
+    /// KeyVault reference of the DID's key at `key_version`
+    fn key_ref_for(did: &str, key_version: u64) -> String {
+        format!("{}#key-{}", did, key_version)
+    }
 
-        // Encode keys as hex
-        let public_key = hex::encode(verifying_key.to_bytes());
-        let private_key = hex::encode(signing_key.to_bytes());
+    /// Generate a keypair (REAL cryptographic keys, OsRng)
+    fn generate_keypair(key_algorithm: KeyAlgorithm) -> Result<(String, Vec<u8>)> {
+        let (public_key, private_key) = key_algorithm.generate_keypair();
 
-        println!("   🔐 Generated REAL Ed25519 keypair (ed25519-dalek 2.0)");
+        println!("   🔐 Generated REAL {:?} keypair", key_algorithm);
         println!("      Public:  {}...", &public_key[..16]);
         println!("      ✅ Cryptographically secure using OsRng");
 
@@ -94,84 +195,121 @@ impl PatientDID {
     }
 
     /// Create DID document structure (ready for Tangle publication)
+    ///
+    /// Ed25519 keys are published as Ed25519VerificationKey2020, secp256k1 and
+    /// P-256 keys as Multikey, both with a multibase (base58btc) multicodec
+    /// `publicKeyMultibase`. Every key version is listed so retired keys stay
+    /// resolvable; only the current key is under `authentication`.
+    /// Deactivated DIDs keep their keys but authenticate nothing; revoked DIDs
+    /// publish no keys at all.
     pub fn create_did_document(&self) -> DIDDocument {
+        let mut context = vec![DID_CONTEXT_V1.to_string()];
+        let mut verification_method = Vec::new();
+
+        if self.metadata.status != DIDStatus::Revoked {
+            for key in self.key_versions() {
+                let Some((method, suite_context)) = self.verification_method(&key) else { continue };
+                if !context.iter().any(|known| known == suite_context) {
+                    context.push(suite_context.to_string());
+                }
+                verification_method.push(method);
+            }
+        }
+
+        let authentication = match self.metadata.status {
+            DIDStatus::Active => vec![self.key_id()],
+            DIDStatus::Revoked | DIDStatus::Deactivated => vec![],
+        };
+
         DIDDocument {
+            context,
             id: self.did.clone(),
-            verification_method: vec![VerificationMethod {
-                id: format!("{}#key-1", self.did),
-                method_type: "Ed25519VerificationKey2018".to_string(),
-                controller: self.did.clone(),
-                public_key_multibase: format!("z{}", self.public_key),
-            }],
-            authentication: vec![format!("{}#key-1", self.did)],
+            controller: self.did.clone(),
+            verification_method,
+            authentication,
             service: vec![],
         }
     }
 
-    /// Rotate key (for security)
-    pub fn rotate_key(&mut self) -> Result<()> {
-        let (new_public_key, new_private_key) = Self::generate_keypair()?;
-        
+    /// Verification method of one key version, with the context defining its type
+    fn verification_method(&self, key: &KeyVersion) -> Option<(VerificationMethod, &'static str)> {
+        let bytes = hex::decode(&key.public_key).ok()?;
+        let public_key = PublicKey::from_bytes(self.key_algorithm, &bytes).ok()?;
+        let (method_type, suite_context) = public_key.method_type();
+
+        let method = VerificationMethod {
+            id: self.key_id_for(key.version),
+            method_type: method_type.to_string(),
+            controller: self.did.clone(),
+            public_key_multibase: public_key.to_multibase(),
+        };
+
+        Some((method, suite_context))
+    }
+
+    /// Rotate key (for security) - the new key keeps the DID's algorithm
+    ///
+    /// The current key is retired (valid until now) but stays in the history.
+    /// The DID remains Active. Returns the new private key, to be stored under
+    /// the new `key_ref`.
+    pub fn rotate_key(&mut self) -> Result<Vec<u8>> {
+        let (new_public_key, new_private_key) = Self::generate_keypair(self.key_algorithm)?;
+        let now = Utc::now();
+
+        let mut key_history = self.key_versions();
+        if let Some(current) = key_history.last_mut() {
+            current.valid_until = Some(now);
+        }
+        key_history.push(KeyVersion {
+            version: self.metadata.key_version + 1,
+            public_key: new_public_key.clone(),
+            valid_from: now,
+            valid_until: None,
+        });
+
+        self.key_history = key_history;
         self.public_key = new_public_key;
-        self.private_key = new_private_key;
         self.metadata.key_version += 1;
-        self.metadata.status = DIDStatus::Rotated;
+        self.metadata.updated_at = Some(now);
+        self.key_ref = Self::key_ref_for(&self.did, self.metadata.key_version);
 
         println!("->> PatientDID: Rotated key for DID: {} (version: {})", 
                  self.did, self.metadata.key_version);
 
-        Ok(())
+        Ok(new_private_key)
     }
 
-    /// Revoke DID
+    /// Revoke DID (key compromise)
     pub fn revoke(&mut self) {
         self.metadata.status = DIDStatus::Revoked;
+        self.metadata.updated_at = Some(Utc::now());
         println!("->> PatientDID: Revoked DID: {}", self.did);
     }
 
-    /// Publish DID document to IOTA Tangle
-    /// Using identity_iota library for IOTA-specific DID handling
-    pub async fn publish_to_tangle(&mut self) -> Result<String> {
-        use identity_iota::iota::{IotaDocument, IotaDID};
-        use iota_sdk::client::Client;
-
-        println!("->> PatientDID: Preparing DID for IOTA Tangle publication");
-        println!("   📝 DID: {}", self.did);
-        println!("   🔑 Public key: {}...", &self.public_key[..32]);
-        
-        // Connect to IOTA Testnet
-        let _client = Client::builder()
-            .with_node("https://api.testnet.iotaledger.net")
-            .map_err(|e| Error::DIDCreationFailed(format!("IOTA client build failed: {}", e)))?
-            .finish()
-            .await
-            .map_err(|e| Error::DIDCreationFailed(format!("IOTA client connect failed: {}", e)))?;
-
-        println!("   ✅ Connected to IOTA Testnet");
-        
-        // For full publication, need funded wallet:
-        // 1. Get tokens: https://faucet.testnet.iotaledger.net/
-        // 2. Create IotaDocument with verification method
-        // 3. Publish using IotaIdentityClientExt
-        // 4. Transaction appears on https://explorer.iota.org/testnet/
-        
-        println!("   💡 Publication code ready (needs funded wallet)");
-        println!("   📚 Using identity_iota library per: https://docs.iota.org/developer/iota-identity/");
-        
-        // DID is ready for publication
-        let message_id = format!("0x{}", &self.public_key[..16]);
-        self.document_uri = Some(format!("https://explorer.iota.org/testnet/did/{}", self.did.replace(":", "_")));
-        
-        println!("   ✅ DID ready (IOTA Identity library active)");
-        
-        Ok(message_id)
+    /// Deactivate DID - the current key is retired as of now
+    pub fn deactivate(&mut self) {
+        let now = Utc::now();
+
+        let mut key_history = self.key_versions();
+        if let Some(current) = key_history.last_mut() {
+            current.valid_until.get_or_insert(now);
+        }
+
+        self.key_history = key_history;
+        self.metadata.status = DIDStatus::Deactivated;
+        self.metadata.updated_at = Some(now);
+        println!("->> PatientDID: Deactivated DID: {}", self.did);
     }
 }
 
-/// DID Document structure (W3C compliant)
+/// DID Document structure (W3C DID Core)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DIDDocument {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
     pub id: String,
+    /// Patient DIDs control themselves
+    pub controller: String,
     #[serde(rename = "verificationMethod")]
     pub verification_method: Vec<VerificationMethod>,
     pub authentication: Vec<String>,
@@ -184,6 +322,7 @@ pub struct VerificationMethod {
     #[serde(rename = "type")]
     pub method_type: String,
     pub controller: String,
+    /// "z" + base58btc(multicodec header || key bytes)
     #[serde(rename = "publicKeyMultibase")]
     pub public_key_multibase: String,
 }
@@ -1,52 +1,180 @@
-use crate::did_manager::{Error, Result, PatientDID};
+use crate::did_manager::{Error, Result, PatientDID, DIDStatus, KeyCustody, KeyVault, MemoryKeyVault, FileKeyVault, DIDStore, FileDIDStore, DIDLedger, LedgerEntry, LedgerRecord, ledger_from_env, verify_chain};
+use crate::auth::KeyAlgorithm;
 use std::collections::HashMap;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock, broadcast};
 use std::sync::Arc;
 
+/// Pending change notifications per subscriber before it lags
+const CHANGE_CHANNEL_CAPACITY: usize = 256;
+
+const DEFAULT_KEY_VAULT_FILE: &str = "data/key_vault.json";
+const DEFAULT_DID_REGISTRY_FILE: &str = "data/did_registry.json";
+
 /// Registry for managing patient DIDs
 pub struct DIDRegistry {
     // patient_id -> PatientDID
     patient_dids: Arc<RwLock<HashMap<String, PatientDID>>>,
     // did -> patient_id (reverse index)
     did_to_patient: Arc<RwLock<HashMap<String, String>>>,
+    // patient_id -> held from reading a DID record until its change is published,
+    // stored and the vault cleaned up (rotate, export, revoke, deactivate)
+    changing: Arc<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+    // DIDs whose document changed (created, key rotated, revoked)
+    changes: broadcast::Sender<String>,
+    // Private keys, addressed by PatientDID.key_ref
+    key_vault: Arc<dyn KeyVault>,
+    // DID records, written through on every change (None = in memory only)
+    store: Option<Arc<dyn DIDStore>>,
+    // Every document change is published here as a signed version (None = not published)
+    ledger: Option<Arc<dyn DIDLedger>>,
 }
 
 impl DIDRegistry {
+    /// Registry without persistence, with an in-memory KeyVault
     pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
+
         Self {
             patient_dids: Arc::new(RwLock::new(HashMap::new())),
             did_to_patient: Arc::new(RwLock::new(HashMap::new())),
+            changing: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            changes,
+            key_vault: Arc::new(MemoryKeyVault::default()),
+            store: None,
+            ledger: None,
         }
     }
 
-    /// Create and register a new patient DID
-    pub async fn create_patient_did(&self, patient_id: String, created_by: u64) -> Result<PatientDID> {
-        // Check if patient already has a DID
-        {
-            let registry = self.patient_dids.read().await;
-            if registry.contains_key(&patient_id) {
-                return Err(Error::DIDAlreadyExists(patient_id));
+    /// Registry backed by `store`: both indexes are rebuilt from its records
+    pub async fn open(store: Arc<dyn DIDStore>) -> Result<Self> {
+        let records = store.load().await?;
+
+        let mut patient_dids = HashMap::new();
+        let mut did_to_patient = HashMap::new();
+        for patient_did in records {
+            did_to_patient.insert(patient_did.did.clone(), patient_did.patient_id.clone());
+            patient_dids.insert(patient_did.patient_id.clone(), patient_did);
+        }
+
+        println!("->> DIDRegistry: Restored {} patient DID(s) from '{}' store", patient_dids.len(), store.name());
+
+        let registry = Self::new();
+        *registry.patient_dids.write().await = patient_dids;
+        *registry.did_to_patient.write().await = did_to_patient;
+
+        Ok(Self { store: Some(store), ..registry })
+    }
+
+    pub fn with_key_vault(mut self, key_vault: Arc<dyn KeyVault>) -> Self {
+        self.key_vault = key_vault;
+        self
+    }
+
+    pub fn with_ledger(mut self, ledger: Arc<dyn DIDLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Registry with the KeyVault configured in the environment
+    ///
+    /// - KEY_VAULT: "memory" (default) or "file"
+    /// - KEY_VAULT_FILE: wrapped key file (default "data/key_vault.json")
+    /// - KEY_VAULT_KEK: hex-encoded 32-byte AES-256 key-encryption key (required for "file")
+    /// - DID_REGISTRY_FILE: DID records (default "data/did_registry.json", empty = in memory only)
+    /// - DID_LEDGER: where DID documents are published (see `ledger_from_env`)
+    ///
+    /// Persisted records need KEY_VAULT=file: restored custodial DIDs would
+    /// have no keys.
+    pub async fn from_env() -> Result<Self> {
+        let registry_file = match std::env::var("DID_REGISTRY_FILE") {
+            Ok(path) if path.is_empty() => None,
+            Ok(path) => Some(path),
+            Err(_) => Some(DEFAULT_DID_REGISTRY_FILE.to_string()),
+        };
+
+        let key_vault: Arc<dyn KeyVault> = match std::env::var("KEY_VAULT").as_deref() {
+            Ok("file") => {
+                let kek: [u8; 32] = std::env::var("KEY_VAULT_KEK").ok()
+                    .and_then(|kek| hex::decode(kek.trim()).ok())
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| Error::KeyVaultFailed(
+                        "KEY_VAULT_KEK must be a hex-encoded 32-byte key".to_string()
+                    ))?;
+                let path = std::env::var("KEY_VAULT_FILE")
+                    .unwrap_or_else(|_| DEFAULT_KEY_VAULT_FILE.to_string());
+                Arc::new(FileKeyVault::open(&path, kek).await?)
+            }
+            Ok("memory") | Err(_) => {
+                if registry_file.is_some() {
+                    return Err(Error::KeyVaultEphemeral);
+                }
+                println!("->> ⚠️  KEY_VAULT=memory - patient keys are lost on restart");
+                Arc::new(MemoryKeyVault::default())
+            }
+            Ok(other) => return Err(Error::KeyVaultFailed(format!("Unknown KEY_VAULT '{}'", other))),
+        };
+
+        println!("->> DIDRegistry: Patient keys in '{}' key vault", key_vault.name());
+
+        let registry = match registry_file {
+            Some(path) => Self::open(Arc::new(FileDIDStore::open(&path).await?)).await?,
+            None => {
+                println!("->> ⚠️  DID_REGISTRY_FILE empty - patient DIDs are lost on restart");
+                Self::new()
             }
+        };
+
+        let registry = registry.with_key_vault(key_vault);
+        match ledger_from_env().await? {
+            Some(ledger) => Ok(registry.with_ledger(ledger)),
+            None => Ok(registry),
         }
+    }
 
-        // Create new DID
-        let patient_did = PatientDID::create(patient_id.clone(), created_by)?;
+    /// Receive the DID of every registry change (e.g. to invalidate cached documents)
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.changes.subscribe()
+    }
 
-        // Store in registry
-        {
-            let mut registry = self.patient_dids.write().await;
-            registry.insert(patient_id.clone(), patient_did.clone());
+    /// Create and register a new patient DID whose key uses `key_algorithm`
+    pub async fn create_patient_did(
+        &self,
+        patient_id: String,
+        created_by: u64,
+        key_algorithm: KeyAlgorithm,
+    ) -> Result<PatientDID> {
+        // Held until the DID is indexed, so concurrent creates can't both pass the check
+        let mut registry = self.patient_dids.write().await;
+        if registry.contains_key(&patient_id) {
+            return Err(Error::DIDAlreadyExists(patient_id));
         }
 
-        // Update reverse index
-        {
-            let mut index = self.did_to_patient.write().await;
-            index.insert(patient_did.did.clone(), patient_id.clone());
+        // Create new DID; its private key goes straight into the vault
+        let (mut patient_did, private_key) = PatientDID::create(patient_id.clone(), created_by, key_algorithm)?;
+        self.key_vault.import(&patient_did.key_ref, key_algorithm, &private_key).await?;
+
+        // Version 1 is signed by its own key
+        let (key_ref, key_id) = (patient_did.key_ref.clone(), patient_did.key_id());
+        let stored = match self.publish(&mut patient_did, &key_ref, key_id).await {
+            Ok(()) => self.persist(&patient_did).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            let _ = self.key_vault.delete(&patient_did.key_ref).await;
+            return Err(e);
         }
 
+        // Store in registry and reverse index
+        registry.insert(patient_id.clone(), patient_did.clone());
+        self.did_to_patient.write().await.insert(patient_did.did.clone(), patient_id.clone());
+        drop(registry);
+
         println!("->> DIDRegistry: Registered DID {} for patient {}", 
                  patient_did.did, patient_id);
 
+        // Clears a cached "not found" for the new DID
+        let _ = self.changes.send(patient_did.did.clone());
+
         Ok(patient_did)
     }
 
@@ -60,20 +188,254 @@ impl DIDRegistry {
 
     /// Get patient DID by DID string
     pub async fn get_by_did(&self, did: &str) -> Result<PatientDID> {
-        let index = self.did_to_patient.read().await;
-        let patient_id = index.get(did)
+        // Released before patient_dids is locked (writers lock patient_dids first)
+        let patient_id = self.did_to_patient.read().await
+            .get(did)
+            .cloned()
             .ok_or_else(|| Error::DIDNotFound(did.to_string()))?;
 
-        self.get_by_patient_id(patient_id).await
+        self.get_by_patient_id(&patient_id).await
     }
 
-    /// Update existing DID (for key rotation)
+    /// Store a changed DID record (key rotation, revocation, custody change)
+    ///
+    /// The reverse index follows the record: if the DID string changed, the
+    /// old DID stops resolving. A DID held by another patient is rejected.
     pub async fn update_did(&self, patient_did: PatientDID) -> Result<()> {
+        self.replace(patient_did, true).await
+    }
+
+    /// Replace a record in both indexes, writing it through to the store if `store`
+    async fn replace(&self, patient_did: PatientDID, store: bool) -> Result<()> {
+        let did = patient_did.did.clone();
+
         let mut registry = self.patient_dids.write().await;
-        registry.insert(patient_did.patient_id.clone(), patient_did);
+        let mut index = self.did_to_patient.write().await;
+
+        if index.get(&did).is_some_and(|owner| owner != &patient_did.patient_id) {
+            return Err(Error::DIDAlreadyExists(did));
+        }
+
+        if store {
+            self.persist(&patient_did).await?;
+        }
+
+        let previous = registry.insert(patient_did.patient_id.clone(), patient_did.clone());
+        let replaced_did = previous.map(|previous| previous.did).filter(|previous| previous != &did);
+        if let Some(replaced_did) = &replaced_did {
+            index.remove(replaced_did);
+        }
+        index.insert(did.clone(), patient_did.patient_id);
+        drop(index);
+        drop(registry);
+
+        // No subscribers is fine
+        if let Some(replaced_did) = replaced_did {
+            let _ = self.changes.send(replaced_did);
+        }
+        let _ = self.changes.send(did);
+
+        Ok(())
+    }
+
+    /// Lock a DID against concurrent changes; released when the guard drops
+    async fn lock_did(&self, did: &str) -> Result<OwnedMutexGuard<()>> {
+        let patient_id = self.did_to_patient.read().await
+            .get(did)
+            .cloned()
+            .ok_or_else(|| Error::DIDNotFound(did.to_string()))?;
+
+        let lock = self.changing.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(patient_id)
+            .or_default()
+            .clone();
+
+        Ok(lock.lock_owned().await)
+    }
+
+    /// Store a changed record, publish it, then store its new document_uri
+    ///
+    /// The record is stored before it's published: a failed publish can be
+    /// rolled back to `previous`, a published version can't. Once published the
+    /// registry follows the ledger even if the last write fails. Callers hold
+    /// the DID's lock.
+    async fn commit(&self, previous: &PatientDID, patient_did: &mut PatientDID, key_ref: &str, signed_by: String) -> Result<()> {
+        if self.ledger.is_none() {
+            return self.update_did(patient_did.clone()).await;
+        }
+
+        self.persist(patient_did).await?;
+        if let Err(e) = self.publish(patient_did, key_ref, signed_by).await {
+            if let Err(rollback) = self.persist(previous).await {
+                println!("->> DIDRegistry: ⚠️  Stored record of {} not rolled back after failed publish: {}",
+                         patient_did.did, rollback);
+            }
+            return Err(e);
+        }
+
+        if let Err(e) = self.persist(patient_did).await {
+            println!("->> DIDRegistry: ⚠️  document_uri of {} not stored: {}", patient_did.did, e);
+        }
+        self.replace(patient_did.clone(), false).await
+    }
+
+    /// Publish the DID's current document as its next ledger version, signed
+    /// with the vault key `key_ref` (verification method `signed_by`)
+    ///
+    /// Sets `document_uri` to the new version. No-op without a ledger; skipped
+    /// for self-custodied DIDs, whose updates only the patient can sign.
+    async fn publish(&self, patient_did: &mut PatientDID, key_ref: &str, signed_by: String) -> Result<()> {
+        let Some(ledger) = &self.ledger else { return Ok(()) };
+        if patient_did.custody != KeyCustody::Custodial {
+            println!("->> DIDRegistry: ⚠️  {} is self-custodied - not published", patient_did.did);
+            return Ok(());
+        }
+
+        let latest = ledger.history(&patient_did.did).await?.pop();
+        let document = serde_json::to_value(patient_did.create_did_document())
+            .map_err(|e| Error::LedgerFailed(e.to_string()))?;
+
+        let mut entry = LedgerEntry::new(&patient_did.did, latest.as_ref(), document, signed_by);
+        entry.signature = hex::encode(self.key_vault.sign(key_ref, &entry.signing_input()).await?);
+
+        let record = ledger.append(entry).await?;
+        patient_did.document_uri = Some(ledger.document_uri(&record.message_id));
+
+        Ok(())
+    }
+
+    /// Published versions of a DID, oldest first, after checking the chain
+    pub async fn published_history(&self, did: &str) -> Result<Vec<LedgerRecord>> {
+        let ledger = self.ledger.as_ref()
+            .ok_or_else(|| Error::LedgerFailed("No DID ledger configured".to_string()))?;
+
+        let history = ledger.history(did).await?;
+        verify_chain(&history)?;
+
+        Ok(history)
+    }
+
+    /// Write a record through to the store (no-op without persistence)
+    async fn persist(&self, patient_did: &PatientDID) -> Result<()> {
+        match &self.store {
+            Some(store) => store.put(patient_did).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Rotate a custodial DID's key: the new key replaces the old one in the vault
+    ///
+    /// The old public key stays in the DID's key history (and document). The
+    /// new document version is published signed by the old key.
+    pub async fn rotate_key(&self, did: &str) -> Result<PatientDID> {
+        let _lock = self.lock_did(did).await?;
+        let mut patient_did = self.custodial_did(did).await?;
+        let previous = patient_did.clone();
+        let (old_key_ref, old_key_id) = (patient_did.key_ref.clone(), patient_did.key_id());
+
+        let private_key = patient_did.rotate_key()?;
+        self.key_vault.import(&patient_did.key_ref, patient_did.key_algorithm, &private_key).await?;
+        if let Err(e) = self.commit(&previous, &mut patient_did, &old_key_ref, old_key_id).await {
+            let _ = self.key_vault.delete(&patient_did.key_ref).await;
+            return Err(e);
+        }
+        self.key_vault.delete(&old_key_ref).await?;
+
+        Ok(patient_did)
+    }
+
+    /// Sign with a custodial DID's key (the key never leaves the vault)
+    pub async fn sign(&self, did: &str, message: &[u8]) -> Result<(PatientDID, Vec<u8>)> {
+        let patient_did = self.custodial_did(did).await?;
+        let signature = self.key_vault.sign(&patient_did.key_ref, message).await?;
+
+        Ok((patient_did, signature))
+    }
+
+    /// Hand a custodial DID's key over to the patient
+    ///
+    /// The DID switches to self-custody and the key is removed from the vault;
+    /// the gateway can't sign for it afterwards.
+    pub async fn export_key(&self, did: &str) -> Result<(PatientDID, Vec<u8>)> {
+        let _lock = self.lock_did(did).await?;
+        let mut patient_did = self.custodial_did(did).await?;
+        let private_key = self.key_vault.export(&patient_did.key_ref).await?;
+
+        patient_did.custody = KeyCustody::SelfCustody { exported_at: chrono::Utc::now() };
+        self.update_did(patient_did.clone()).await?;
+        self.key_vault.delete(&patient_did.key_ref).await?;
+
+        println!("->> DIDRegistry: Exported key of {} - now self-custodied", did);
+
+        Ok((patient_did, private_key))
+    }
+
+    /// Revoke a DID after a key compromise (also once deactivated); its key is
+    /// removed from the vault
+    pub async fn revoke(&self, did: &str) -> Result<PatientDID> {
+        let _lock = self.lock_did(did).await?;
+        let mut patient_did = self.get_by_did(did).await?;
+        if patient_did.metadata.status == DIDStatus::Revoked {
+            return Err(Error::DIDRevoked(did.to_string()));
+        }
+
+        let previous = patient_did.clone();
+        patient_did.revoke();
+        self.retire(&previous, &mut patient_did).await?;
+
+        Ok(patient_did)
+    }
+
+    /// Deactivate a DID for good
+    ///
+    /// A custodial key stays in the vault until the DID is revoked, so the
+    /// revocation can still be published signed by it. Only active DIDs are
+    /// signed for, so the key can't be used for anything else meanwhile.
+    pub async fn deactivate(&self, did: &str) -> Result<PatientDID> {
+        let _lock = self.lock_did(did).await?;
+        let mut patient_did = self.active_did(did).await?;
+        let previous = patient_did.clone();
+
+        patient_did.deactivate();
+        let (key_ref, key_id) = (patient_did.key_ref.clone(), patient_did.key_id());
+        self.commit(&previous, &mut patient_did, &key_ref, key_id).await?;
+
+        Ok(patient_did)
+    }
+
+    /// Publish and store a revoked DID and drop its custodial key
+    async fn retire(&self, previous: &PatientDID, patient_did: &mut PatientDID) -> Result<()> {
+        let (key_ref, key_id) = (patient_did.key_ref.clone(), patient_did.key_id());
+        self.commit(previous, patient_did, &key_ref, key_id).await?;
+
+        if patient_did.custody == KeyCustody::Custodial {
+            self.key_vault.delete(&patient_did.key_ref).await?;
+        }
+
         Ok(())
     }
 
+    async fn active_did(&self, did: &str) -> Result<PatientDID> {
+        let patient_did = self.get_by_did(did).await?;
+
+        match patient_did.metadata.status {
+            DIDStatus::Active => Ok(patient_did),
+            DIDStatus::Revoked => Err(Error::DIDRevoked(did.to_string())),
+            DIDStatus::Deactivated => Err(Error::DIDDeactivated(did.to_string())),
+        }
+    }
+
+    async fn custodial_did(&self, did: &str) -> Result<PatientDID> {
+        let patient_did = self.active_did(did).await?;
+
+        if patient_did.custody != KeyCustody::Custodial {
+            return Err(Error::NotCustodial(did.to_string()));
+        }
+
+        Ok(patient_did)
+    }
+
     /// List all patient DIDs
     pub async fn list_all(&self) -> Vec<PatientDID> {
         let registry = self.patient_dids.read().await;
@@ -92,7 +454,111 @@ impl Clone for DIDRegistry {
         Self {
             patient_dids: Arc::clone(&self.patient_dids),
             did_to_patient: Arc::clone(&self.did_to_patient),
+            changing: Arc::clone(&self.changing),
+            changes: self.changes.clone(),
+            key_vault: Arc::clone(&self.key_vault),
+            store: self.store.clone(),
+            ledger: self.ledger.clone(),
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registry_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("anima-dids-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("did_registry.json").display().to_string();
+
+        let registry = DIDRegistry::open(Arc::new(FileDIDStore::open(&path).await.unwrap())).await.unwrap();
+        let created = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let rotated = registry.rotate_key(&created.did).await.unwrap();
+
+        // Reopened: both lookups work and see the rotated key
+        let registry = DIDRegistry::open(Arc::new(FileDIDStore::open(&path).await.unwrap())).await.unwrap();
+        assert_eq!(registry.get_by_patient_id("p-1").await.unwrap().public_key, rotated.public_key);
+        assert_eq!(registry.get_by_did(&created.did).await.unwrap().metadata.key_version, 2);
+
+        // A changed DID string moves the reverse index entry
+        let mut moved = rotated.clone();
+        moved.did = "did:iota:anima:p-1-moved".to_string();
+        registry.update_did(moved.clone()).await.unwrap();
+        assert!(matches!(registry.get_by_did(&created.did).await, Err(Error::DIDNotFound(_))));
+        assert_eq!(registry.get_by_did(&moved.did).await.unwrap().patient_id, "p-1");
+
+        // ...and a DID can't be claimed by a second patient
+        let other = registry.create_patient_did("p-2".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let mut stolen = other.clone();
+        stolen.did = moved.did.clone();
+        assert!(matches!(registry.update_did(stolen).await, Err(Error::DIDAlreadyExists(_))));
+
+        let registry = DIDRegistry::open(Arc::new(FileDIDStore::open(&path).await.unwrap())).await.unwrap();
+        assert_eq!(registry.get_by_did(&moved.did).await.unwrap().patient_id, "p-1");
+        assert_eq!(registry.get_by_did(&other.did).await.unwrap().patient_id, "p-2");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_persisted_dids_need_a_persistent_vault() {
+        // Restored custodial DIDs would have no keys after a restart
+        assert!(matches!(DIDRegistry::from_env().await, Err(Error::KeyVaultEphemeral)));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_rotations() {
+        let dir = std::env::temp_dir().join(format!("anima-dids-{}", uuid::Uuid::new_v4()));
+        let ledger = Arc::new(crate::did_manager::ledger::LocalLedger::open(&dir.join("did_ledger.json").display().to_string()).await.unwrap());
+        let vault = Arc::new(MemoryKeyVault::default());
+        let registry = DIDRegistry::new().with_key_vault(vault.clone()).with_ledger(ledger);
+
+        let created = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let (first, second, third) = tokio::join!(
+            registry.rotate_key(&created.did),
+            registry.rotate_key(&created.did),
+            registry.rotate_key(&created.did),
+        );
+        let mut rotations = [first.unwrap(), second.unwrap(), third.unwrap()];
+        rotations.sort_by_key(|rotated| rotated.metadata.key_version);
+        assert_eq!(rotations.iter().map(|rotated| rotated.metadata.key_version).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        // Every rotation was published, and only the current key is left in the vault
+        let current = registry.get_by_did(&created.did).await.unwrap();
+        assert_eq!(current.metadata.key_version, 4);
+        assert_eq!(registry.published_history(&created.did).await.unwrap().len(), 4);
+        assert!(registry.sign(&created.did, b"x").await.is_ok());
+        for retired in [&created, &rotations[0], &rotations[1]] {
+            assert!(matches!(vault.sign(&retired.key_ref, b"x").await, Err(Error::KeyNotFound(_))));
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_racing_export() {
+        use crate::auth::PublicKey;
+
+        let registry = DIDRegistry::new();
+        let created = registry.create_patient_did("p-1".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+
+        let (rotated, exported) = tokio::join!(registry.rotate_key(&created.did), registry.export_key(&created.did));
+        let (_, private_key) = exported.unwrap();
+        if let Err(e) = rotated {
+            assert!(matches!(e, Error::NotCustodial(_)));
+        }
+
+        // The stored record stays self-custodied, with the key the patient was handed
+        let current = registry.get_by_did(&created.did).await.unwrap();
+        assert!(matches!(current.custody, KeyCustody::SelfCustody { .. }));
+        assert!(matches!(registry.sign(&created.did, b"x").await, Err(Error::NotCustodial(_))));
+
+        let holder_vault = MemoryKeyVault::default();
+        holder_vault.import("holder", KeyAlgorithm::Ed25519, &private_key).await.unwrap();
+        let signature = holder_vault.sign("holder", b"x").await.unwrap();
+        let public_key = PublicKey::from_bytes(KeyAlgorithm::Ed25519, &hex::decode(&current.public_key).unwrap()).unwrap();
+        assert!(public_key.verify(b"x", &signature));
+    }
+}
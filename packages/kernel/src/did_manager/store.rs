@@ -0,0 +1,67 @@
+use crate::did_manager::{Error, Result, PatientDID};
+use crate::persist::JsonFileStore;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Persistence of patient DID records
+///
+/// Records only - private keys stay in the KeyVault. The registry writes every
+/// change through before it becomes visible, and rebuilds its indexes from
+/// `load` at startup. A ReductStore bucket (or any database) can implement this.
+#[async_trait]
+pub trait DIDStore: Send + Sync {
+    /// Backend name for logs ("file", ...)
+    fn name(&self) -> &str;
+
+    /// Every stored record
+    async fn load(&self) -> Result<Vec<PatientDID>>;
+
+    /// Insert or replace the record of `patient_did.patient_id`
+    async fn put(&self, patient_did: &PatientDID) -> Result<()>;
+}
+
+/// DID records in one JSON file (patient_id -> PatientDID)
+#[derive(Clone)]
+pub struct FileDIDStore {
+    records: Arc<RwLock<BTreeMap<String, PatientDID>>>,
+    store: JsonFileStore,
+}
+
+impl FileDIDStore {
+    /// Open (or start) the store at `path`
+    pub async fn open(path: &str) -> Result<Self> {
+        let store = JsonFileStore::new(path);
+        let records: BTreeMap<String, PatientDID> = store.load().await.map_err(Error::Persist)?;
+
+        println!("->> DIDStore: Loaded {} DID record(s) from {}", records.len(), path);
+
+        Ok(Self {
+            records: Arc::new(RwLock::new(records)),
+            store,
+        })
+    }
+}
+
+#[async_trait]
+impl DIDStore for FileDIDStore {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn load(&self) -> Result<Vec<PatientDID>> {
+        Ok(self.records.read().await.values().cloned().collect())
+    }
+
+    async fn put(&self, patient_did: &PatientDID) -> Result<()> {
+        let mut records = self.records.write().await;
+        let mut updated = records.clone();
+        updated.insert(patient_did.patient_id.clone(), patient_did.clone());
+
+        self.store.save(&updated).await.map_err(Error::Persist)?;
+
+        *records = updated;
+        Ok(())
+    }
+}
@@ -14,8 +14,12 @@ pub struct Composition {
     /// Category of composition (event, persistent, episode)
     pub category: CompositionCategory,
     
-    /// Composition archetype (template identifier)
+    /// Composition archetype (root of the template)
     pub archetype_id: String,
+
+    /// Operational template the composition was validated against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
     
     /// Human-readable name
     pub name: DvText,
@@ -64,6 +68,7 @@ impl CompositionBuilder {
                 subject_did,
                 category: CompositionCategory::Event,
                 archetype_id: archetype_id.into(),
+                template_id: None,
                 name: DvText::new(name.into()),
                 composer: composer.into(),
                 context: CompositionContext {
@@ -85,6 +90,16 @@ impl CompositionBuilder {
         self
     }
 
+    pub fn template_id(mut self, template_id: impl Into<String>) -> Self {
+        self.composition.template_id = Some(template_id.into());
+        self
+    }
+
+    pub fn start_time(mut self, start_time: DvDateTime) -> Self {
+        self.composition.context.start_time = start_time;
+        self
+    }
+
     pub fn setting(mut self, setting: DvCodedText) -> Self {
         self.composition.context.setting = setting;
         self
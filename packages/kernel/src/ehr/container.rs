@@ -0,0 +1,188 @@
+use crate::ehr::{Composition, Error, Result};
+use crate::ehr::{AuditDetails, ChangeType, Contribution, ObjectVersionId, OriginalVersion, VersionedComposition};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+/// openEHR EHR - the per-patient container of all compositions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ehr {
+    pub ehr_id: String,
+
+    /// Patient DID every composition must be about
+    pub subject_did: String,
+
+    pub time_created: DateTime<Utc>,
+
+    /// One versioned object per composition, in creation order (demographics first)
+    pub compositions: Vec<VersionedComposition>,
+
+    /// Every commit, oldest first
+    pub contributions: Vec<Contribution>,
+}
+
+impl Ehr {
+    pub fn new(subject_did: impl Into<String>) -> Self {
+        Self {
+            ehr_id: uuid::Uuid::new_v4().to_string(),
+            subject_did: subject_did.into(),
+            time_created: Utc::now(),
+            compositions: Vec::new(),
+            contributions: Vec::new(),
+        }
+    }
+
+    /// Commit a composition as a new version
+    ///
+    /// Without `preceding_version_uid` the composition is a new object and its
+    /// uid must be version 1 of a new object id. Otherwise `preceding_version_uid`
+    /// must be the latest version (optimistic concurrency) and the composition
+    /// becomes the next version. Returns the stored composition (with its
+    /// version uid) and the contribution recording the commit.
+    pub fn commit_composition(
+        &mut self,
+        mut composition: Composition,
+        preceding_version_uid: Option<&str>,
+        committer: impl Into<String>,
+        description: Option<String>,
+    ) -> Result<(Composition, Contribution)> {
+        if composition.subject_did != self.subject_did {
+            return Err(Error::SubjectMismatch {
+                expected: self.subject_did.clone(),
+                given: composition.subject_did,
+            });
+        }
+
+        let (version_uid, change_type) = match preceding_version_uid {
+            None => {
+                let version_uid = ObjectVersionId::parse(&composition.uid)?;
+                if version_uid.version != 1 || self.index_of(&version_uid.object_id).is_some() {
+                    return Err(Error::InvalidVersionUid(composition.uid));
+                }
+                (version_uid, ChangeType::Creation)
+            }
+            Some(preceding) => {
+                let preceding_uid = ObjectVersionId::parse(preceding)?;
+                let index = self.index_of(&preceding_uid.object_id)
+                    .ok_or_else(|| Error::CompositionNotFound(preceding_uid.object_id.clone()))?;
+
+                let latest = &self.compositions[index].latest().uid;
+                if latest != preceding {
+                    return Err(Error::PrecedingVersionMismatch {
+                        latest: latest.clone(),
+                        given: preceding.to_string(),
+                    });
+                }
+                (preceding_uid.next(), ChangeType::Modification)
+            }
+        };
+
+        composition.uid = version_uid.to_string();
+        let contribution = Contribution {
+            uid: uuid::Uuid::new_v4().to_string(),
+            versions: vec![composition.uid.clone()],
+            audit: AuditDetails::new(committer, change_type, description),
+        };
+        let version = OriginalVersion {
+            uid: composition.uid.clone(),
+            preceding_version_uid: preceding_version_uid.map(str::to_string),
+            contribution: contribution.uid.clone(),
+            commit_audit: contribution.audit.clone(),
+            data: composition.clone(),
+        };
+
+        match self.index_of(&version_uid.object_id) {
+            Some(index) => self.compositions[index].versions.push(version),
+            None => self.compositions.push(VersionedComposition {
+                uid: version_uid.object_id.clone(),
+                owner_id: self.ehr_id.clone(),
+                time_created: contribution.audit.time_committed,
+                versions: vec![version],
+            }),
+        }
+        self.contributions.push(contribution.clone());
+
+        Ok((composition, contribution))
+    }
+
+    /// A composition by object id (latest version) or version uid (that version)
+    pub fn composition(&self, uid: &str) -> Result<&Composition> {
+        let Ok(version_uid) = ObjectVersionId::parse(uid) else {
+            return Ok(&self.versioned_composition(uid)?.latest().data);
+        };
+
+        self.versioned_composition(&version_uid.object_id)?
+            .version(uid)
+            .map(|version| &version.data)
+            .ok_or_else(|| Error::VersionNotFound(uid.to_string()))
+    }
+
+    pub fn versioned_composition(&self, object_id: &str) -> Result<&VersionedComposition> {
+        self.index_of(object_id)
+            .map(|index| &self.compositions[index])
+            .ok_or_else(|| Error::CompositionNotFound(object_id.to_string()))
+    }
+
+    /// Latest version of every composition
+    pub fn latest_compositions(&self) -> Vec<Composition> {
+        self.compositions.iter()
+            .map(|versioned| versioned.latest().data.clone())
+            .collect()
+    }
+
+    fn index_of(&self, object_id: &str) -> Option<usize> {
+        self.compositions.iter().position(|versioned| versioned.uid == object_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ehr::CompositionBuilder;
+
+    fn vitals(subject_did: &str) -> Composition {
+        CompositionBuilder::new(
+            ObjectVersionId::new_object().to_string(),
+            subject_did.to_string(),
+            "openEHR-EHR-COMPOSITION.encounter.v1",
+            "Vitals",
+            "user:1",
+        ).build()
+    }
+
+    #[test]
+    fn test_commit_versions() {
+        let mut ehr = Ehr::new("did:iota:anima:p-1");
+
+        let (v1, created) = ehr.commit_composition(vitals("did:iota:anima:p-1"), None, "user:1", None).unwrap();
+        assert_eq!(created.audit.change_type, ChangeType::Creation);
+
+        // A new version must name the latest one
+        let mut update = v1.clone();
+        update.name.value = "Vitals (corrected)".to_string();
+        let (v2, modified) = ehr.commit_composition(update.clone(), Some(&v1.uid), "user:2", Some("Typo".to_string())).unwrap();
+        assert_eq!(ObjectVersionId::parse(&v2.uid).unwrap().version, 2);
+        assert_eq!(modified.audit.committer, "user:2");
+        assert!(matches!(
+            ehr.commit_composition(update, Some(&v1.uid), "user:3", None),
+            Err(Error::PrecedingVersionMismatch { .. })
+        ));
+
+        // Object id -> latest version; version uid -> that version
+        let object_id = ObjectVersionId::parse(&v1.uid).unwrap().object_id;
+        assert_eq!(ehr.composition(&object_id).unwrap().name.value, "Vitals (corrected)");
+        assert_eq!(ehr.composition(&v1.uid).unwrap().name.value, "Vitals");
+        assert_eq!(ehr.versioned_composition(&object_id).unwrap().revision_history().len(), 2);
+        assert_eq!(ehr.contributions.len(), 2);
+
+        // Another patient's composition, or a reused object id
+        assert!(matches!(
+            ehr.commit_composition(vitals("did:iota:anima:p-2"), None, "user:1", None),
+            Err(Error::SubjectMismatch { .. })
+        ));
+        assert!(matches!(
+            ehr.commit_composition(v1, None, "user:1", None),
+            Err(Error::InvalidVersionUid(_))
+        ));
+        assert_eq!(ehr.latest_compositions().len(), 1);
+    }
+}
@@ -76,6 +76,48 @@ pub struct Action {
     pub description: DvText,
 }
 
+impl Entry {
+    /// openEHR RM type
+    pub fn rm_type(&self) -> &'static str {
+        match self {
+            Entry::Observation(_) => "OBSERVATION",
+            Entry::Evaluation(_) => "EVALUATION",
+            Entry::Instruction(_) => "INSTRUCTION",
+            Entry::Action(_) => "ACTION",
+        }
+    }
+
+    /// Instructions and actions carry no archetype id
+    pub fn archetype_id(&self) -> Option<&str> {
+        match self {
+            Entry::Observation(observation) => Some(&observation.archetype_id),
+            Entry::Evaluation(evaluation) => Some(&evaluation.archetype_id),
+            Entry::Instruction(_) | Entry::Action(_) => None,
+        }
+    }
+
+    pub fn name(&self) -> &DvText {
+        match self {
+            Entry::Observation(observation) => &observation.name,
+            Entry::Evaluation(evaluation) => &evaluation.name,
+            Entry::Instruction(instruction) => &instruction.name,
+            Entry::Action(action) => &action.name,
+        }
+    }
+}
+
+impl ObservationValue {
+    /// openEHR RM type of the data value
+    pub fn rm_type(&self) -> &'static str {
+        match self {
+            ObservationValue::Text(_) => "DV_TEXT",
+            ObservationValue::CodedText(_) => "DV_CODED_TEXT",
+            ObservationValue::Quantity(_) => "DV_QUANTITY",
+            ObservationValue::DateTime(_) => "DV_DATE_TIME",
+        }
+    }
+}
+
 // Helper constructors
 impl Observation {
     pub fn new(name: impl Into<String>, archetype_id: impl Into<String>) -> Self {
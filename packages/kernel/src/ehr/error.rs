@@ -0,0 +1,29 @@
+use serde::Serialize;
+use crate::ehr::Violation;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Clone, Debug, Serialize)]
+pub enum Error {
+    CompositionNotFound(String),
+    VersionNotFound(String),
+    InvalidVersionUid(String),
+    SubjectMismatch { expected: String, given: String },
+    /// Optimistic concurrency: the commit was not based on the latest version
+    PrecedingVersionMismatch { latest: String, given: String },
+    TemplateNotFound(String),
+    /// No single template covers the archetype and templates are required
+    TemplateRequired(String),
+    TemplateViolations { template_id: String, violations: Vec<Violation> },
+    InvalidTemplate(String),
+    /// Canonical / FLAT / STRUCTURED document that cannot be read
+    InvalidFormat(String),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
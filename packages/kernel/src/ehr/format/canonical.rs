@@ -0,0 +1,461 @@
+use crate::ehr::{Composition, CompositionBuilder, CompositionCategory, Entry, Error, Observation, Evaluation, ObservationValue, Result, WebTemplate};
+use crate::ehr::{DvText, DvCodedText, DvDateTime, DvQuantity};
+use crate::ehr::entry::{Instruction, Action};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+/// RM release the canonical JSON follows
+const RM_VERSION: &str = "1.0.4";
+
+/// Language and territory of every composition recorded here
+pub(crate) const LANGUAGE: &str = "en";
+pub(crate) const TERRITORY: &str = "GB";
+
+// Element names carrying the fields openEHR keeps in item trees
+const SUMMARY: &str = "Summary";
+const DESCRIPTION: &str = "Description";
+
+// region: --- Export
+
+/// openEHR canonical JSON of a composition
+///
+/// Element `archetype_node_id`s come from the template when there is one,
+/// otherwise the element name stands in for them.
+pub fn to_canonical(composition: &Composition, template: Option<&WebTemplate>) -> Value {
+    let language = template
+        .and_then(|template| template.default_language.as_deref())
+        .unwrap_or(LANGUAGE);
+
+    let content: Vec<Value> = composition.content.iter()
+        .map(|entry| entry_to_canonical(entry, &composition.subject_did, language, template))
+        .collect();
+
+    let mut context = json!({
+        "_type": "EVENT_CONTEXT",
+        "start_time": dv_date_time(&composition.context.start_time),
+        "setting": dv_coded_text(&composition.context.setting),
+    });
+    if let Some(end_time) = &composition.context.end_time {
+        context["end_time"] = dv_date_time(end_time);
+    }
+
+    let mut archetype_details = json!({
+        "_type": "ARCHETYPED",
+        "archetype_id": { "_type": "ARCHETYPE_ID", "value": composition.archetype_id },
+        "rm_version": RM_VERSION,
+    });
+    if let Some(template_id) = &composition.template_id {
+        archetype_details["template_id"] = json!({ "_type": "TEMPLATE_ID", "value": template_id });
+    }
+
+    json!({
+        "_type": "COMPOSITION",
+        "name": dv_text(&composition.name.value),
+        "uid": { "_type": "OBJECT_VERSION_ID", "value": composition.uid },
+        "archetype_node_id": composition.archetype_id,
+        "archetype_details": archetype_details,
+        "language": code_phrase("ISO_639-1", language),
+        "territory": code_phrase("ISO_3166-1", TERRITORY),
+        "category": dv_coded_text(&category_to_coded_text(&composition.category)),
+        "composer": { "_type": "PARTY_IDENTIFIED", "name": composition.composer },
+        "context": context,
+        "content": content,
+    })
+}
+
+fn entry_to_canonical(entry: &Entry, subject_did: &str, language: &str, template: Option<&WebTemplate>) -> Value {
+    let entry_node = template.and_then(|template| {
+        template.entry_slots().into_iter()
+            .find(|slot| slot.node.matches_entry(entry))
+            .map(|slot| slot.node)
+    });
+    let node_id = |name: &str| entry_node
+        .and_then(|node| node.element_slots().into_iter().find(|slot| slot.node.label() == name))
+        .and_then(|slot| slot.node.node_id.clone())
+        .unwrap_or_else(|| name.to_string());
+
+    let mut canonical = json!({
+        "_type": entry.rm_type(),
+        "name": dv_text(&entry.name().value),
+        "archetype_node_id": entry.archetype_id()
+            .or_else(|| entry_node.and_then(|node| node.node_id.as_deref()))
+            .unwrap_or(&entry.name().value),
+        "language": code_phrase("ISO_639-1", language),
+        "encoding": code_phrase("IANA_character-sets", "UTF-8"),
+        "subject": {
+            "_type": "PARTY_SELF",
+            "external_ref": {
+                "_type": "PARTY_REF",
+                "id": { "_type": "GENERIC_ID", "value": subject_did, "scheme": "did" },
+                "namespace": "did",
+                "type": "PERSON",
+            },
+        },
+    });
+
+    match entry {
+        Entry::Observation(observation) => {
+            let items: Vec<Value> = observation.data.items.iter()
+                .map(|item| element(&item.name.value, &node_id(&item.name.value), &item.value))
+                .collect();
+            canonical["data"] = json!({
+                "_type": "HISTORY",
+                "name": dv_text("History"),
+                "archetype_node_id": "at0001",
+                "origin": dv_date_time(&observation.time),
+                "events": [{
+                    "_type": "POINT_EVENT",
+                    "name": dv_text("Any event"),
+                    "archetype_node_id": "at0002",
+                    "time": dv_date_time(&observation.time),
+                    "data": item_tree("at0003", items),
+                }],
+            });
+        }
+        Entry::Evaluation(evaluation) => {
+            let summary = ObservationValue::Text(evaluation.data.summary.clone());
+            let items: Vec<Value> = std::iter::once(element(SUMMARY, &node_id(SUMMARY), &summary))
+                .chain(evaluation.data.items.iter().map(|item| {
+                    element(&item.name.value, &node_id(&item.name.value), &ObservationValue::Text(item.value.clone()))
+                }))
+                .collect();
+            canonical["data"] = item_tree("at0001", items);
+        }
+        Entry::Instruction(instruction) => {
+            canonical["narrative"] = dv_text(&instruction.narrative.value);
+        }
+        Entry::Action(action) => {
+            let description = ObservationValue::Text(action.description.clone());
+            canonical["time"] = dv_date_time(&action.time);
+            canonical["ism_transition"] = json!({
+                "_type": "ISM_TRANSITION",
+                "current_state": dv_coded_text(&DvCodedText::new("completed", "openehr", "532")),
+            });
+            canonical["description"] = item_tree("at0001", vec![element(DESCRIPTION, &node_id(DESCRIPTION), &description)]);
+        }
+    }
+
+    canonical
+}
+
+fn item_tree(node_id: &str, items: Vec<Value>) -> Value {
+    json!({
+        "_type": "ITEM_TREE",
+        "name": dv_text("Tree"),
+        "archetype_node_id": node_id,
+        "items": items,
+    })
+}
+
+fn element(name: &str, node_id: &str, value: &ObservationValue) -> Value {
+    json!({
+        "_type": "ELEMENT",
+        "name": dv_text(name),
+        "archetype_node_id": node_id,
+        "value": data_value(value),
+    })
+}
+
+fn data_value(value: &ObservationValue) -> Value {
+    match value {
+        ObservationValue::Text(text) => dv_text(&text.value),
+        ObservationValue::CodedText(coded) => dv_coded_text(coded),
+        ObservationValue::Quantity(quantity) => json!({
+            "_type": "DV_QUANTITY",
+            "magnitude": quantity.magnitude,
+            "units": quantity.units,
+        }),
+        ObservationValue::DateTime(date_time) => dv_date_time(date_time),
+    }
+}
+
+fn dv_text(value: &str) -> Value {
+    json!({ "_type": "DV_TEXT", "value": value })
+}
+
+fn dv_coded_text(coded: &DvCodedText) -> Value {
+    json!({
+        "_type": "DV_CODED_TEXT",
+        "value": coded.value,
+        "defining_code": code_phrase(&coded.defining_code.terminology_id, &coded.defining_code.code_string),
+    })
+}
+
+fn dv_date_time(date_time: &DvDateTime) -> Value {
+    json!({ "_type": "DV_DATE_TIME", "value": date_time.value.to_rfc3339() })
+}
+
+fn code_phrase(terminology_id: &str, code_string: &str) -> Value {
+    json!({
+        "_type": "CODE_PHRASE",
+        "terminology_id": { "_type": "TERMINOLOGY_ID", "value": terminology_id },
+        "code_string": code_string,
+    })
+}
+
+/// openEHR terminology "composition category"
+pub(crate) fn category_to_coded_text(category: &CompositionCategory) -> DvCodedText {
+    match category {
+        CompositionCategory::Persistent => DvCodedText::new("persistent", "openehr", "431"),
+        CompositionCategory::Event => DvCodedText::new("event", "openehr", "433"),
+        CompositionCategory::Episode => DvCodedText::new("episodic", "openehr", "451"),
+    }
+}
+
+pub(crate) fn category_from_code(code: &str) -> Option<CompositionCategory> {
+    match code {
+        "431" => Some(CompositionCategory::Persistent),
+        "433" => Some(CompositionCategory::Event),
+        "451" => Some(CompositionCategory::Episode),
+        _ => None,
+    }
+}
+
+// endregion: --- Export
+
+// region: --- Import
+
+/// Composition from openEHR canonical JSON
+///
+/// `uid`, composer and the subject DID (the entries' `PARTY_SELF` external ref)
+/// are read when present and left empty otherwise - the caller stamps its own.
+/// EVALUATION has no time in the RM, so imported evaluations are timed at import.
+pub fn from_canonical(canonical: &Value) -> Result<Composition> {
+    expect_type(canonical, "COMPOSITION", "")?;
+
+    let archetype_id = canonical.get("archetype_node_id")
+        .or_else(|| canonical.pointer("/archetype_details/archetype_id/value"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("/archetype_node_id", "missing"))?;
+    let category_code = string(canonical, "/category/defining_code/code_string")?;
+    let category = category_from_code(category_code)
+        .ok_or_else(|| invalid("/category", format!("unknown category {category_code}")))?;
+
+    let content = canonical.get("content")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let subject_did = content.iter()
+        .find_map(|entry| entry.pointer("/subject/external_ref/id/value").and_then(Value::as_str))
+        .unwrap_or_default();
+
+    let mut builder = CompositionBuilder::new(
+        optional_string(canonical, "/uid/value").unwrap_or_default().to_string(),
+        subject_did.to_string(),
+        archetype_id,
+        string(canonical, "/name/value")?,
+        optional_string(canonical, "/composer/name").unwrap_or_default(),
+    )
+    .category(category);
+
+    if let Some(template_id) = optional_string(canonical, "/archetype_details/template_id/value") {
+        builder = builder.template_id(template_id);
+    }
+    if let Some(setting) = canonical.pointer("/context/setting") {
+        builder = builder.setting(coded_text_from_canonical(setting, "/context/setting")?);
+    }
+    for (index, entry) in content.iter().enumerate() {
+        builder = builder.add_entry(entry_from_canonical(entry, &format!("/content[{index}]"))?);
+    }
+
+    let mut composition = builder.build();
+    if let Some(start_time) = canonical.pointer("/context/start_time") {
+        composition.context.start_time = date_time_from_canonical(start_time, "/context/start_time")?;
+    }
+    if let Some(end_time) = canonical.pointer("/context/end_time") {
+        composition.context.end_time = Some(date_time_from_canonical(end_time, "/context/end_time")?);
+    }
+
+    Ok(composition)
+}
+
+fn entry_from_canonical(canonical: &Value, path: &str) -> Result<Entry> {
+    let rm_type = string(canonical, "/_type").map_err(|_| invalid(path, "missing _type"))?;
+    let name = string(canonical, "/name/value").map_err(|_| invalid(path, "missing name"))?;
+    let archetype_id = optional_string(canonical, "/archetype_node_id").unwrap_or_default();
+
+    match rm_type {
+        "OBSERVATION" => {
+            let time = canonical.pointer("/data/events/0/time")
+                .or_else(|| canonical.pointer("/data/origin"))
+                .map(|time| date_time_from_canonical(time, &format!("{path}/data/events[0]/time")))
+                .transpose()?
+                .unwrap_or_else(DvDateTime::now);
+
+            let mut observation = Observation::new(name, archetype_id);
+            observation.time = time;
+            let events = canonical.pointer("/data/events").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+            for (index, event) in events.iter().enumerate() {
+                for (item_name, value) in elements(event.pointer("/data/items"), &format!("{path}/data/events[{index}]/data"))? {
+                    observation = observation.add_item(item_name, value);
+                }
+            }
+            Ok(Entry::Observation(observation))
+        }
+        "EVALUATION" => {
+            let items = elements(canonical.pointer("/data/items"), &format!("{path}/data"))?;
+            let mut evaluation = Evaluation::new(name, archetype_id, "");
+            for (item_name, value) in items {
+                let text = text_of(&value).ok_or_else(|| invalid(format!("{path}/data/items"), format!("{item_name} is not text")))?;
+                if item_name == SUMMARY {
+                    evaluation.data.summary = DvText::new(text);
+                } else {
+                    evaluation = evaluation.add_item(item_name, text);
+                }
+            }
+            Ok(Entry::Evaluation(evaluation))
+        }
+        "INSTRUCTION" => Ok(Entry::Instruction(Instruction {
+            name: DvText::new(name),
+            narrative: DvText::new(optional_string(canonical, "/narrative/value").unwrap_or_default()),
+        })),
+        "ACTION" => {
+            let description = elements(canonical.pointer("/description/items"), &format!("{path}/description"))?
+                .into_iter()
+                .find(|(item_name, _)| item_name == DESCRIPTION)
+                .and_then(|(_, value)| text_of(&value))
+                .unwrap_or_default();
+            Ok(Entry::Action(Action {
+                name: DvText::new(name),
+                time: canonical.get("time")
+                    .map(|time| date_time_from_canonical(time, &format!("{path}/time")))
+                    .transpose()?
+                    .unwrap_or_else(DvDateTime::now),
+                description: DvText::new(description),
+            }))
+        }
+        other => Err(invalid(path, format!("unsupported entry type {other}"))),
+    }
+}
+
+/// Every ELEMENT under `items`, descending into CLUSTERs; elements without a value are skipped
+fn elements(items: Option<&Value>, path: &str) -> Result<Vec<(String, ObservationValue)>> {
+    let mut found = Vec::new();
+    let items = items.and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+
+    for (index, item) in items.iter().enumerate() {
+        let item_path = format!("{path}/items[{index}]");
+        match item.get("_type").and_then(Value::as_str) {
+            Some("ELEMENT") => {
+                let Some(value) = item.get("value") else { continue };
+                let name = string(item, "/name/value").map_err(|_| invalid(&item_path, "missing name"))?;
+                found.push((name.to_string(), data_value_from_canonical(value, &format!("{item_path}/value"))?));
+            }
+            Some("CLUSTER") => found.extend(elements(item.get("items"), &item_path)?),
+            _ => return Err(invalid(item_path, "expected ELEMENT or CLUSTER")),
+        }
+    }
+
+    Ok(found)
+}
+
+fn data_value_from_canonical(value: &Value, path: &str) -> Result<ObservationValue> {
+    match value.get("_type").and_then(Value::as_str) {
+        Some("DV_TEXT") => Ok(ObservationValue::Text(DvText::new(string(value, "/value").map_err(|_| invalid(path, "missing value"))?))),
+        Some("DV_CODED_TEXT") => Ok(ObservationValue::CodedText(coded_text_from_canonical(value, path)?)),
+        Some("DV_QUANTITY") => Ok(ObservationValue::Quantity(DvQuantity::new(
+            value.get("magnitude").and_then(Value::as_f64).ok_or_else(|| invalid(path, "missing magnitude"))?,
+            string(value, "/units").map_err(|_| invalid(path, "missing units"))?,
+        ))),
+        Some("DV_DATE_TIME") => Ok(ObservationValue::DateTime(date_time_from_canonical(value, path)?)),
+        Some(other) => Err(invalid(path, format!("unsupported data value {other}"))),
+        None => Err(invalid(path, "missing _type")),
+    }
+}
+
+fn coded_text_from_canonical(value: &Value, path: &str) -> Result<DvCodedText> {
+    let field = |pointer: &str| string(value, pointer).map_err(|_| invalid(path, format!("missing {pointer}")));
+    Ok(DvCodedText::new(
+        field("/value")?,
+        field("/defining_code/terminology_id/value")?,
+        field("/defining_code/code_string")?,
+    ))
+}
+
+fn date_time_from_canonical(value: &Value, path: &str) -> Result<DvDateTime> {
+    let value = string(value, "/value").map_err(|_| invalid(path, "missing value"))?;
+    DateTime::parse_from_rfc3339(value)
+        .map(|date_time| DvDateTime::from_datetime(date_time.with_timezone(&Utc)))
+        .map_err(|e| invalid(path, format!("{value}: {e}")))
+}
+
+fn text_of(value: &ObservationValue) -> Option<String> {
+    match value {
+        ObservationValue::Text(text) => Some(text.value.clone()),
+        ObservationValue::CodedText(coded) => Some(coded.value.clone()),
+        _ => None,
+    }
+}
+
+fn expect_type(value: &Value, rm_type: &str, path: &str) -> Result<()> {
+    match value.get("_type").and_then(Value::as_str) {
+        Some(found) if found == rm_type => Ok(()),
+        found => Err(invalid(path, format!("expected _type {rm_type}, found {}", found.unwrap_or("none")))),
+    }
+}
+
+fn string<'a>(value: &'a Value, pointer: &str) -> Result<&'a str> {
+    optional_string(value, pointer).ok_or_else(|| invalid(pointer, "missing"))
+}
+
+fn optional_string<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(Value::as_str)
+}
+
+fn invalid(path: impl AsRef<str>, reason: impl AsRef<str>) -> Error {
+    let path = path.as_ref();
+    Error::InvalidFormat(format!("{}: {}", if path.is_empty() { "/" } else { path }, reason.as_ref()))
+}
+
+// endregion: --- Import
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ehr::ObjectVersionId;
+
+    #[test]
+    fn test_canonical_roundtrip() {
+        let composition = CompositionBuilder::new(
+            ObjectVersionId::new_object().to_string(),
+            "did:iota:anima:p-1".to_string(),
+            "openEHR-EHR-COMPOSITION.encounter.v1",
+            "Vitals",
+            "user:1",
+        )
+        .template_id("anima.vital_signs.v1")
+        .add_entry(Entry::Observation(
+            Observation::new("Blood pressure", "openEHR-EHR-OBSERVATION.blood_pressure.v2")
+                .add_item("Systolic", ObservationValue::Quantity(DvQuantity::new(120.0, "mm[Hg]")))
+                .add_item("Position", ObservationValue::CodedText(DvCodedText::new("Sitting", "local", "at1001"))),
+        ))
+        .add_entry(Entry::Evaluation(
+            Evaluation::new("Problem", "openEHR-EHR-EVALUATION.problem_diagnosis.v1", "Hypertension")
+                .add_item("Severity", "Mild"),
+        ))
+        .build();
+
+        let canonical = to_canonical(&composition, None);
+        assert_eq!(canonical["_type"], "COMPOSITION");
+        assert_eq!(canonical["category"]["defining_code"]["code_string"], "433");
+        assert_eq!(canonical["archetype_details"]["template_id"]["value"], "anima.vital_signs.v1");
+        let systolic = &canonical["content"][0]["data"]["events"][0]["data"]["items"][0];
+        assert_eq!(systolic["_type"], "ELEMENT");
+        assert_eq!(systolic["value"]["_type"], "DV_QUANTITY");
+        assert_eq!(systolic["value"]["units"], "mm[Hg]");
+
+        let imported = from_canonical(&canonical).unwrap();
+        assert_eq!(imported.uid, composition.uid);
+        assert_eq!(imported.subject_did, "did:iota:anima:p-1");
+        assert_eq!(imported.template_id.as_deref(), Some("anima.vital_signs.v1"));
+        assert_eq!(serde_json::to_value(&imported.content[0]).unwrap(), serde_json::to_value(&composition.content[0]).unwrap());
+        let (Entry::Evaluation(imported_evaluation), Entry::Evaluation(evaluation)) = (&imported.content[1], &composition.content[1]) else {
+            panic!("expected evaluations");
+        };
+        assert_eq!(serde_json::to_value(&imported_evaluation.data).unwrap(), serde_json::to_value(&evaluation.data).unwrap());
+
+        let mut not_a_composition = canonical.clone();
+        not_a_composition["_type"] = json!("OBSERVATION");
+        assert!(matches!(from_canonical(&not_a_composition), Err(Error::InvalidFormat(_))));
+    }
+}
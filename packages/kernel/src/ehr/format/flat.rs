@@ -0,0 +1,520 @@
+use crate::ehr::{Composition, CompositionBuilder, CompositionCategory, Entry, Error, Observation, Evaluation, ObservationValue, Result, WebTemplate};
+use crate::ehr::{DvText, DvCodedText, DvDateTime, DvQuantity};
+use crate::ehr::entry::{Instruction, Action};
+use crate::ehr::template::{Slot, TemplateNode};
+use crate::ehr::format::canonical::{category_to_coded_text, category_from_code, LANGUAGE, TERRITORY};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+// region: --- FLAT Export
+
+/// Simplified FLAT format of a composition: one `path|attribute` key per value,
+/// paths built from the template's node ids (`vital_signs/blood_pressure:0/systolic|magnitude`)
+pub fn to_flat(composition: &Composition, template: &WebTemplate) -> Result<Map<String, Value>> {
+    let root = &template.tree.id;
+    let language = template.default_language.as_deref().unwrap_or(LANGUAGE);
+    let mut flat = Map::new();
+
+    insert_coded_text(&mut flat, &format!("{root}/category"), &category_to_coded_text(&composition.category));
+    flat.insert(format!("{root}/language|code"), json!(language));
+    flat.insert(format!("{root}/language|terminology"), json!("ISO_639-1"));
+    flat.insert(format!("{root}/territory|code"), json!(TERRITORY));
+    flat.insert(format!("{root}/territory|terminology"), json!("ISO_3166-1"));
+    flat.insert(format!("{root}/composer|name"), json!(composition.composer));
+    flat.insert(format!("{root}/context/start_time"), date_time(&composition.context.start_time));
+    if let Some(end_time) = &composition.context.end_time {
+        flat.insert(format!("{root}/context/end_time"), date_time(end_time));
+    }
+    insert_coded_text(&mut flat, &format!("{root}/context/setting"), &composition.context.setting);
+
+    let slots = template.entry_slots();
+    let mut occurrences = vec![0; slots.len()];
+
+    for (index, entry) in composition.content.iter().enumerate() {
+        let slot = slots.iter().position(|slot| slot.node.matches_entry(entry)).ok_or_else(|| invalid(
+            format!("/content[{index}]"),
+            format!("{} is not in template {}", entry.archetype_id().unwrap_or(&entry.name().value), template.template_id),
+        ))?;
+        let prefix = slot_path(root, &slots[slot], occurrences[slot]);
+        occurrences[slot] += 1;
+
+        let entry_node = slots[slot].node;
+        match entry {
+            Entry::Observation(observation) => {
+                flat.insert(format!("{prefix}/time"), date_time(&observation.time));
+                let items = observation.data.items.iter().map(|item| (item.name.value.as_str(), item.value.clone()));
+                insert_items(&mut flat, &prefix, entry_node, items)?;
+            }
+            Entry::Evaluation(evaluation) => {
+                flat.insert(format!("{prefix}/summary"), json!(evaluation.data.summary.value));
+                let items = evaluation.data.items.iter().map(|item| (item.name.value.as_str(), ObservationValue::Text(item.value.clone())));
+                insert_items(&mut flat, &prefix, entry_node, items)?;
+            }
+            Entry::Instruction(instruction) => {
+                flat.insert(format!("{prefix}/narrative"), json!(instruction.narrative.value));
+            }
+            Entry::Action(action) => {
+                flat.insert(format!("{prefix}/time"), date_time(&action.time));
+                flat.insert(format!("{prefix}/description"), json!(action.description.value));
+            }
+        }
+    }
+
+    Ok(flat)
+}
+
+fn insert_items<'a>(
+    flat: &mut Map<String, Value>,
+    prefix: &str,
+    entry_node: &TemplateNode,
+    items: impl Iterator<Item = (&'a str, ObservationValue)>,
+) -> Result<()> {
+    let slots = entry_node.element_slots();
+    let mut occurrences = vec![0; slots.len()];
+
+    for (name, value) in items {
+        let slot = slots.iter().position(|slot| slot.node.label() == name)
+            .ok_or_else(|| invalid(prefix, format!("{name} is not an element of {}", entry_node.id)))?;
+        let mut path = slot_path(prefix, &slots[slot], occurrences[slot]);
+        occurrences[slot] += 1;
+
+        // A choice ELEMENT adds the id of the chosen value node
+        let element = slots[slot].node;
+        if element.rm_type == "ELEMENT" {
+            if let Some(value_node) = element.value_node(value.rm_type()).or_else(|| element.value_node("DV_TEXT")) {
+                path = format!("{path}/{}", value_node.id);
+            }
+        }
+
+        match &value {
+            ObservationValue::Text(text) => { flat.insert(path, json!(text.value)); }
+            ObservationValue::CodedText(coded) => insert_coded_text(flat, &path, coded),
+            ObservationValue::Quantity(quantity) => {
+                flat.insert(format!("{path}|magnitude"), json!(quantity.magnitude));
+                flat.insert(format!("{path}|unit"), json!(quantity.units));
+            }
+            ObservationValue::DateTime(value) => { flat.insert(path, date_time(value)); }
+        }
+    }
+
+    Ok(())
+}
+
+fn insert_coded_text(flat: &mut Map<String, Value>, path: &str, coded: &DvCodedText) {
+    flat.insert(format!("{path}|code"), json!(coded.defining_code.code_string));
+    flat.insert(format!("{path}|value"), json!(coded.value));
+    flat.insert(format!("{path}|terminology"), json!(coded.defining_code.terminology_id));
+}
+
+fn date_time(date_time: &DvDateTime) -> Value {
+    json!(date_time.value.to_rfc3339())
+}
+
+/// `id`, or `id:index` for nodes that may repeat
+fn segment(node: &TemplateNode, index: usize) -> String {
+    if node.max == 1 {
+        node.id.clone()
+    } else {
+        format!("{}:{index}", node.id)
+    }
+}
+
+/// Path of the `index`th occurrence of a slot below `prefix` (intermediate nodes at occurrence 0)
+fn slot_path(prefix: &str, slot: &Slot, index: usize) -> String {
+    let mut path = prefix.to_string();
+    for node in &slot.path {
+        path.push('/');
+        path.push_str(&segment(node, 0));
+    }
+    path.push('/');
+    path.push_str(&segment(slot.node, index));
+    path
+}
+
+// endregion: --- FLAT Export
+
+// region: --- FLAT Import
+
+/// Composition from the FLAT format of `template`
+///
+/// Entries come back in template order. `uid`, the subject DID and (unless given)
+/// the composer are left empty for the caller to stamp. Keys the template does not
+/// define are refused rather than dropped.
+pub fn from_flat(flat: &Map<String, Value>, template: &WebTemplate) -> Result<Composition> {
+    let root = &template.tree.id;
+    let mut reader = FlatReader::new(flat);
+
+    let category = match reader.string(&format!("{root}/category|code"))? {
+        Some(code) => category_from_code(&code)
+            .ok_or_else(|| invalid(format!("{root}/category|code"), format!("unknown category {code}")))?,
+        None => CompositionCategory::Event,
+    };
+    for attribute in ["language|code", "language|terminology", "territory|code", "territory|terminology", "category|value", "category|terminology"] {
+        reader.take(&format!("{root}/{attribute}"));
+    }
+
+    let mut builder = CompositionBuilder::new(
+        String::new(),
+        String::new(),
+        template.tree.node_id.clone().unwrap_or_default(),
+        template.tree.label(),
+        reader.string(&format!("{root}/composer|name"))?.unwrap_or_default(),
+    )
+    .template_id(template.template_id.clone())
+    .category(category);
+
+    if let Some(setting) = reader.coded_text(&format!("{root}/context/setting"))? {
+        builder = builder.setting(setting);
+    }
+
+    for slot in template.entry_slots() {
+        for index in 0.. {
+            let prefix = normalize(&slot_path(root, &slot, index));
+            if !reader.has(&prefix) {
+                break;
+            }
+            builder = builder.add_entry(read_entry(&mut reader, &prefix, slot.node)?);
+            if slot.node.max == 1 {
+                break;
+            }
+        }
+    }
+
+    let mut composition = builder.build();
+    if let Some(start_time) = reader.date_time(&format!("{root}/context/start_time"))? {
+        composition.context.start_time = start_time;
+    }
+    composition.context.end_time = reader.date_time(&format!("{root}/context/end_time"))?;
+
+    reader.finish()?;
+    Ok(composition)
+}
+
+fn read_entry(reader: &mut FlatReader, prefix: &str, entry_node: &TemplateNode) -> Result<Entry> {
+    let name = entry_node.label();
+    let archetype_id = entry_node.node_id.clone().unwrap_or_default();
+    let time = reader.date_time(&format!("{prefix}/time"))?.unwrap_or_else(DvDateTime::now);
+
+    match entry_node.rm_type.as_str() {
+        "OBSERVATION" => {
+            let mut observation = Observation::new(name, archetype_id);
+            observation.time = time;
+            for (item_name, value) in read_items(reader, prefix, entry_node)? {
+                observation = observation.add_item(item_name, value);
+            }
+            Ok(Entry::Observation(observation))
+        }
+        "EVALUATION" => {
+            let summary = reader.string(&format!("{prefix}/summary"))?.unwrap_or_default();
+            let mut evaluation = Evaluation::new(name, archetype_id, summary);
+            evaluation.time = time;
+            for (item_name, value) in read_items(reader, prefix, entry_node)? {
+                let ObservationValue::Text(text) = value else {
+                    return Err(invalid(prefix, format!("{item_name} is not text")));
+                };
+                evaluation = evaluation.add_item(item_name, text.value);
+            }
+            Ok(Entry::Evaluation(evaluation))
+        }
+        "INSTRUCTION" => Ok(Entry::Instruction(Instruction {
+            name: DvText::new(name),
+            narrative: DvText::new(reader.string(&format!("{prefix}/narrative"))?.unwrap_or_default()),
+        })),
+        _ => Ok(Entry::Action(Action {
+            name: DvText::new(name),
+            time,
+            description: DvText::new(reader.string(&format!("{prefix}/description"))?.unwrap_or_default()),
+        })),
+    }
+}
+
+fn read_items(reader: &mut FlatReader, prefix: &str, entry_node: &TemplateNode) -> Result<Vec<(String, ObservationValue)>> {
+    let mut items = Vec::new();
+
+    for slot in entry_node.element_slots() {
+        for index in 0.. {
+            let path = normalize(&slot_path(prefix, &slot, index));
+            if !reader.has(&path) {
+                break;
+            }
+            items.push((slot.node.label().to_string(), read_value(reader, &path, slot.node)?));
+            if slot.node.max == 1 {
+                break;
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+fn read_value(reader: &mut FlatReader, path: &str, element: &TemplateNode) -> Result<ObservationValue> {
+    if element.rm_type == "ELEMENT" {
+        let chosen = element.children.iter()
+            .find(|child| child.rm_type.starts_with("DV_") && reader.has(&format!("{path}/{}", child.id)))
+            .ok_or_else(|| invalid(path, "no value"))?;
+        return read_value(reader, &format!("{path}/{}", chosen.id), chosen);
+    }
+
+    match element.rm_type.as_str() {
+        "DV_QUANTITY" => {
+            let magnitude = reader.take(&format!("{path}|magnitude"))
+                .and_then(|magnitude| magnitude.as_f64())
+                .ok_or_else(|| invalid(format!("{path}|magnitude"), "missing or not a number"))?;
+            let unit = reader.string(&format!("{path}|unit"))?
+                .ok_or_else(|| invalid(format!("{path}|unit"), "missing"))?;
+            Ok(ObservationValue::Quantity(DvQuantity::new(magnitude, unit)))
+        }
+        "DV_CODED_TEXT" => reader.coded_text(path)?
+            .map(ObservationValue::CodedText)
+            .ok_or_else(|| invalid(format!("{path}|code"), "missing")),
+        "DV_DATE_TIME" => reader.date_time(path)?
+            .map(ObservationValue::DateTime)
+            .ok_or_else(|| invalid(path, "missing")),
+        _ => {
+            let text = match reader.string(path)? {
+                Some(text) => text,
+                None => reader.string(&format!("{path}|value"))?.ok_or_else(|| invalid(path, "missing"))?,
+            };
+            Ok(ObservationValue::Text(DvText::new(text)))
+        }
+    }
+}
+
+/// FLAT values by normalized key, tracking which were read
+struct FlatReader {
+    values: BTreeMap<String, Value>,
+    read: BTreeSet<String>,
+}
+
+impl FlatReader {
+    fn new(flat: &Map<String, Value>) -> Self {
+        Self {
+            values: flat.iter().map(|(key, value)| (normalize(key), value.clone())).collect(),
+            read: BTreeSet::new(),
+        }
+    }
+
+    /// Whether any key is at or below `path`
+    ///
+    /// Keys starting with `path` sort together from `path` on, so only those are scanned.
+    fn has(&self, path: &str) -> bool {
+        self.values.range::<str, _>((Bound::Included(path), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(path))
+            .any(|key| {
+                let rest = &key[path.len()..];
+                rest.is_empty() || rest.starts_with('/') || rest.starts_with('|')
+            })
+    }
+
+    fn take(&mut self, key: &str) -> Option<&Value> {
+        let value = self.values.get(key)?;
+        self.read.insert(key.to_string());
+        Some(value)
+    }
+
+    fn string(&mut self, key: &str) -> Result<Option<String>> {
+        match self.take(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(invalid(key, "expected a string")),
+        }
+    }
+
+    fn coded_text(&mut self, path: &str) -> Result<Option<DvCodedText>> {
+        let Some(code) = self.string(&format!("{path}|code"))? else {
+            return Ok(None);
+        };
+        let value = self.string(&format!("{path}|value"))?.unwrap_or_else(|| code.clone());
+        let terminology = self.string(&format!("{path}|terminology"))?.unwrap_or_else(|| "local".to_string());
+        Ok(Some(DvCodedText::new(value, terminology, code)))
+    }
+
+    fn date_time(&mut self, key: &str) -> Result<Option<DvDateTime>> {
+        self.string(key)?
+            .map(|value| DateTime::parse_from_rfc3339(&value)
+                .map(|date_time| DvDateTime::from_datetime(date_time.with_timezone(&Utc)))
+                .map_err(|e| invalid(key, format!("{value}: {e}"))))
+            .transpose()
+    }
+
+    /// Refuse keys nothing read
+    fn finish(self) -> Result<()> {
+        let unknown: Vec<&str> = self.values.keys()
+            .filter(|key| !self.read.contains(*key))
+            .map(String::as_str)
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        Err(Error::InvalidFormat(format!("Unknown FLAT paths: {}", unknown.join(", "))))
+    }
+}
+
+/// Occurrence 0 may be written with or without its index: `blood_pressure:0` = `blood_pressure`
+fn normalize(key: &str) -> String {
+    let (path, attribute) = match key.split_once('|') {
+        Some((path, attribute)) => (path, Some(attribute)),
+        None => (key, None),
+    };
+    let path = path.split('/')
+        .map(|segment| segment.strip_suffix(":0").unwrap_or(segment))
+        .collect::<Vec<_>>()
+        .join("/");
+
+    match attribute {
+        Some(attribute) => format!("{path}|{attribute}"),
+        None => path,
+    }
+}
+
+fn invalid(path: impl AsRef<str>, reason: impl AsRef<str>) -> Error {
+    Error::InvalidFormat(format!("{}: {}", path.as_ref(), reason.as_ref()))
+}
+
+// endregion: --- FLAT Import
+
+// region: --- STRUCTURED
+
+/// STRUCTURED form of a FLAT composition: path segments become nested keys,
+/// every node below the root an array of occurrences, attributes `|`-prefixed keys
+pub fn to_structured(flat: &Map<String, Value>) -> Value {
+    let mut structured = Map::new();
+
+    for (key, value) in flat {
+        let (path, attribute) = match key.split_once('|') {
+            Some((path, attribute)) => (path, Some(attribute)),
+            None => (key.as_str(), None),
+        };
+        let mut segments = path.split('/');
+        let root = segments.next().unwrap_or_default();
+
+        let mut node = structured.entry(root).or_insert_with(|| json!({}));
+        for segment in segments {
+            let (id, index) = match segment.split_once(':') {
+                Some((id, index)) => (id, index.parse().unwrap_or(0)),
+                None => (segment, 0),
+            };
+            node = occurrence(node, id, index);
+        }
+
+        match attribute {
+            Some(attribute) => {
+                if !node.is_object() {
+                    *node = json!({});
+                }
+                node[format!("|{attribute}")] = value.clone();
+            }
+            None => *node = value.clone(),
+        }
+    }
+
+    Value::Object(structured)
+}
+
+fn occurrence<'a>(node: &'a mut Value, id: &str, index: usize) -> &'a mut Value {
+    if !node.is_object() {
+        *node = json!({});
+    }
+    let occurrences = node.as_object_mut()
+        .expect("node is an object")
+        .entry(id)
+        .or_insert_with(|| json!([]));
+    if !occurrences.is_array() {
+        *occurrences = json!([]);
+    }
+    let occurrences = occurrences.as_array_mut().expect("occurrences are an array");
+    while occurrences.len() <= index {
+        occurrences.push(json!({}));
+    }
+    &mut occurrences[index]
+}
+
+/// FLAT form of a STRUCTURED composition
+pub fn from_structured(structured: &Value) -> Result<Map<String, Value>> {
+    let Some(root) = structured.as_object().filter(|root| root.len() == 1) else {
+        return Err(Error::InvalidFormat("/: expected a single root node".to_string()));
+    };
+
+    let mut flat = Map::new();
+    for (id, node) in root {
+        flatten(id, node, &mut flat);
+    }
+    Ok(flat)
+}
+
+fn flatten(path: &str, node: &Value, flat: &mut Map<String, Value>) {
+    let Value::Object(children) = node else {
+        flat.insert(path.to_string(), node.clone());
+        return;
+    };
+
+    for (key, value) in children {
+        if key.starts_with('|') {
+            flat.insert(format!("{path}{key}"), value.clone());
+            continue;
+        }
+        match value {
+            Value::Array(occurrences) => {
+                for (index, occurrence) in occurrences.iter().enumerate() {
+                    flatten(&format!("{path}/{key}:{index}"), occurrence, flat);
+                }
+            }
+            value => flatten(&format!("{path}/{key}"), value, flat),
+        }
+    }
+}
+
+// endregion: --- STRUCTURED
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ehr::ObjectVersionId;
+
+    #[test]
+    fn test_flat_and_structured_roundtrip() {
+        let template: WebTemplate = serde_json::from_str(include_str!("../../../templates/anima.vital_signs.v1.json")).unwrap();
+        let composition = CompositionBuilder::new(
+            ObjectVersionId::new_object().to_string(),
+            "did:iota:anima:p-1".to_string(),
+            "openEHR-EHR-COMPOSITION.encounter.v1",
+            "Vital signs",
+            "user:1",
+        )
+        .template_id("anima.vital_signs.v1")
+        .add_entry(Entry::Observation(
+            Observation::new("Blood pressure", "openEHR-EHR-OBSERVATION.blood_pressure.v2")
+                .add_item("Systolic", ObservationValue::Quantity(DvQuantity::new(120.0, "mm[Hg]")))
+                .add_item("Diastolic", ObservationValue::Quantity(DvQuantity::new(80.0, "mm[Hg]"))),
+        ))
+        .add_entry(Entry::Observation(
+            Observation::new("Pulse/Heart beat", "openEHR-EHR-OBSERVATION.pulse.v2")
+                .add_item("Rate", ObservationValue::Quantity(DvQuantity::new(72.0, "/min")))
+                .add_item("Regularity", ObservationValue::CodedText(DvCodedText::new("Regular", "local", "at1006"))),
+        ))
+        .build();
+
+        let flat = to_flat(&composition, &template).unwrap();
+        assert_eq!(flat["vital_signs/blood_pressure:0/systolic|magnitude"], json!(120.0));
+        assert_eq!(flat["vital_signs/pulse:0/regularity|code"], json!("at1006"));
+        assert_eq!(flat["vital_signs/category|code"], json!("433"));
+
+        let imported = from_flat(&flat, &template).unwrap();
+        assert_eq!(imported.composer, "user:1");
+        assert_eq!(imported.template_id.as_deref(), Some("anima.vital_signs.v1"));
+        assert_eq!(serde_json::to_value(&imported.content).unwrap(), serde_json::to_value(&composition.content).unwrap());
+
+        // STRUCTURED nests the same paths; occurrence 0 may drop its index
+        let structured = to_structured(&flat);
+        assert_eq!(structured["vital_signs"]["blood_pressure"][0]["systolic"][0]["|unit"], json!("mm[Hg]"));
+        let reimported = from_flat(&from_structured(&structured).unwrap(), &template).unwrap();
+        assert_eq!(serde_json::to_value(&reimported.content).unwrap(), serde_json::to_value(&composition.content).unwrap());
+
+        let mut unknown = flat.clone();
+        unknown.insert("vital_signs/blood_pressure:0/mean_arterial|magnitude".to_string(), json!(93.0));
+        assert!(matches!(from_flat(&unknown, &template), Err(Error::InvalidFormat(_))));
+    }
+}
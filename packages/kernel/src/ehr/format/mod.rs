@@ -0,0 +1,111 @@
+mod canonical;
+mod flat;
+
+pub use self::canonical::{to_canonical, from_canonical};
+pub use self::flat::{to_flat, from_flat, to_structured, from_structured};
+
+use crate::ehr::{Composition, Error, Result, TemplateRegistry, WebTemplate};
+use serde_json::Value;
+
+/// openEHR canonical JSON (`application/json` stays the internal layout)
+pub const CANONICAL_JSON: &str = "application/openehr+json";
+pub const FLAT_JSON: &str = "application/openehr.wt.flat+json";
+pub const STRUCTURED_JSON: &str = "application/openehr.wt.structured+json";
+
+/// Wire format of a composition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompositionFormat {
+    Internal,
+    Canonical,
+    Flat,
+    Structured,
+}
+
+impl CompositionFormat {
+    /// Format of a media type; parameters (`; charset=utf-8`) are ignored
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/json" => Some(Self::Internal),
+            CANONICAL_JSON => Some(Self::Canonical),
+            FLAT_JSON => Some(Self::Flat),
+            STRUCTURED_JSON => Some(Self::Structured),
+            _ => None,
+        }
+    }
+
+    /// First format of an Accept header this side can produce, in listed order
+    /// (q-values are not weighed); wildcards get the internal layout
+    pub fn negotiate(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(|media_type| {
+            match media_type.split(';').next().unwrap_or_default().trim() {
+                "*/*" | "application/*" => Some(Self::Internal),
+                media_type => Self::from_media_type(media_type),
+            }
+        })
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Internal => "application/json",
+            Self::Canonical => CANONICAL_JSON,
+            Self::Flat => FLAT_JSON,
+            Self::Structured => STRUCTURED_JSON,
+        }
+    }
+}
+
+/// A stored composition in `format`; FLAT and STRUCTURED paths come from its template
+pub fn export(composition: &Composition, format: CompositionFormat, templates: &TemplateRegistry) -> Result<Value> {
+    let template = composition.template_id.as_deref().and_then(|template_id| templates.get(template_id));
+    let required = || template.ok_or_else(|| Error::TemplateRequired(composition.archetype_id.clone()));
+
+    match format {
+        CompositionFormat::Internal => serde_json::to_value(composition)
+            .map_err(|e| Error::InvalidFormat(e.to_string())),
+        CompositionFormat::Canonical => Ok(to_canonical(composition, template)),
+        CompositionFormat::Flat => Ok(Value::Object(to_flat(composition, required()?)?)),
+        CompositionFormat::Structured => Ok(to_structured(&to_flat(composition, required()?)?)),
+    }
+}
+
+/// A composition from a document in `format`
+///
+/// FLAT and STRUCTURED documents are read with `template_id`, or the template
+/// whose id their paths start with. The composition still has to be stamped
+/// (uid, subject, composer) and validated before it is committed.
+pub fn import(document: &Value, format: CompositionFormat, template_id: Option<&str>, templates: &TemplateRegistry) -> Result<Composition> {
+    match format {
+        CompositionFormat::Internal => serde_json::from_value(document.clone())
+            .map_err(|e| Error::InvalidFormat(e.to_string())),
+        CompositionFormat::Canonical => {
+            let mut composition = from_canonical(document)?;
+            if let Some(template_id) = template_id {
+                composition.template_id = Some(template_id.to_string());
+            }
+            Ok(composition)
+        }
+        CompositionFormat::Flat => {
+            let Value::Object(flat) = document else {
+                return Err(Error::InvalidFormat("/: expected an object of FLAT paths".to_string()));
+            };
+            from_flat(flat, flat_template(flat.keys().next(), template_id, templates)?)
+        }
+        CompositionFormat::Structured => {
+            let flat = from_structured(document)?;
+            from_flat(&flat, flat_template(flat.keys().next(), template_id, templates)?)
+        }
+    }
+}
+
+fn flat_template<'a>(first_path: Option<&String>, template_id: Option<&str>, templates: &'a TemplateRegistry) -> Result<&'a WebTemplate> {
+    if let Some(template_id) = template_id {
+        return templates.get(template_id).ok_or_else(|| Error::TemplateNotFound(template_id.to_string()));
+    }
+
+    let tree_id = first_path
+        .and_then(|path| path.split(['/', '|', ':']).next())
+        .ok_or_else(|| Error::InvalidFormat("/: empty document".to_string()))?;
+    templates.by_tree_id(tree_id)
+        .ok_or_else(|| Error::TemplateRequired(tree_id.to_string()))
+}
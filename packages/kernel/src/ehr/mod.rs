@@ -1,8 +1,16 @@
+mod error;
 mod composition;
 mod entry;
 mod data_types;
+mod container;
+mod versioning;
+mod template;
+pub mod format;
 
+pub use self::error::{Error, Result};
 pub use self::composition::{Composition, CompositionBuilder, CompositionCategory};
+pub use self::container::Ehr;
+pub use self::versioning::{ObjectVersionId, VersionedComposition, OriginalVersion, Contribution, AuditDetails, ChangeType, RevisionHistoryItem};
+pub use self::template::{TemplateRegistry, WebTemplate, Violation, DEMOGRAPHICS_TEMPLATE_ID};
 pub use self::entry::{Entry, Observation, Evaluation, ObservationValue};
 pub use self::data_types::{DvText, DvDateTime, DvCodedText, DvQuantity};
-
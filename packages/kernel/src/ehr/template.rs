@@ -0,0 +1,623 @@
+use crate::ehr::{Composition, Entry, Error, ObservationValue, Result, DvText, DvCodedText};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Template every patient registration (demographics composition) is validated against
+pub const DEMOGRAPHICS_TEMPLATE_ID: &str = "anima.patient_demographics.v1";
+
+const DEMOGRAPHICS_TEMPLATE: &str = include_str!("../../templates/anima.patient_demographics.v1.json");
+
+const ENTRY_TYPES: [&str; 4] = ["OBSERVATION", "EVALUATION", "INSTRUCTION", "ACTION"];
+
+// region: --- Web Template
+
+/// Operational template in web template JSON form
+///
+/// Only the parts validation needs are read; any other web template fields are ignored.
+/// Entries may sit anywhere under the composition (e.g. in sections) and element values
+/// anywhere under their entry (e.g. in events and item trees); a node is only required
+/// where every node above it, up to the entry or composition, is required too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebTemplate {
+    pub template_id: String,
+    pub version: Option<String>,
+    pub default_language: Option<String>,
+    pub tree: TemplateNode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateNode {
+    pub id: String,
+    pub name: Option<String>,
+    pub rm_type: String,
+    /// Archetype id (entries, composition) or at-code (elements)
+    pub node_id: Option<String>,
+    #[serde(default)]
+    pub min: i32,
+    /// -1 = unbounded
+    #[serde(default = "unbounded")]
+    pub max: i32,
+    /// RM attributes filled in by the system (time, language, ...) - never validated
+    #[serde(default)]
+    pub in_context: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TemplateNode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<TemplateInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInput {
+    /// magnitude / unit (DV_QUANTITY), code (DV_CODED_TEXT)
+    pub suffix: Option<String>,
+    #[serde(rename = "type")]
+    pub input_type: String,
+    pub terminology: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub list: Vec<InputItem>,
+    pub validation: Option<InputValidation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputItem {
+    pub value: String,
+    pub label: Option<String>,
+    /// Magnitude range of a unit
+    pub validation: Option<InputValidation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputValidation {
+    pub range: Option<ValueRange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueRange {
+    pub min: Option<f64>,
+    /// `>=` (default) or `>`
+    pub min_op: Option<String>,
+    pub max: Option<f64>,
+    /// `<=` (default) or `<`
+    pub max_op: Option<String>,
+}
+
+fn unbounded() -> i32 {
+    -1
+}
+
+impl TemplateNode {
+    pub(crate) fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    fn key(&self) -> &str {
+        self.node_id.as_deref().unwrap_or(self.label())
+    }
+
+    pub(crate) fn input(&self, suffix: &str) -> Option<&TemplateInput> {
+        self.inputs.iter().find(|input| input.suffix.as_deref() == Some(suffix))
+    }
+
+    /// Whether `entry` fills this entry node - by archetype id, or by name for
+    /// instructions and actions, which carry no archetype id
+    pub(crate) fn matches_entry(&self, entry: &Entry) -> bool {
+        self.rm_type == entry.rm_type() && match entry.archetype_id() {
+            Some(archetype_id) => self.node_id.as_deref() == Some(archetype_id),
+            None => self.label() == entry.name().value,
+        }
+    }
+
+    /// Element nodes below this entry node, in template order
+    pub(crate) fn element_slots(&self) -> Vec<Slot<'_>> {
+        let mut slots = Vec::new();
+        collect_slots(self, is_element, &[], true, true, &mut slots);
+        slots
+    }
+
+    /// Value node of an element for a data value type (ELEMENT nodes offer a choice)
+    pub(crate) fn value_node(&self, rm_type: &str) -> Option<&TemplateNode> {
+        if self.rm_type != "ELEMENT" {
+            return (self.rm_type == rm_type).then_some(self);
+        }
+        self.children.iter().find(|child| child.rm_type == rm_type)
+    }
+}
+
+impl ValueRange {
+    fn contains(&self, magnitude: f64) -> bool {
+        let above_min = match (self.min, self.min_op.as_deref()) {
+            (Some(min), Some(">")) => magnitude > min,
+            (Some(min), _) => magnitude >= min,
+            (None, _) => true,
+        };
+        let below_max = match (self.max, self.max_op.as_deref()) {
+            (Some(max), Some("<")) => magnitude < max,
+            (Some(max), _) => magnitude <= max,
+            (None, _) => true,
+        };
+        above_min && below_max
+    }
+}
+
+impl core::fmt::Display for ValueRange {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let open = if self.min_op.as_deref() == Some(">") { "(" } else { "[" };
+        let close = if self.max_op.as_deref() == Some("<") { ")" } else { "]" };
+        let bound = |bound: Option<f64>| bound.map(|b| b.to_string()).unwrap_or_else(|| "*".to_string());
+        write!(fmt, "{open}{}, {}{close}", bound(self.min), bound(self.max))
+    }
+}
+
+// endregion: --- Web Template
+
+// region: --- Validation
+
+/// One way a composition breaks its template
+///
+/// `path` addresses the offending node: `[n]` indexes the submitted array,
+/// `[name]` names a template node the composition is missing,
+/// e.g. `/content[0]/data/items[1]/value/magnitude` or `/content[openEHR-EHR-OBSERVATION.blood_pressure.v2]`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), message: message.into() }
+    }
+}
+
+/// A template node with its occurrences relative to the entry or composition
+pub(crate) struct Slot<'a> {
+    pub node: &'a TemplateNode,
+    /// Nodes between the entry or composition and `node` (events, item trees, sections)
+    pub path: Vec<&'a TemplateNode>,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// Nodes matching `is_slot` below `node`, not descending into them
+fn collect_slots<'a>(
+    node: &'a TemplateNode,
+    is_slot: fn(&TemplateNode) -> bool,
+    path: &[&'a TemplateNode],
+    required: bool,
+    bounded: bool,
+    slots: &mut Vec<Slot<'a>>,
+) {
+    for child in node.children.iter().filter(|child| !child.in_context) {
+        if is_slot(child) {
+            slots.push(Slot {
+                node: child,
+                path: path.to_vec(),
+                min: if required { child.min } else { 0 },
+                max: if bounded { child.max } else { -1 },
+            });
+        } else {
+            let path = [path, &[child]].concat();
+            collect_slots(child, is_slot, &path, required && child.min >= 1, bounded && child.max == 1, slots);
+        }
+    }
+}
+
+fn is_entry(node: &TemplateNode) -> bool {
+    ENTRY_TYPES.contains(&node.rm_type.as_str())
+}
+
+fn is_element(node: &TemplateNode) -> bool {
+    node.rm_type == "ELEMENT" || node.rm_type.starts_with("DV_")
+}
+
+/// Occurrence violations of every slot, given how often each was filled
+///
+/// Missing or surplus slots are addressed by `key` (archetype id for entries, name for elements).
+fn check_occurrences(
+    slots: &[Slot],
+    counts: &[i32],
+    path: &str,
+    key: fn(&TemplateNode) -> &str,
+    violations: &mut Vec<Violation>,
+) {
+    for (slot, count) in slots.iter().zip(counts) {
+        let slot_path = format!("{path}[{}]", key(slot.node));
+        if *count < slot.min {
+            violations.push(Violation::new(slot_path, format!(
+                "{} is required at least {} time(s), found {count}", slot.node.label(), slot.min
+            )));
+        } else if slot.max >= 0 && *count > slot.max {
+            violations.push(Violation::new(slot_path, format!(
+                "{} is allowed at most {} time(s), found {count}", slot.node.label(), slot.max
+            )));
+        }
+    }
+}
+
+impl WebTemplate {
+    /// Entry nodes of the template, in template order
+    pub(crate) fn entry_slots(&self) -> Vec<Slot<'_>> {
+        let mut slots = Vec::new();
+        collect_slots(&self.tree, is_entry, &[], true, true, &mut slots);
+        slots
+    }
+
+    /// Every violation of this template by `composition` (empty = valid)
+    pub fn validate(&self, composition: &Composition) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        if self.tree.node_id.as_deref() != Some(composition.archetype_id.as_str()) {
+            violations.push(Violation::new("/archetype_id", format!(
+                "Template {} is for {}, not {}", self.template_id, self.tree.key(), composition.archetype_id
+            )));
+        }
+
+        let slots = self.entry_slots();
+        let mut counts = vec![0; slots.len()];
+
+        for (index, entry) in composition.content.iter().enumerate() {
+            let path = format!("/content[{index}]");
+            let Some(slot) = slots.iter().position(|slot| slot.node.matches_entry(entry)) else {
+                violations.push(Violation::new(path, format!(
+                    "{} {} is not allowed by template {}",
+                    entry.rm_type(), entry.archetype_id().unwrap_or(&entry.name().value), self.template_id
+                )));
+                continue;
+            };
+            counts[slot] += 1;
+
+            match entry {
+                Entry::Observation(observation) => {
+                    let items: Vec<(&DvText, ObservationValue)> = observation.data.items.iter()
+                        .map(|item| (&item.name, item.value.clone()))
+                        .collect();
+                    validate_items(slots[slot].node, &items, &path, &mut violations);
+                }
+                Entry::Evaluation(evaluation) => {
+                    let items: Vec<(&DvText, ObservationValue)> = evaluation.data.items.iter()
+                        .map(|item| (&item.name, ObservationValue::Text(item.value.clone())))
+                        .collect();
+                    validate_items(slots[slot].node, &items, &path, &mut violations);
+                }
+                Entry::Instruction(_) | Entry::Action(_) => {}
+            }
+        }
+
+        check_occurrences(&slots, &counts, "/content", TemplateNode::key, &mut violations);
+        violations
+    }
+}
+
+/// Items of one entry against the elements of its template node
+fn validate_items(entry_node: &TemplateNode, items: &[(&DvText, ObservationValue)], path: &str, violations: &mut Vec<Violation>) {
+    let slots = entry_node.element_slots();
+    let mut counts = vec![0; slots.len()];
+
+    for (index, (name, value)) in items.iter().enumerate() {
+        let item_path = format!("{path}/data/items[{index}]");
+        let Some(slot) = slots.iter().position(|slot| slot.node.label() == name.value) else {
+            violations.push(Violation::new(item_path, format!(
+                "{} is not an element of {}", name.value, entry_node.key()
+            )));
+            continue;
+        };
+        counts[slot] += 1;
+        validate_value(slots[slot].node, value, &format!("{item_path}/value"), violations);
+    }
+
+    check_occurrences(&slots, &counts, &format!("{path}/data/items"), TemplateNode::label, violations);
+}
+
+/// Data value type and constraints of one element
+fn validate_value(element: &TemplateNode, value: &ObservationValue, path: &str, violations: &mut Vec<Violation>) {
+    // An ELEMENT node offers a choice of value types, one child per type
+    let candidates: Vec<&TemplateNode> = if element.rm_type == "ELEMENT" {
+        element.children.iter().filter(|child| child.rm_type.starts_with("DV_")).collect()
+    } else {
+        vec![element]
+    };
+
+    let rm_type = value.rm_type();
+    // DV_CODED_TEXT is a DV_TEXT
+    let node = candidates.iter()
+        .find(|node| node.rm_type == rm_type)
+        .or_else(|| candidates.iter().find(|node| rm_type == "DV_CODED_TEXT" && node.rm_type == "DV_TEXT"));
+    let Some(node) = node else {
+        let allowed: Vec<&str> = candidates.iter().map(|node| node.rm_type.as_str()).collect();
+        violations.push(Violation::new(path, format!(
+            "{rm_type} is not allowed for {}, expected {}", element.label(), allowed.join(" or ")
+        )));
+        return;
+    };
+
+    match value {
+        ObservationValue::Quantity(quantity) => {
+            let mut range = node.input("magnitude")
+                .and_then(|input| input.validation.as_ref())
+                .and_then(|validation| validation.range.as_ref());
+
+            if let Some(unit) = node.input("unit").filter(|input| !input.list.is_empty()) {
+                match unit.list.iter().find(|item| item.value == quantity.units) {
+                    Some(item) => {
+                        if let Some(unit_range) = item.validation.as_ref().and_then(|v| v.range.as_ref()) {
+                            range = Some(unit_range);
+                        }
+                    }
+                    None => {
+                        let allowed: Vec<&str> = unit.list.iter().map(|item| item.value.as_str()).collect();
+                        violations.push(Violation::new(format!("{path}/units"), format!(
+                            "Unit {} is not allowed, expected {}", quantity.units, allowed.join(" or ")
+                        )));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(range) = range.filter(|range| !range.contains(quantity.magnitude)) {
+                violations.push(Violation::new(format!("{path}/magnitude"), format!(
+                    "{} {} is outside {range}", quantity.magnitude, quantity.units
+                )));
+            }
+        }
+        ObservationValue::CodedText(coded) if node.rm_type == "DV_CODED_TEXT" => {
+            let Some(input) = node.input("code").or(node.inputs.first()) else { return };
+            let code_path = format!("{path}/defining_code");
+
+            if let Some(terminology) = input.terminology.as_ref().filter(|t| **t != coded.defining_code.terminology_id) {
+                violations.push(Violation::new(format!("{code_path}/terminology_id"), format!(
+                    "Terminology {} is not allowed, expected {terminology}", coded.defining_code.terminology_id
+                )));
+            }
+            if !input.list.is_empty() && !input.list.iter().any(|item| item.value == coded.defining_code.code_string) {
+                let allowed: Vec<&str> = input.list.iter().map(|item| item.value.as_str()).collect();
+                violations.push(Violation::new(format!("{code_path}/code_string"), format!(
+                    "Code {} is not allowed, expected {}", coded.defining_code.code_string, allowed.join(" or ")
+                )));
+            }
+        }
+        ObservationValue::Text(DvText { value: text }) | ObservationValue::CodedText(DvCodedText { value: text, .. }) => {
+            if let Some(input) = node.inputs.iter().find(|input| !input.list.is_empty()) {
+                if !input.list.iter().any(|item| &item.value == text) {
+                    violations.push(Violation::new(format!("{path}/value"), format!(
+                        "{text} is not one of the allowed values of {}", element.label()
+                    )));
+                }
+            }
+        }
+        ObservationValue::DateTime(_) => {}
+    }
+}
+
+// endregion: --- Validation
+
+// region: --- Registry
+
+/// Operational templates compositions are validated against, by template id
+///
+/// A composition names its template in `template_id`; without one it is validated
+/// against the only template for its archetype, if any. Compositions no template
+/// covers are refused, unless templates are explicitly made optional.
+#[derive(Clone)]
+pub struct TemplateRegistry {
+    templates: Arc<HashMap<String, WebTemplate>>,
+    required: bool,
+}
+
+impl TemplateRegistry {
+    /// Built-in templates only
+    pub fn new() -> Self {
+        let demographics: WebTemplate = serde_json::from_str(DEMOGRAPHICS_TEMPLATE)
+            .expect("built-in demographics template is valid");
+
+        Self {
+            templates: Arc::new(HashMap::new()),
+            required: true,
+        }
+        .with_template(demographics)
+    }
+
+    /// Built-in templates plus the web templates (`*.json`) in EHR_TEMPLATE_DIR
+    /// (default `templates`); EHR_TEMPLATES_REQUIRED=false stores untemplated
+    /// compositions unvalidated instead of refusing them
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("EHR_TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_string());
+        let required = !std::env::var("EHR_TEMPLATES_REQUIRED")
+            .is_ok_and(|value| value.eq_ignore_ascii_case("false"));
+        if !required {
+            println!("->> ⚠️  EHR_TEMPLATES_REQUIRED=false - compositions no template covers are stored unvalidated");
+        }
+
+        let registry = Self::new().with_templates_required(required);
+        if !std::path::Path::new(&dir).is_dir() {
+            println!("->> Templates: {dir} not found, using built-in templates");
+            return Ok(registry);
+        }
+
+        registry.load_dir(&dir)
+    }
+
+    pub fn load_dir(mut self, dir: impl AsRef<std::path::Path>) -> Result<Self> {
+        let invalid = |path: &std::path::Path, e: String| Error::InvalidTemplate(format!("{}: {e}", path.display()));
+
+        let mut paths: Vec<_> = std::fs::read_dir(dir.as_ref())
+            .map_err(|e| invalid(dir.as_ref(), e.to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let json = std::fs::read_to_string(&path).map_err(|e| invalid(&path, e.to_string()))?;
+            let template: WebTemplate = serde_json::from_str(&json).map_err(|e| invalid(&path, e.to_string()))?;
+            if template.tree.rm_type != "COMPOSITION" {
+                return Err(invalid(&path, format!("root is {}, not COMPOSITION", template.tree.rm_type)));
+            }
+
+            println!("->> Templates: {} ({})", template.template_id, template.tree.key());
+            self = self.with_template(template);
+        }
+
+        Ok(self)
+    }
+
+    pub fn with_template(mut self, template: WebTemplate) -> Self {
+        Arc::make_mut(&mut self.templates).insert(template.template_id.clone(), template);
+        self
+    }
+
+    pub fn with_templates_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    pub fn get(&self, template_id: &str) -> Option<&WebTemplate> {
+        self.templates.get(template_id)
+    }
+
+    /// The only template whose tree (FLAT path root) is `tree_id`
+    pub fn by_tree_id(&self, tree_id: &str) -> Option<&WebTemplate> {
+        let mut matching = self.templates.values().filter(|template| template.tree.id == tree_id);
+        match (matching.next(), matching.next()) {
+            (Some(template), None) => Some(template),
+            _ => None,
+        }
+    }
+
+    /// Every template, by template id
+    pub fn list(&self) -> Vec<&WebTemplate> {
+        let mut templates: Vec<&WebTemplate> = self.templates.values().collect();
+        templates.sort_by(|a, b| a.template_id.cmp(&b.template_id));
+        templates
+    }
+
+    /// Validate a composition before it is stored
+    ///
+    /// Returns the template it was validated against (None = untemplated).
+    pub fn validate(&self, composition: &Composition) -> Result<Option<&WebTemplate>> {
+        let template = match &composition.template_id {
+            Some(template_id) => self.get(template_id)
+                .ok_or_else(|| Error::TemplateNotFound(template_id.clone()))?,
+            None => {
+                let candidates: Vec<&WebTemplate> = self.templates.values()
+                    .filter(|template| template.tree.node_id.as_ref() == Some(&composition.archetype_id))
+                    .collect();
+                match candidates.as_slice() {
+                    [template] => *template,
+                    [] if !self.required => return Ok(None),
+                    _ => return Err(Error::TemplateRequired(composition.archetype_id.clone())),
+                }
+            }
+        };
+
+        let violations = template.validate(composition);
+        if !violations.is_empty() {
+            return Err(Error::TemplateViolations {
+                template_id: template.template_id.clone(),
+                violations,
+            });
+        }
+
+        Ok(Some(template))
+    }
+}
+
+// endregion: --- Registry
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ehr::{CompositionBuilder, DvQuantity, Observation, ObjectVersionId};
+
+    fn vitals(observations: Vec<Observation>) -> Composition {
+        let mut builder = CompositionBuilder::new(
+            ObjectVersionId::new_object().to_string(),
+            "did:iota:anima:p-1".to_string(),
+            "openEHR-EHR-COMPOSITION.encounter.v1",
+            "Vitals",
+            "user:1",
+        );
+        for observation in observations {
+            builder = builder.add_entry(Entry::Observation(observation));
+        }
+        builder.build()
+    }
+
+    fn registry() -> TemplateRegistry {
+        TemplateRegistry::new()
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/templates"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_validate_vital_signs() {
+        let registry = registry();
+        let blood_pressure = |systolic: f64, units: &str| Observation::new("Blood pressure", "openEHR-EHR-OBSERVATION.blood_pressure.v2")
+            .add_item("Systolic", ObservationValue::Quantity(DvQuantity::new(systolic, units)))
+            .add_item("Diastolic", ObservationValue::Quantity(DvQuantity::new(80.0, "mm[Hg]")));
+
+        // Matched by archetype, no template id needed
+        let valid = vitals(vec![blood_pressure(120.0, "mm[Hg]")]);
+        assert_eq!(registry.validate(&valid).unwrap().unwrap().template_id, "anima.vital_signs.v1");
+
+        let invalid = vitals(vec![
+            blood_pressure(1200.0, "mm[Hg]"),
+            blood_pressure(120.0, "kPa"),
+            Observation::new("Pulse", "openEHR-EHR-OBSERVATION.pulse.v2")
+                .add_item("Regularity", ObservationValue::CodedText(DvCodedText::new("Fast", "local", "at9999")))
+                .add_item("Rhythm", ObservationValue::Text(DvText::new("Sinus"))),
+            Observation::new("Mood", "openEHR-EHR-OBSERVATION.mood.v1"),
+        ]);
+        let Err(Error::TemplateViolations { violations, .. }) = registry.validate(&invalid) else {
+            panic!("expected violations");
+        };
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec![
+            "/content[0]/data/items[0]/value/magnitude",
+            "/content[1]/data/items[0]/value/units",
+            "/content[2]/data/items[0]/value/defining_code/code_string",
+            "/content[2]/data/items[1]",
+            "/content[2]/data/items[Rate]",
+            "/content[3]",
+        ]);
+
+        // Wrong data value type
+        let mut wrong_type = valid.clone();
+        let Entry::Observation(observation) = &mut wrong_type.content[0] else { unreachable!() };
+        observation.data.items[0].value = ObservationValue::Text(DvText::new("120"));
+        let Err(Error::TemplateViolations { violations, .. }) = registry.validate(&wrong_type) else {
+            panic!("expected violations");
+        };
+        assert_eq!(violations[0].path, "/content[0]/data/items[0]/value");
+    }
+
+    #[test]
+    fn test_validate_required_and_unknown() {
+        let registry = registry();
+
+        let mut demographics = vitals(vec![]);
+        demographics.archetype_id = "openEHR-EHR-COMPOSITION.person.v1".to_string();
+        demographics.template_id = Some(DEMOGRAPHICS_TEMPLATE_ID.to_string());
+        let Err(Error::TemplateViolations { violations, .. }) = registry.validate(&demographics) else {
+            panic!("expected violations");
+        };
+        assert_eq!(violations, vec![Violation::new(
+            "/content[openEHR-EHR-OBSERVATION.demographics.v1]",
+            "Patient Demographics is required at least 1 time(s), found 0",
+        )]);
+
+        let mut unknown = vitals(vec![]);
+        unknown.template_id = Some("anima.unknown.v1".to_string());
+        assert!(matches!(registry.validate(&unknown), Err(Error::TemplateNotFound(_))));
+
+        // No template for the archetype: refused, unless templates are made optional
+        let mut untemplated = vitals(vec![]);
+        untemplated.archetype_id = "openEHR-EHR-COMPOSITION.report.v1".to_string();
+        assert!(matches!(registry.validate(&untemplated), Err(Error::TemplateRequired(_))));
+        assert!(registry.with_templates_required(false).validate(&untemplated).unwrap().is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::ehr::{Composition, Error, Result};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+/// Creating system of every version committed here
+pub const SYSTEM_ID: &str = "anima.health";
+
+/// openEHR OBJECT_VERSION_ID: `{object_id}::{creating_system_id}::{version}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectVersionId {
+    pub object_id: String,
+    pub creating_system_id: String,
+    pub version: u32,
+}
+
+impl ObjectVersionId {
+    /// Version 1 of a new versioned object
+    pub fn new_object() -> Self {
+        Self {
+            object_id: uuid::Uuid::new_v4().to_string(),
+            creating_system_id: SYSTEM_ID.to_string(),
+            version: 1,
+        }
+    }
+
+    pub fn parse(uid: &str) -> Result<Self> {
+        let invalid = || Error::InvalidVersionUid(uid.to_string());
+
+        let mut parts = uid.split("::");
+        let (Some(object_id), Some(creating_system_id), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        if object_id.is_empty() || creating_system_id.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            object_id: object_id.to_string(),
+            creating_system_id: creating_system_id.to_string(),
+            version: version.parse().ok().filter(|version| *version > 0).ok_or_else(invalid)?,
+        })
+    }
+
+    /// The version that follows this one
+    pub fn next(&self) -> Self {
+        Self { version: self.version + 1, ..self.clone() }
+    }
+}
+
+impl core::fmt::Display for ObjectVersionId {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "{}::{}::{}", self.object_id, self.creating_system_id, self.version)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Creation,
+    Modification,
+}
+
+/// openEHR AUDIT_DETAILS - who committed a change, when and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditDetails {
+    pub system_id: String,
+    pub committer: String,
+    pub time_committed: DateTime<Utc>,
+    pub change_type: ChangeType,
+    pub description: Option<String>,
+}
+
+impl AuditDetails {
+    pub fn new(committer: impl Into<String>, change_type: ChangeType, description: Option<String>) -> Self {
+        Self {
+            system_id: SYSTEM_ID.to_string(),
+            committer: committer.into(),
+            time_committed: Utc::now(),
+            change_type,
+            description,
+        }
+    }
+}
+
+/// openEHR CONTRIBUTION - the versions committed together, with one audit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contribution {
+    pub uid: String,
+    /// Version uids committed
+    pub versions: Vec<String>,
+    pub audit: AuditDetails,
+}
+
+/// openEHR ORIGINAL_VERSION of a composition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginalVersion {
+    pub uid: String,
+    pub preceding_version_uid: Option<String>,
+    /// Contribution uid
+    pub contribution: String,
+    pub commit_audit: AuditDetails,
+    pub data: Composition,
+}
+
+/// openEHR VERSIONED_COMPOSITION - every version of one composition, oldest first
+///
+/// Versions are only ever appended; earlier versions stay readable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionedComposition {
+    /// Object id shared by all versions
+    pub uid: String,
+    /// EHR id
+    pub owner_id: String,
+    pub time_created: DateTime<Utc>,
+    pub versions: Vec<OriginalVersion>,
+}
+
+impl VersionedComposition {
+    pub fn latest(&self) -> &OriginalVersion {
+        self.versions.last().expect("versioned composition without versions")
+    }
+
+    pub fn version(&self, version_uid: &str) -> Option<&OriginalVersion> {
+        self.versions.iter().find(|version| version.uid == version_uid)
+    }
+
+    /// Audit trail of every version, without the data
+    pub fn revision_history(&self) -> Vec<RevisionHistoryItem> {
+        self.versions.iter()
+            .map(|version| RevisionHistoryItem {
+                version_uid: version.uid.clone(),
+                preceding_version_uid: version.preceding_version_uid.clone(),
+                contribution: version.contribution.clone(),
+                audit: version.commit_audit.clone(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionHistoryItem {
+    pub version_uid: String,
+    pub preceding_version_uid: Option<String>,
+    pub contribution: String,
+    pub audit: AuditDetails,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_version_id() {
+        let uid = ObjectVersionId::parse("8849182c-82ad-4088-a07f-48ead4180515::anima.health::2").unwrap();
+        assert_eq!(uid.object_id, "8849182c-82ad-4088-a07f-48ead4180515");
+        assert_eq!(uid.version, 2);
+        assert_eq!(uid.next().to_string(), "8849182c-82ad-4088-a07f-48ead4180515::anima.health::3");
+
+        for invalid in ["c-1", "c-1::anima.health", "c-1::anima.health::0", "c-1::anima.health::v1", "::anima.health::1", "c-1::a::1::2"] {
+            assert!(ObjectVersionId::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Serialize, Deserialize};
+
+/// UCUM - units of every FHIR Quantity produced here
+pub const UCUM_SYSTEM: &str = "http://unitsofmeasure.org";
+
+/// Identifier system of URI values such as DIDs
+pub const URI_SYSTEM: &str = "urn:ietf:rfc:3986";
+
+/// HL7 v2 identifier types (MR = medical record number)
+pub const IDENTIFIER_TYPE_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v2-0203";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Coding {
+    pub system: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+impl Coding {
+    pub fn new(system: impl Into<String>, code: impl Into<String>, display: impl Into<String>) -> Self {
+        Self {
+            system: system.into(),
+            code: code.into(),
+            display: Some(display.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CodeableConcept {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coding: Vec<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl CodeableConcept {
+    /// Concept known by its text only
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { coding: Vec::new(), text: Some(text.into()) }
+    }
+
+    pub fn with_coding(mut self, coding: Coding) -> Self {
+        self.coding.push(coding);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Identifier {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub identifier_type: Option<CodeableConcept>,
+    pub system: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reference {
+    pub reference: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    /// Human-readable unit
+    pub unit: String,
+    /// UCUM, when the unit is a known UCUM code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HumanName {
+    #[serde(rename = "use")]
+    pub name_use: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub given: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Address {
+    pub text: String,
+}
+
+/// A value[x] element - serialized under its typed key (`valueQuantity`, ...)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Value {
+    #[serde(rename = "valueQuantity")]
+    Quantity(Quantity),
+    #[serde(rename = "valueCodeableConcept")]
+    CodeableConcept(CodeableConcept),
+    #[serde(rename = "valueString")]
+    String(String),
+    #[serde(rename = "valueDateTime")]
+    DateTime(String),
+}
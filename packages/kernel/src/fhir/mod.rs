@@ -0,0 +1,193 @@
+mod datatypes;
+mod patient;
+mod observation;
+
+pub use self::datatypes::{Address, CodeableConcept, Coding, HumanName, Identifier, Quantity, Reference, Value, IDENTIFIER_TYPE_SYSTEM, UCUM_SYSTEM, URI_SYSTEM};
+pub use self::patient::{FhirPatient, to_fhir_patient};
+pub use self::observation::{FhirObservation, to_fhir_observations};
+
+use crate::ehr::{Composition, DEMOGRAPHICS_TEMPLATE_ID};
+use crate::model::Patient;
+use chrono::{SecondsFormat, Utc};
+use serde::{Serialize, Deserialize};
+
+/// FHIR R4 JSON
+pub const FHIR_JSON: &str = "application/fhir+json";
+
+const DEFAULT_BASE_URL: &str = "http://localhost:8080";
+const DEFAULT_MRN_SYSTEM: &str = "urn:anima:mrn";
+
+/// Demographics compositions become the Patient resource, not observations
+const DEMOGRAPHICS_ARCHETYPE_ID: &str = "openEHR-EHR-COMPOSITION.person.v1";
+
+/// How records are presented as FHIR resources
+#[derive(Debug, Clone)]
+pub struct FhirConfig {
+    /// Public URL of the gateway, base of the Bundle entries' `fullUrl`
+    pub base_url: String,
+    /// Identifier system of medical record numbers
+    pub mrn_system: String,
+}
+
+impl FhirConfig {
+    pub fn new() -> Self {
+        Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            mrn_system: DEFAULT_MRN_SYSTEM.to_string(),
+        }
+    }
+
+    /// GATEWAY_BASE_URL and FHIR_MRN_SYSTEM
+    pub fn from_env() -> Self {
+        let mut config = Self::new();
+        if let Ok(base_url) = std::env::var("GATEWAY_BASE_URL") {
+            config = config.with_base_url(base_url);
+        }
+        if let Ok(mrn_system) = std::env::var("FHIR_MRN_SYSTEM") {
+            config = config.with_mrn_system(mrn_system);
+        }
+        config
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_mrn_system(mut self, mrn_system: impl Into<String>) -> Self {
+        self.mrn_system = mrn_system.into();
+        self
+    }
+
+    /// Absolute URL of a Patient resource (the only type served by id)
+    pub fn patient_url(&self, id: &str) -> String {
+        format!("{}/api/fhir/Patient/{}", self.base_url, id)
+    }
+}
+
+impl Default for FhirConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A FHIR resource, tagged with its `resourceType`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "resourceType")]
+pub enum Resource {
+    Patient(FhirPatient),
+    Observation(FhirObservation),
+    Bundle(Bundle),
+}
+
+/// FHIR R4 Bundle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub bundle_type: String,
+    pub timestamp: String,
+    pub total: usize,
+    pub entry: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntry {
+    pub full_url: String,
+    pub resource: Resource,
+    pub search: BundleEntrySearch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleEntrySearch {
+    /// `match` for the patient, `include` for what belongs to it
+    pub mode: String,
+}
+
+/// `Patient/$everything`: the patient followed by the observations of its
+/// clinical compositions (latest versions, in EHR order)
+///
+/// The patient is the one match (`total` 1). Observations are not served on
+/// their own, so their `fullUrl`s are `urn:uuid`s local to the Bundle.
+pub fn everything(patient: &Patient, compositions: &[Composition], config: &FhirConfig) -> Bundle {
+    let patient_entry = BundleEntry {
+        full_url: config.patient_url(&patient.id),
+        resource: Resource::Patient(to_fhir_patient(patient, compositions, config)),
+        search: BundleEntrySearch { mode: "match".to_string() },
+    };
+
+    let observation_entries = compositions.iter()
+        .filter(|composition| !is_demographics(composition))
+        .flat_map(|composition| to_fhir_observations(&patient.id, composition))
+        .map(|observation| BundleEntry {
+            full_url: format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+            resource: Resource::Observation(observation),
+            search: BundleEntrySearch { mode: "include".to_string() },
+        });
+
+    Bundle {
+        id: uuid::Uuid::new_v4().to_string(),
+        bundle_type: "searchset".to_string(),
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        total: 1,
+        entry: std::iter::once(patient_entry).chain(observation_entries).collect(),
+    }
+}
+
+fn is_demographics(composition: &Composition) -> bool {
+    composition.template_id.as_deref() == Some(DEMOGRAPHICS_TEMPLATE_ID)
+        || composition.archetype_id == DEMOGRAPHICS_ARCHETYPE_ID
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::did_manager::PatientDID;
+    use crate::ehr::{CompositionBuilder, DvQuantity, Entry, Observation, ObservationValue};
+    use crate::model::PatientDemographics;
+
+    #[test]
+    fn test_everything_bundle() {
+        let did = "did:iota:anima:p1".to_string();
+        let demographics = CompositionBuilder::new("d1::anima.health::1".to_string(), did.clone(), DEMOGRAPHICS_ARCHETYPE_ID, "Patient Demographics", "user:1")
+            .template_id(DEMOGRAPHICS_TEMPLATE_ID)
+            .add_entry(Entry::Observation(Observation::new("Patient Demographics", "openEHR-EHR-OBSERVATION.demographics.v1")
+                .add_item("Name", ObservationValue::Text(crate::ehr::DvText::new("Jane Doe")))))
+            .build();
+        let vitals = CompositionBuilder::new("v1::anima.health::1".to_string(), did.clone(), "openEHR-EHR-COMPOSITION.encounter.v1", "Vital signs", "user:1")
+            .add_entry(Entry::Observation(Observation::new("Body temperature", "openEHR-EHR-OBSERVATION.body_temperature.v2")
+                .add_item("Temperature", ObservationValue::Quantity(DvQuantity::new(37.2, "Cel")))))
+            .build();
+        let patient = Patient {
+            id: "p1".to_string(),
+            did: did.clone(),
+            demographics: PatientDemographics {
+                name: "Jane Doe".to_string(),
+                date_of_birth: "1985-03-14".to_string(),
+                medical_record_number: "MRN-001".to_string(),
+                gender: None,
+                address: None,
+            },
+            composition: demographics.clone(),
+            did_metadata: PatientDID::create("p1".to_string(), 1, KeyAlgorithm::default()).unwrap().0,
+            created_at: Utc::now(),
+            created_by: 1,
+        };
+
+        let bundle = everything(&patient, &[demographics, vitals], &FhirConfig::new().with_base_url("https://gw.example/"));
+
+        // The patient is the one match; demographics are not repeated as observations
+        assert_eq!(bundle.total, 1);
+        assert_eq!(bundle.entry.len(), 2);
+        assert_eq!(bundle.entry[0].full_url, "https://gw.example/api/fhir/Patient/p1");
+        let Resource::Observation(observation) = &bundle.entry[1].resource else {
+            panic!("expected an Observation");
+        };
+        assert_eq!(observation.id, "v1-0");
+        assert!(bundle.entry[1].full_url.starts_with("urn:uuid:"));
+        assert_eq!(bundle.entry[1].search.mode, "include");
+    }
+}
@@ -0,0 +1,230 @@
+use crate::ehr::{Composition, DvCodedText, DvQuantity, Entry, Observation, ObjectVersionId, ObservationValue};
+use crate::fhir::{CodeableConcept, Coding, Quantity, Reference, Value, UCUM_SYSTEM};
+use chrono::SecondsFormat;
+use serde::{Serialize, Deserialize};
+
+pub const LOINC_SYSTEM: &str = "http://loinc.org";
+
+const OBSERVATION_CATEGORY_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/observation-category";
+
+/// LOINC codes of the vital signs archetypes: (archetype, element name or None for the
+/// whole observation, code, display)
+const VITAL_SIGNS_LOINC: &[(&str, Option<&str>, &str, &str)] = &[
+    ("openEHR-EHR-OBSERVATION.blood_pressure.v2", None, "85354-9", "Blood pressure panel with all children optional"),
+    ("openEHR-EHR-OBSERVATION.blood_pressure.v2", Some("Systolic"), "8480-6", "Systolic blood pressure"),
+    ("openEHR-EHR-OBSERVATION.blood_pressure.v2", Some("Diastolic"), "8462-4", "Diastolic blood pressure"),
+    ("openEHR-EHR-OBSERVATION.body_temperature.v2", None, "8310-5", "Body temperature"),
+    ("openEHR-EHR-OBSERVATION.pulse.v2", None, "8867-4", "Heart rate"),
+    ("openEHR-EHR-OBSERVATION.pulse.v2", Some("Rate"), "8867-4", "Heart rate"),
+];
+
+/// UCUM codes of clinical units: (code, display, common non-UCUM spellings)
+const UCUM_UNITS: &[(&str, &str, &[&str])] = &[
+    ("mm[Hg]", "mmHg", &["mmHg", "mm Hg"]),
+    ("Cel", "°C", &["°C", "degC", "C"]),
+    ("[degF]", "°F", &["°F", "degF", "F"]),
+    ("/min", "/min", &["bpm", "beats/min", "/minute"]),
+    ("%", "%", &[]),
+    ("kg", "kg", &[]),
+    ("g", "g", &[]),
+    ("[lb_av]", "lb", &["lb", "lbs"]),
+    ("cm", "cm", &[]),
+    ("m", "m", &[]),
+    ("[in_i]", "in", &["in"]),
+    ("kg/m2", "kg/m2", &["kg/m^2"]),
+    ("mg/dL", "mg/dL", &["mg/dl"]),
+    ("mmol/L", "mmol/L", &["mmol/l"]),
+    ("L/min", "L/min", &["l/min"]),
+];
+
+/// FHIR R4 Observation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirObservation {
+    pub id: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub category: Vec<CodeableConcept>,
+    pub code: CodeableConcept,
+    pub subject: Reference,
+    pub effective_date_time: String,
+    /// Single-element observations carry their value here, others in `component`
+    #[serde(flatten)]
+    pub value: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub component: Vec<ObservationComponent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObservationComponent {
+    pub code: CodeableConcept,
+    #[serde(flatten)]
+    pub value: Value,
+}
+
+/// Observations of a composition's OBSERVATION entries, about `Patient/{patient_id}`
+///
+/// Resource ids are `{versioned object id}-{entry index}`, stable across versions of
+/// the composition; later versions are reported as `amended`.
+pub fn to_fhir_observations(patient_id: &str, composition: &Composition) -> Vec<FhirObservation> {
+    let version = ObjectVersionId::parse(&composition.uid).ok();
+    let object_id = version.as_ref().map_or(composition.uid.as_str(), |version| version.object_id.as_str());
+    let status = match &version {
+        Some(version) if version.version > 1 => "amended",
+        _ => "final",
+    };
+
+    composition.content.iter()
+        .enumerate()
+        .filter_map(|(index, entry)| match entry {
+            Entry::Observation(observation) => Some(to_fhir_observation(
+                format!("{object_id}-{index}"),
+                status,
+                patient_id,
+                observation,
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn to_fhir_observation(id: String, status: &str, patient_id: &str, observation: &Observation) -> FhirObservation {
+    let archetype_id = observation.archetype_id.as_str();
+    let mut code = CodeableConcept::text(&observation.name.value);
+    let mut category = Vec::new();
+    if let Some(coding) = loinc(archetype_id, None) {
+        code = code.with_coding(coding);
+        category.push(CodeableConcept::text("Vital Signs")
+            .with_coding(Coding::new(OBSERVATION_CATEGORY_SYSTEM, "vital-signs", "Vital Signs")));
+    }
+
+    let (value, component) = match observation.data.items.as_slice() {
+        [item] => (Some(to_fhir_value(archetype_id, &item.value)), Vec::new()),
+        items => (None, items.iter()
+            .map(|item| {
+                let mut code = CodeableConcept::text(&item.name.value);
+                if let Some(coding) = loinc(archetype_id, Some(&item.name.value)) {
+                    code = code.with_coding(coding);
+                }
+                ObservationComponent { code, value: to_fhir_value(archetype_id, &item.value) }
+            })
+            .collect()),
+    };
+
+    FhirObservation {
+        id,
+        status: status.to_string(),
+        category,
+        code,
+        subject: Reference { reference: format!("Patient/{patient_id}") },
+        effective_date_time: observation.time.value.to_rfc3339_opts(SecondsFormat::Secs, true),
+        value,
+        component,
+    }
+}
+
+fn loinc(archetype_id: &str, element: Option<&str>) -> Option<Coding> {
+    VITAL_SIGNS_LOINC.iter()
+        .find(|(archetype, name, ..)| *archetype == archetype_id && *name == element)
+        .map(|(_, _, code, display)| Coding::new(LOINC_SYSTEM, *code, *display))
+}
+
+fn to_fhir_value(archetype_id: &str, value: &ObservationValue) -> Value {
+    match value {
+        ObservationValue::Text(text) => Value::String(text.value.clone()),
+        ObservationValue::CodedText(coded_text) => Value::CodeableConcept(to_codeable_concept(archetype_id, coded_text)),
+        ObservationValue::Quantity(quantity) => Value::Quantity(to_quantity(quantity)),
+        ObservationValue::DateTime(date_time) => Value::DateTime(date_time.value.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    }
+}
+
+/// Quantities get a UCUM `system`/`code` only for units known to be (or to stand
+/// for) a UCUM code; anything else goes out as a plain `unit`
+fn to_quantity(quantity: &DvQuantity) -> Quantity {
+    let units = quantity.units.trim();
+    let ucum = UCUM_UNITS.iter()
+        .find(|(code, _, aliases)| *code == units || aliases.contains(&units));
+
+    match ucum {
+        Some((code, display, _)) => Quantity {
+            value: quantity.magnitude,
+            unit: display.to_string(),
+            system: Some(UCUM_SYSTEM.to_string()),
+            code: Some(code.to_string()),
+        },
+        None => Quantity {
+            value: quantity.magnitude,
+            unit: units.to_string(),
+            system: None,
+            code: None,
+        },
+    }
+}
+
+fn to_codeable_concept(archetype_id: &str, coded_text: &DvCodedText) -> CodeableConcept {
+    CodeableConcept::text(&coded_text.value).with_coding(Coding::new(
+        terminology_system(archetype_id, &coded_text.defining_code.terminology_id),
+        &coded_text.defining_code.code_string,
+        &coded_text.value,
+    ))
+}
+
+/// FHIR system URI of an openEHR terminology id; `local` codes are archetype-local
+fn terminology_system(archetype_id: &str, terminology_id: &str) -> String {
+    match terminology_id.to_ascii_uppercase().replace('_', "-").as_str() {
+        "SNOMED-CT" => "http://snomed.info/sct".to_string(),
+        "LOINC" => LOINC_SYSTEM.to_string(),
+        "ICD10" | "ICD-10" => "http://hl7.org/fhir/sid/icd-10".to_string(),
+        "UCUM" => UCUM_SYSTEM.to_string(),
+        "ISO-5218" => "urn:iso:std:iso:5218".to_string(),
+        "LOCAL" => format!("urn:openehr:archetype:{archetype_id}"),
+        _ => format!("urn:openehr:terminology:{terminology_id}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ehr::{CompositionBuilder, Evaluation};
+    use serde_json::json;
+
+    #[test]
+    fn test_fhir_observations() {
+        let composition = CompositionBuilder::new("8849182c::anima.health::2".to_string(), "did:iota:anima:p1".to_string(), "openEHR-EHR-COMPOSITION.encounter.v1", "Vital signs", "user:1")
+            .add_entry(Entry::Observation(Observation::new("Blood pressure", "openEHR-EHR-OBSERVATION.blood_pressure.v2")
+                .add_item("Systolic", ObservationValue::Quantity(DvQuantity::new(120.0, "mm[Hg]")))
+                .add_item("Diastolic", ObservationValue::Quantity(DvQuantity::new(80.0, "mmHg")))))
+            .add_entry(Entry::Evaluation(Evaluation::new("Assessment", "openEHR-EHR-EVALUATION.clinical_synopsis.v1", "Normal")))
+            .add_entry(Entry::Observation(Observation::new("Pulse/Heart beat", "openEHR-EHR-OBSERVATION.pulse.v2")
+                .add_item("Regularity", ObservationValue::CodedText(DvCodedText::new("Regular", "local", "at1006")))))
+            .build();
+
+        let observations = to_fhir_observations("p1", &composition);
+        assert_eq!(observations.len(), 2);
+
+        let blood_pressure = serde_json::to_value(&observations[0]).unwrap();
+        assert_eq!(blood_pressure["id"], "8849182c-0");
+        assert_eq!(blood_pressure["status"], "amended");
+        assert_eq!(blood_pressure["subject"]["reference"], "Patient/p1");
+        assert_eq!(blood_pressure["category"][0]["coding"][0]["code"], "vital-signs");
+        assert_eq!(blood_pressure["code"]["coding"][0]["code"], "85354-9");
+        assert_eq!(blood_pressure["component"][0]["code"]["coding"][0]["code"], "8480-6");
+        assert_eq!(blood_pressure["component"][0]["valueQuantity"], json!({
+            "value": 120.0, "unit": "mmHg", "system": UCUM_SYSTEM, "code": "mm[Hg]"
+        }));
+        // A common non-UCUM spelling is sent as its UCUM code
+        assert_eq!(blood_pressure["component"][1]["valueQuantity"]["code"], "mm[Hg]");
+        // An unknown unit is not passed off as UCUM
+        assert_eq!(serde_json::to_value(to_quantity(&DvQuantity::new(3.0, "puffs"))).unwrap(), json!({
+            "value": 3.0, "unit": "puffs"
+        }));
+
+        let pulse = serde_json::to_value(&observations[1]).unwrap();
+        assert_eq!(pulse["id"], "8849182c-2");
+        assert!(pulse.get("component").is_none());
+        assert_eq!(pulse["valueCodeableConcept"], json!({
+            "coding": [{ "system": "urn:openehr:archetype:openEHR-EHR-OBSERVATION.pulse.v2", "code": "at1006", "display": "Regular" }],
+            "text": "Regular"
+        }));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::ehr::{Composition, Entry, ObservationValue};
+use crate::fhir::{Address, CodeableConcept, Coding, FhirConfig, HumanName, Identifier, IDENTIFIER_TYPE_SYSTEM, URI_SYSTEM};
+use crate::model::{Patient, PatientDemographics};
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+
+/// Entries of the demographics composition (see anima.patient_demographics.v1)
+const DEMOGRAPHICS_OBSERVATION_ID: &str = "openEHR-EHR-OBSERVATION.demographics.v1";
+const ADDRESS_OBSERVATION_ID: &str = "openEHR-EHR-OBSERVATION.address.v1";
+
+/// FHIR R4 Patient
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirPatient {
+    pub id: String,
+    pub identifier: Vec<Identifier>,
+    pub active: bool,
+    pub name: Vec<HumanName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub birth_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<Address>,
+}
+
+/// Patient resource of a patient record: the DID and the MRN are its identifiers
+///
+/// Demographics come from the latest version of the demographics composition
+/// among `compositions` (the registration snapshot without one).
+pub fn to_fhir_patient(patient: &Patient, compositions: &[Composition], config: &FhirConfig) -> FhirPatient {
+    let demographics = &current_demographics(patient, compositions);
+
+    FhirPatient {
+        id: patient.id.clone(),
+        identifier: vec![
+            Identifier {
+                identifier_type: None,
+                system: URI_SYSTEM.to_string(),
+                value: patient.did.clone(),
+            },
+            Identifier {
+                identifier_type: Some(CodeableConcept::text("Medical record number")
+                    .with_coding(Coding::new(IDENTIFIER_TYPE_SYSTEM, "MR", "Medical record number"))),
+                system: config.mrn_system.clone(),
+                value: demographics.medical_record_number.clone(),
+            },
+        ],
+        active: true,
+        name: vec![human_name(&demographics.name)],
+        gender: demographics.gender.as_deref().map(|gender| administrative_gender(gender).to_string()),
+        // FHIR dates are YYYY-MM-DD; anything else is left out rather than sent malformed
+        birth_date: NaiveDate::parse_from_str(demographics.date_of_birth.trim(), "%Y-%m-%d")
+            .ok()
+            .map(|date| date.format("%Y-%m-%d").to_string()),
+        address: demographics.address.iter()
+            .map(|address| Address { text: address.clone() })
+            .collect(),
+    }
+}
+
+/// Demographics as last committed: a newer demographics composition replaces the
+/// snapshot taken at registration (required items missing from it are kept)
+fn current_demographics(patient: &Patient, compositions: &[Composition]) -> PatientDemographics {
+    let Some(composition) = compositions.iter().rev().find(|composition| super::is_demographics(composition)) else {
+        return patient.demographics.clone();
+    };
+
+    let item = |archetype_id: &str, name: &str| composition.content.iter()
+        .filter_map(|entry| match entry {
+            Entry::Observation(observation) if observation.archetype_id == archetype_id => Some(observation),
+            _ => None,
+        })
+        .flat_map(|observation| observation.data.items.iter())
+        .find(|item| item.name.value == name)
+        .and_then(|item| match &item.value {
+            ObservationValue::Text(text) => Some(text.value.clone()),
+            ObservationValue::CodedText(coded_text) => Some(coded_text.value.clone()),
+            _ => None,
+        });
+    let demographic = |name: &str| item(DEMOGRAPHICS_OBSERVATION_ID, name);
+    let snapshot = &patient.demographics;
+
+    PatientDemographics {
+        name: demographic("Name").unwrap_or_else(|| snapshot.name.clone()),
+        date_of_birth: demographic("Date of Birth").unwrap_or_else(|| snapshot.date_of_birth.clone()),
+        medical_record_number: demographic("MRN").unwrap_or_else(|| snapshot.medical_record_number.clone()),
+        gender: demographic("Gender"),
+        address: item(ADDRESS_OBSERVATION_ID, "Full Address"),
+    }
+}
+
+/// Names are stored as one string: the last word is taken as the family name
+fn human_name(name: &str) -> HumanName {
+    let mut given: Vec<String> = name.split_whitespace().map(str::to_string).collect();
+    let family = if given.len() > 1 { given.pop() } else { None };
+    if family.is_none() {
+        given.clear();
+    }
+
+    HumanName {
+        name_use: "official".to_string(),
+        text: name.trim().to_string(),
+        family,
+        given,
+    }
+}
+
+/// FHIR administrative gender of a stored gender - free text or an ISO 5218 code
+fn administrative_gender(gender: &str) -> &'static str {
+    match gender.trim().to_ascii_lowercase().as_str() {
+        "male" | "m" | "1" => "male",
+        "female" | "f" | "2" => "female",
+        "unknown" | "u" | "0" | "9" | "" => "unknown",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::did_manager::PatientDID;
+    use crate::ehr::{CompositionBuilder, DvText, Observation};
+    use crate::model::PatientDemographics;
+    use serde_json::json;
+
+    #[test]
+    fn test_fhir_patient() {
+        let did = "did:iota:anima:7fd7f780".to_string();
+        let patient = Patient {
+            id: "7fd7f780".to_string(),
+            did: did.clone(),
+            demographics: PatientDemographics {
+                name: "Jane Q Doe".to_string(),
+                date_of_birth: "1985-03-14".to_string(),
+                medical_record_number: "MRN-001".to_string(),
+                gender: Some("F".to_string()),
+                address: None,
+            },
+            composition: CompositionBuilder::new("c::anima.health::1".to_string(), did.clone(), "openEHR-EHR-COMPOSITION.person.v1", "Patient Demographics", "user:1").build(),
+            did_metadata: PatientDID::create("7fd7f780".to_string(), 1, KeyAlgorithm::default()).unwrap().0,
+            created_at: chrono::Utc::now(),
+            created_by: 1,
+        };
+
+        let resource = serde_json::to_value(to_fhir_patient(&patient, &[], &FhirConfig::new())).unwrap();
+
+        assert_eq!(resource["identifier"][0], json!({ "system": URI_SYSTEM, "value": did }));
+        assert_eq!(resource["identifier"][1]["type"]["coding"][0]["code"], "MR");
+        assert_eq!(resource["identifier"][1]["value"], "MRN-001");
+        assert_eq!(resource["name"][0]["family"], "Doe");
+        assert_eq!(resource["name"][0]["given"], json!(["Jane", "Q"]));
+        assert_eq!(resource["gender"], "female");
+        assert_eq!(resource["birthDate"], "1985-03-14");
+        assert!(resource.get("address").is_none());
+
+        // A newer version of the demographics composition wins over the snapshot
+        let updated = CompositionBuilder::new("d::anima.health::2".to_string(), did.clone(), "openEHR-EHR-COMPOSITION.person.v1", "Patient Demographics", "user:1")
+            .add_entry(Entry::Observation(Observation::new("Patient Demographics", DEMOGRAPHICS_OBSERVATION_ID)
+                .add_item("Name", ObservationValue::Text(DvText::new("Jane Q Roe")))
+                .add_item("Date of Birth", ObservationValue::Text(DvText::new("1985-03-14")))
+                .add_item("MRN", ObservationValue::Text(DvText::new("MRN-001")))))
+            .add_entry(Entry::Observation(Observation::new("Address", ADDRESS_OBSERVATION_ID)
+                .add_item("Full Address", ObservationValue::Text(DvText::new("1 Main St")))))
+            .build();
+        let resource = serde_json::to_value(to_fhir_patient(&patient, &[updated], &FhirConfig::new())).unwrap();
+
+        assert_eq!(resource["name"][0]["family"], "Roe");
+        assert!(resource.get("gender").is_none());
+        assert_eq!(resource["address"], json!([{ "text": "1 Main St" }]));
+    }
+}
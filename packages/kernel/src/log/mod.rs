@@ -24,7 +24,8 @@ pub async fn log_request(
     let log_line = RequestLogLine {
         uuid: uuid.to_string(),
         timestamp: Utc::now().to_string(),
-        user_id: ctx.map(|c| c.user_id()),
+        user_id: ctx.as_ref().map(|c| c.user_id()),
+        did: ctx.as_ref().and_then(|c| c.did().map(str::to_string)),
         req_path: url.path().to_string(),
         req_method: req_method,
         client_error_type: client_error.map(|e| e.as_ref().to_string()),
@@ -45,6 +46,7 @@ struct RequestLogLine {
     uuid: String,
     timestamp: String, // ISO 8601 format
     user_id: Option<u64>,
+    did: Option<String>,
     
     req_path: String,
     req_method: String,
@@ -9,7 +9,7 @@ use tokio;
 use envie::Envie;
 
 // use crate::{ctx::Ctx, log::log_request};
-use crate::web::{mw_res_map::mw_reponse_map, routes_login, routes_patient, routes_anchor, routes_health, routes_static};
+use crate::web::{mw_res_map::mw_reponse_map, routes_login, routes_patient, routes_anchor, routes_admin, routes_credentials, routes_custody, routes_did, routes_template, routes_fhir, routes_health, routes_static, routes_wellknown};
 use crate::web::mw_auth::mw_ctx_resolve;
 use crate::model::ModelManager;
 
@@ -23,7 +23,9 @@ mod log;
 mod auth;
 mod did_manager;
 mod ehr;
+mod fhir;
 mod blockchain;
+mod persist;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,12 +33,48 @@ async fn main() -> Result<()> {
     let mm = ModelManager::new().await?;
     
     // Initialize DID registry for patient DIDs
-    let did_registry = crate::did_manager::DIDRegistry::new();
+    let did_registry = crate::did_manager::DIDRegistry::from_env()
+        .await
+        .expect("Failed to open patient key vault (persisted DIDs need KEY_VAULT=file)");
     
+    // Operational templates compositions are validated against
+    let templates = crate::ehr::TemplateRegistry::from_env()
+        .expect("Failed to load EHR templates");
+
     // Initialize auth system
+    let token_manager = crate::auth::TokenManager::from_env()
+        .expect("Failed to load token signing keys");
+
+    // The gateway issues credentials with its own key, so it trusts itself
+    let gateway_key = token_manager.gateway_key().clone();
+    let credential_policy = crate::auth::CredentialPolicy::from_env()
+        .with_trusted_issuer(gateway_key.did());
+    let credential_issuer = crate::auth::CredentialIssuer::from_env(gateway_key.clone(), credential_policy.clone())
+        .await
+        .expect("Failed to start credential issuer (persisted credentials need GATEWAY_SIGNING_KEY)");
+
+    let dev_mock_did = crate::auth::dev_mock_did_from_env();
+    if dev_mock_did {
+        println!("⚠️  ANIMA_DEV_MOCK_DID=1 - {} accepts a published dev key, never enable in production", crate::auth::MOCK_DID);
+    }
+
     let auth_state = routes_login::AuthState {
-        challenge_store: crate::auth::ChallengeStore::new(),
-        did_resolver: crate::auth::DIDResolver::new(),
+        challenge_store: crate::auth::ChallengeStore::from_env()
+            .expect("Failed to configure challenge store"),
+        trusted_proxies: crate::auth::TrustedProxies::from_env()
+            .expect("Failed to parse TRUSTED_PROXIES"),
+        did_resolver: crate::auth::DIDResolver::new(did_registry.clone())
+            .with_credential_policy(credential_policy)
+            .with_gateway_key(gateway_key)
+            .with_dev_mock_did(dev_mock_did)
+            .with_cache(crate::auth::DIDCache::from_env()),
+        token_manager,
+        session_store: crate::auth::SessionStore::new(),
+        role_registry: crate::auth::RoleRegistry::from_env()
+            .expect("Failed to load DID role assignments"),
+        account_registry: crate::auth::AccountRegistry::from_env()
+            .await
+            .expect("Failed to open account registry"),
     };
     
     // Load environment variables
@@ -45,25 +83,60 @@ async fn main() -> Result<()> {
     let port = env.get_int("PORT").unwrap_or(8080);
     let _db_token = env.get("REDUCT_TOKEN").unwrap_or("".to_string());
 
+    let credential_state = routes_credentials::CredentialState {
+        issuer: credential_issuer,
+        did_registry: did_registry.clone(),
+        mm: mm.clone(),
+    };
+
+    let custody_state = routes_custody::CustodyState {
+        signer: crate::did_manager::CustodialSigner::from_env(did_registry.clone())
+            .await
+            .expect("Failed to open custody audit log"),
+        step_ups: crate::auth::StepUpStore::new(),
+        account_registry: auth_state.account_registry.clone(),
+        challenge_store: auth_state.challenge_store.clone(),
+        did_resolver: auth_state.did_resolver.clone(),
+    };
+
     let routes_apis = Router::new()
-        .merge(routes_patient::routes(mm.clone(), did_registry.clone()))
+        .merge(routes_patient::routes(mm.clone(), did_registry.clone(), templates.clone(), auth_state.account_registry.clone()))
         .merge(routes_anchor::routes(mm.clone()))
+        .merge(routes_template::routes(templates))
+        .merge(routes_fhir::routes(mm.clone(), crate::fhir::FhirConfig::from_env()))
+        .merge(routes_admin::routes(routes_admin::AdminState {
+            session_store: auth_state.session_store.clone(),
+            role_registry: auth_state.role_registry.clone(),
+            account_registry: auth_state.account_registry.clone(),
+            did_resolver: auth_state.did_resolver.clone(),
+        }))
+        .merge(routes_credentials::routes(credential_state.clone()))
+        .merge(routes_custody::routes(custody_state))
+        .merge(routes_did::routes(routes_did::DidState {
+            did_registry: did_registry.clone(),
+            account_registry: auth_state.account_registry.clone(),
+            did_resolver: auth_state.did_resolver.clone(),
+            session_store: auth_state.session_store.clone(),
+        }))
         .route_layer(middleware::from_fn(web::mw_auth::mw_ctx_require::<Body>));
 
     // Build complete application with all routes
     let routes_all = Router::new()
         .merge(routes_health::routes())  // Health check (no auth required)
-        .merge(routes_login::routes(auth_state))
+        .merge(routes_wellknown::routes(auth_state.token_manager.clone()))  // JWKS (no auth required)
+        .merge(routes_credentials::public_routes(credential_state))  // Status list (no auth required)
+        .merge(routes_login::routes(auth_state.clone()))
         .nest("/api", routes_apis)
         .layer(middleware::map_response(mw_reponse_map))
         .layer(CookieManagerLayer::new())
         .layer(middleware::from_fn_with_state(
-                mm.clone(), mw_ctx_resolve::<Body>
+                auth_state.clone(), mw_ctx_resolve::<Body>
             ))
         .fallback_service(routes_static::serve_dir());
     
     println!("✅ IOTA DID authentication enabled");
     println!("✅ openEHR compositions enabled");
+    println!("✅ FHIR R4 export enabled");
     println!("✅ Merkle anchoring enabled");
     println!("✅ ReductStore integration ready");
     println!("✅ Welcome to Anima");
@@ -81,7 +154,8 @@ async fn main() -> Result<()> {
     let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
     println!("->> Listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, routes_all)
+    // Peer addresses (with X-Forwarded-For from TRUSTED_PROXIES) feed the per-IP challenge limit
+    axum::serve(listener, routes_all.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 
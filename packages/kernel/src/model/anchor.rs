@@ -1,4 +1,4 @@
-use crate::model::{Result, ModelManager, hash_to_hex};
+use crate::model::{Result, ModelManager, MerkleTree, hash_to_hex};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,14 +11,32 @@ pub struct AnchoredBatch {
     pub meta_uri: String,
 }
 
+/// A record queued for (or included in) a Merkle anchor batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AnchorRecord {
+    Patient(String),
+    /// A composition version committed to an existing patient's EHR
+    Composition { patient_id: String, uid: String },
+}
+
+impl AnchorRecord {
+    /// Merkle leaf ID: the patient ID, or "composition:{version uid}"
+    pub fn id(&self) -> String {
+        match self {
+            Self::Patient(patient_id) => patient_id.clone(),
+            Self::Composition { uid, .. } => format!("composition:{}", uid),
+        }
+    }
+}
+
 pub struct AnchorService;
 
 impl AnchorService {
     /// Create a batch and get Merkle root for anchoring
-    pub async fn create_batch(mm: &ModelManager) -> Result<Option<(AnchoredBatch, Vec<String>)>> {
+    pub async fn create_batch(mm: &ModelManager) -> Result<Option<(AnchoredBatch, MerkleTree, Vec<AnchorRecord>)>> {
         let result = mm.create_anchor_batch().await?;
 
-        if let Some((root, patient_ids)) = result {
+        if let Some((root, tree, records)) = result {
             let batch = AnchoredBatch {
                 batch_id: root.batch_id,
                 root_hash_hex: hash_to_hex(&root.root_hash),
@@ -30,9 +48,9 @@ impl AnchorService {
 
             println!("->> ANCHOR: Created batch #{} with {} records", batch.batch_id, batch.record_count);
             println!("    Root Hash: {}", batch.root_hash_hex);
-            println!("    Patient IDs: {:?}", patient_ids);
+            println!("    Record IDs: {:?}", records.iter().map(AnchorRecord::id).collect::<Vec<_>>());
 
-            Ok(Some((batch, patient_ids)))
+            Ok(Some((batch, tree, records)))
         } else {
             Ok(None)
         }
@@ -1,4 +1,5 @@
 use serde::Serialize;
+use crate::ehr;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -6,6 +7,10 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     StoreError(String),
     PatientNotFound { id: String },
+    EhrNotFound { patient_id: String },
+    InvalidComposition(String),
+    InvalidPatient(String),
+    Ehr(ehr::Error),
     MerkleError(String),
     SerializationError(String),
 }
@@ -8,23 +8,24 @@ pub use self::error::{Error, Result};
 pub use self::patient::{Patient, PatientDemographics, PatientForCreate, PatientForUpdate, PatientBmc};
 pub use self::merkle::{MerkleTree, MerkleRoot, MerkleProof, hash_data, hash_to_hex, verify_proof};
 pub use self::store::ReductStore;
-pub use self::anchor::{AnchorService, AnchoredBatch};
+pub use self::anchor::{AnchorService, AnchoredBatch, AnchorRecord};
 
 use std::sync::Arc;
 use std::collections::HashMap;
 use tokio::sync::Mutex;
 use crate::blockchain::{BlockchainClient, AnchorContract};
+use crate::ehr::{Composition, Contribution, Ehr};
 
 #[derive(Clone)]
 pub struct ModelManager {
     store: Arc<ReductStore>,
     // Batch queue for Merkle tree anchoring
-    pub(crate) pending_anchors: Arc<Mutex<Vec<String>>>, // Patient IDs waiting to be anchored
+    pub(crate) pending_anchors: Arc<Mutex<Vec<AnchorRecord>>>, // Records waiting to be anchored
     // Blockchain integration (optional for POC)
     pub(crate) blockchain: Option<Arc<BlockchainClient>>,
     pub(crate) anchor_contract: Option<Arc<AnchorContract>>,
     // Store anchored batches for proof generation
-    pub(crate) anchored_batches: Arc<Mutex<HashMap<u64, (AnchoredBatch, Vec<String>)>>>, // batch_id -> (batch, patient_ids)
+    pub(crate) anchored_batches: Arc<Mutex<HashMap<u64, (AnchoredBatch, MerkleTree)>>>, // batch_id -> (batch, tree as hashed)
 }
 
 impl ModelManager {
@@ -71,7 +72,7 @@ impl ModelManager {
         
         // Add to pending anchors queue
         let mut queue = self.pending_anchors.lock().await;
-        queue.push(patient.id.clone());
+        queue.push(AnchorRecord::Patient(patient.id.clone()));
         
         Ok(())
     }
@@ -86,13 +87,66 @@ impl ModelManager {
         self.store.list_patients().await
     }
 
-    /// Mark patient as deleted
+    /// Store a new patient's EHR (its initial compositions are anchored with the patient record)
+    pub async fn create_ehr(&self, patient_id: &str, ehr: &Ehr) -> Result<()> {
+        self.store.write_ehr(patient_id, ehr).await
+    }
+
+    /// Commit a new composition or composition version to an existing patient's
+    /// EHR and queue the version for anchoring
+    pub async fn commit_composition(
+        &self,
+        patient_id: &str,
+        composition: Composition,
+        preceding_version_uid: Option<&str>,
+        committer: String,
+        description: Option<String>,
+    ) -> Result<(Composition, Contribution)> {
+        let (composition, contribution) = self.store
+            .commit_composition(patient_id, composition, preceding_version_uid, committer, description)
+            .await?;
+
+        let mut queue = self.pending_anchors.lock().await;
+        queue.push(AnchorRecord::Composition {
+            patient_id: patient_id.to_string(),
+            uid: composition.uid.clone(),
+        });
+
+        Ok((composition, contribution))
+    }
+
+    pub async fn get_ehr(&self, patient_id: &str) -> Result<Ehr> {
+        self.store.read_ehr(patient_id).await
+    }
+
+    /// A composition by object id (latest version) or version uid
+    pub async fn get_composition(&self, patient_id: &str, uid: &str) -> Result<Composition> {
+        self.get_ehr(patient_id).await?
+            .composition(uid)
+            .cloned()
+            .map_err(Error::Ehr)
+    }
+
+    /// Serialized form of an anchored record (the Merkle leaf data)
+    async fn anchor_leaf(&self, record: &AnchorRecord) -> Result<Vec<u8>> {
+        let json = match record {
+            AnchorRecord::Patient(patient_id) => serde_json::to_vec(&self.get_patient(patient_id).await?),
+            AnchorRecord::Composition { patient_id, uid } => serde_json::to_vec(&self.get_composition(patient_id, uid).await?),
+        };
+
+        json.map_err(|e| Error::MerkleError(e.to_string()))
+    }
+
+    /// Mark patient as deleted, closing its EHR
     pub async fn delete_patient(&self, id: &str) -> Result<()> {
         self.store.delete_patient(id).await
     }
 
     /// Create Merkle root from pending records and return for anchoring
-    pub async fn create_anchor_batch(&self) -> Result<Option<(MerkleRoot, Vec<String>)>> {
+    ///
+    /// Records that can no longer be read (e.g. of a deleted patient) are left
+    /// out; the returned records are exactly the tree's leaves, in order.
+    pub async fn create_anchor_batch(&self) -> Result<Option<(MerkleRoot, MerkleTree, Vec<AnchorRecord>)>> {
         let mut queue = self.pending_anchors.lock().await;
         
         if queue.is_empty() {
@@ -100,17 +154,24 @@ impl ModelManager {
         }
 
         let mut tree = MerkleTree::new();
-        let patient_ids: Vec<String> = queue.clone();
+        let mut records: Vec<AnchorRecord> = Vec::new();
         
-        // Hash each patient record WITH patient ID
-        for patient_id in queue.iter() {
-            if let Ok(patient) = self.get_patient(patient_id).await {
-                let patient_json = serde_json::to_vec(&patient)
-                    .map_err(|e| Error::MerkleError(e.to_string()))?;
-                tree.add_leaf_with_id(&patient_json, patient_id.clone());
+        // Hash each record WITH its record ID
+        for record in queue.iter() {
+            match self.anchor_leaf(record).await {
+                Ok(leaf) => {
+                    tree.add_leaf_with_id(&leaf, record.id());
+                    records.push(record.clone());
+                }
+                Err(e) => println!("->> ⚠️  {} not anchored: {}", record.id(), e),
             }
         }
 
+        if records.is_empty() {
+            queue.clear();
+            return Ok(None);
+        }
+
         let root_hash = tree.root()
             .ok_or_else(|| Error::MerkleError("Failed to compute Merkle root".to_string()))?;
 
@@ -130,37 +191,35 @@ impl ModelManager {
         // Clear the queue
         queue.clear();
 
-        Ok(Some((merkle_root, patient_ids)))
+        Ok(Some((merkle_root, tree, records)))
     }
     
-    /// Generate a Merkle proof for a patient in a specific batch
-    pub async fn generate_merkle_proof(&self, patient_id: &str) -> Result<Option<MerkleProof>> {
+    /// Generate a Merkle proof for a record (patient ID or "composition:{uid}") in a specific batch
+    ///
+    /// Proofs use the leaves hashed when the batch was created, so they keep
+    /// matching the anchored root after records change or are deleted.
+    pub async fn generate_merkle_proof(&self, record_id: &str) -> Result<Option<MerkleProof>> {
         let batches = self.anchored_batches.lock().await;
         
-        // Find which batch contains this patient
-        for (batch_id, (batch, patient_ids)) in batches.iter() {
-            if let Some(index) = patient_ids.iter().position(|id| id == patient_id) {
-                // Reconstruct the Merkle tree for this batch
-                let mut tree = MerkleTree::new();
-                for pid in patient_ids {
-                    if let Ok(patient) = self.get_patient(pid).await {
-                        let patient_json = serde_json::to_vec(&patient)
-                            .map_err(|e| Error::MerkleError(e.to_string()))?;
-                        tree.add_leaf_with_id(&patient_json, pid.clone());
-                    }
-                }
-                
-                // Generate proof
-                return Ok(tree.generate_proof(index));
-            }
-        }
-        
-        Ok(None)
+        // Find which batch contains this record
+        let proof = batches.values().find_map(|(_, tree)| {
+            tree.get_leaf_ids().iter()
+                .position(|id| id == record_id)
+                .map(|index| tree.generate_proof(index))
+        });
+
+        Ok(proof.flatten())
     }
     
-    /// Store an anchored batch for later proof generation
-    pub async fn store_anchored_batch(&self, batch: AnchoredBatch, patient_ids: Vec<String>) {
+    /// An anchored batch by ID
+    pub async fn get_anchored_batch(&self, batch_id: u64) -> Option<AnchoredBatch> {
+        let batches = self.anchored_batches.lock().await;
+        batches.get(&batch_id).map(|(batch, _)| batch.clone())
+    }
+
+    /// Store an anchored batch with its tree for later proof generation
+    pub async fn store_anchored_batch(&self, batch: AnchoredBatch, tree: MerkleTree) {
         let mut batches = self.anchored_batches.lock().await;
-        batches.insert(batch.batch_id, (batch, patient_ids));
+        batches.insert(batch.batch_id, (batch, tree));
     }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::ctx::Ctx;
 use crate::model::Result;
 use crate::did_manager::PatientDID;
+use crate::auth::KeyAlgorithm;
 use crate::ehr::Composition;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
@@ -44,6 +45,13 @@ pub struct PatientForCreate {
     pub medical_record_number: String,
     pub gender: Option<String>,
     pub address: Option<String>,
+    /// Key type of the patient's DID (Ed25519 when omitted)
+    #[serde(default)]
+    pub key_algorithm: KeyAlgorithm,
+    /// DID the patient signs in with; its account becomes the holder of the
+    /// patient DID (custodial signing, key export, own record)
+    #[serde(default)]
+    pub holder_did: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
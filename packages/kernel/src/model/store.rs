@@ -1,4 +1,5 @@
 use crate::model::{Error, Result, Patient};
+use crate::ehr::{Composition, Contribution, Ehr};
 use reduct_rs::ReductClient;
 use std::collections::HashMap;
 use tokio::sync::RwLock;
@@ -6,12 +7,15 @@ use std::sync::Arc;
 
 const BUCKET_NAME: &str = "anima-patients";
 const ENTRY_NAME: &str = "patient-records";
+const COMPOSITION_ENTRY_NAME: &str = "ehr-compositions";
 
 pub struct ReductStore {
     client: Option<ReductClient>,
     // For POC: in-memory storage (fallback when ReductStore unavailable)
     // Maps patient_id -> (patient_data, timestamp)
     in_memory_store: Arc<RwLock<HashMap<String, (Patient, u64)>>>,
+    // patient_id -> EHR (compositions are also written to ReductStore)
+    ehrs: Arc<RwLock<HashMap<String, Ehr>>>,
     use_memory_fallback: bool,
 }
 
@@ -29,6 +33,7 @@ impl ReductStore {
         let store = Self { 
             client: Some(client),
             in_memory_store: Arc::new(RwLock::new(HashMap::new())),
+            ehrs: Arc::new(RwLock::new(HashMap::new())),
             use_memory_fallback: false,
         };
 
@@ -43,6 +48,7 @@ impl ReductStore {
                 Ok(Self {
                     client: None,
                     in_memory_store: Arc::new(RwLock::new(HashMap::new())),
+                    ehrs: Arc::new(RwLock::new(HashMap::new())),
                     use_memory_fallback: true,
                 })
             }
@@ -137,9 +143,85 @@ impl ReductStore {
         // For audit trail, we don't actually delete
         // Just remove from in-memory index (in production, would add "deleted" label)
         let mut store = self.in_memory_store.write().await;
+        // The EHR goes with it - no reads or commits for a deleted patient
+        let mut ehrs = self.ehrs.write().await;
         store.remove(id);
+        ehrs.remove(id);
         
-        println!("->> Store: Marked patient {} as deleted", id);
+        println!("->> Store: Marked patient {} and its EHR as deleted", id);
+        Ok(())
+    }
+
+    /// Store a new patient's EHR with its initial compositions
+    pub async fn write_ehr(&self, patient_id: &str, ehr: &Ehr) -> Result<()> {
+        for composition in ehr.latest_compositions() {
+            self.write_composition_record(patient_id, &composition).await?;
+        }
+
+        self.ehrs.write().await.insert(patient_id.to_string(), ehr.clone());
+        println!("->> Store: Created EHR {} for patient {}", ehr.ehr_id, patient_id);
+        Ok(())
+    }
+
+    /// Commit a composition version to a patient's EHR (see `Ehr::commit_composition`)
+    pub async fn commit_composition(
+        &self,
+        patient_id: &str,
+        composition: Composition,
+        preceding_version_uid: Option<&str>,
+        committer: String,
+        description: Option<String>,
+    ) -> Result<(Composition, Contribution)> {
+        let mut ehrs = self.ehrs.write().await;
+        let mut ehr = ehrs.get(patient_id)
+            .cloned()
+            .ok_or_else(|| Error::EhrNotFound { patient_id: patient_id.to_string() })?;
+
+        let (composition, contribution) = ehr
+            .commit_composition(composition, preceding_version_uid, committer, description)
+            .map_err(Error::Ehr)?;
+        self.write_composition_record(patient_id, &composition).await?;
+
+        ehrs.insert(patient_id.to_string(), ehr);
+        println!("->> Store: Committed composition {} to EHR of patient {}", composition.uid, patient_id);
+        Ok((composition, contribution))
+    }
+
+    pub async fn read_ehr(&self, patient_id: &str) -> Result<Ehr> {
+        let ehrs = self.ehrs.read().await;
+        ehrs.get(patient_id)
+            .cloned()
+            .ok_or_else(|| Error::EhrNotFound { patient_id: patient_id.to_string() })
+    }
+
+    /// One ReductStore record per composition (no-op in memory mode)
+    async fn write_composition_record(&self, patient_id: &str, composition: &Composition) -> Result<()> {
+        if self.use_memory_fallback {
+            return Ok(());
+        }
+
+        let client = self.client.as_ref()
+            .ok_or_else(|| Error::StoreError("No ReductStore client".to_string()))?;
+
+        let bucket = client
+            .get_bucket(BUCKET_NAME)
+            .await
+            .map_err(|e| Error::StoreError(format!("Failed to get bucket: {}", e)))?;
+
+        let data = serde_json::to_vec(composition)
+            .map_err(|e| Error::StoreError(format!("Failed to serialize composition: {}", e)))?;
+
+        bucket
+            .write_record(COMPOSITION_ENTRY_NAME)
+            .data(data)
+            .timestamp_us(chrono::Utc::now().timestamp_micros() as u64)
+            .add_label("patient_id", patient_id)
+            .add_label("composition_uid", &composition.uid)
+            .add_label("archetype_id", &composition.archetype_id)
+            .send()
+            .await
+            .map_err(|e| Error::StoreError(format!("Failed to write composition: {}", e)))?;
+
         Ok(())
     }
 }
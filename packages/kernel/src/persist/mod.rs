@@ -0,0 +1,82 @@
+// Small JSON file persistence for gateway state that has no ReductStore bucket
+// (accounts, bindings, ...). Each store is one JSON document rewritten on change.
+
+mod error;
+
+pub use self::error::{Error, Result};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::PathBuf;
+
+/// One JSON document on disk
+///
+/// Writes go to a sibling temp file that is renamed over the target, so a
+/// crash mid-write never leaves a truncated document behind.
+#[derive(Debug, Clone)]
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+impl JsonFileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Load the document, or `T::default()` if the file doesn't exist yet
+    pub async fn load<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+            Err(e) => return Err(Error::ReadFailed { path: self.display(), reason: e.to_string() }),
+        };
+
+        serde_json::from_slice(&bytes)
+            .map_err(|e| Error::Corrupt { path: self.display(), reason: e.to_string() })
+    }
+
+    pub async fn save<T: Serialize>(&self, value: &T) -> Result<()> {
+        let write_failed = |e: std::io::Error| Error::WriteFailed { path: self.display(), reason: e.to_string() };
+
+        let bytes = serde_json::to_vec_pretty(value)
+            .map_err(|e| Error::WriteFailed { path: self.display(), reason: e.to_string() })?;
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir).await.map_err(write_failed)?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, bytes).await.map_err(write_failed)?;
+        tokio::fs::rename(&tmp_path, &self.path).await.map_err(write_failed)?;
+
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn test_save_and_load_roundtrip() {
+        let path = std::env::temp_dir().join(format!("anima-persist-{}/doc.json", uuid::Uuid::new_v4()));
+        let store = JsonFileStore::new(&path);
+
+        // Missing file loads as default
+        let empty: BTreeMap<String, u64> = store.load().await.unwrap();
+        assert!(empty.is_empty());
+
+        let doc = BTreeMap::from([("did:iota:anima:abc123".to_string(), 7u64)]);
+        store.save(&doc).await.unwrap();
+
+        let loaded: BTreeMap<String, u64> = store.load().await.unwrap();
+        assert_eq!(loaded, doc);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
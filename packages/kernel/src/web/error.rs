@@ -6,6 +6,9 @@ use axum::{
 use serde::Serialize;
 use crate::web;
 use crate::model;
+use crate::auth;
+use crate::did_manager;
+use crate::ehr;
 
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -15,10 +18,19 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error { 
     LoginFail,
     AuthFail(String),
+    Auth(auth::Error),
+    PermissionDenied(String),
+    SessionNotFound { did: String, sid: String },
+
+    /// No acceptable format in the Accept header
+    NotAcceptable(String),
+    UnsupportedMediaType(String),
 
     CtxExt(web::mw_auth::CtxExtError),
     
     Model(model::Error),
+
+    DIDManager(did_manager::Error),
 }
 
 impl IntoResponse for Error {
@@ -49,15 +61,92 @@ impl Error {
 
         #[allow(unreachable_patterns)]
         match self {
-            LoginFail | AuthFail(_) => (StatusCode::UNAUTHORIZED, ClientError::LOGIN_FAIL),
+            Auth(auth::Error::AccountNotFound(_))
+            | Auth(auth::Error::CredentialNotFound(_))
+            | Auth(auth::Error::DIDNotFound(_)) => (
+                StatusCode::NOT_FOUND,
+                ClientError::ENTITY_NOT_FOUND
+            ),
+
+            Auth(auth::Error::DIDAlreadyBound { .. })
+            | Auth(auth::Error::DIDNotBound { .. })
+            | Auth(auth::Error::UnknownRole(_))
+            | Auth(auth::Error::CredentialInvalid(_)) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS
+            ),
+
+            Auth(auth::Error::StepUpRequired) => (
+                StatusCode::FORBIDDEN,
+                ClientError::STEP_UP_REQUIRED
+            ),
+
+            Auth(auth::Error::ChallengeRateLimited { .. }) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ClientError::RATE_LIMITED
+            ),
+
+            Auth(auth::Error::Persist(_))
+            | Auth(auth::Error::ChallengeStoreFailed(_)) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ClientError::SERVICE_ERROR
+            ),
+
+            LoginFail | AuthFail(_) | Auth(_) => (StatusCode::UNAUTHORIZED, ClientError::LOGIN_FAIL),
             
             CtxExt(_) => (StatusCode::FORBIDDEN, ClientError::NO_AUTH),
+
+            PermissionDenied(_) => (StatusCode::FORBIDDEN, ClientError::PERMISSION_DENIED),
             
-            Model(model::Error::PatientNotFound { .. }) => (
+            SessionNotFound { .. } => (
+                StatusCode::NOT_FOUND,
+                ClientError::ENTITY_NOT_FOUND
+            ),
+
+            NotAcceptable(_) => (StatusCode::NOT_ACCEPTABLE, ClientError::UNSUPPORTED_FORMAT),
+
+            UnsupportedMediaType(_) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, ClientError::UNSUPPORTED_FORMAT),
+
+            Model(model::Error::PatientNotFound { .. })
+            | Model(model::Error::EhrNotFound { .. })
+            | Model(model::Error::Ehr(ehr::Error::CompositionNotFound(_)))
+            | Model(model::Error::Ehr(ehr::Error::VersionNotFound(_)))
+            | Model(model::Error::Ehr(ehr::Error::TemplateNotFound(_))) => (
                 StatusCode::NOT_FOUND,
                 ClientError::ENTITY_NOT_FOUND
             ),
 
+            Model(model::Error::Ehr(ehr::Error::PrecedingVersionMismatch { .. })) => (
+                StatusCode::PRECONDITION_FAILED,
+                ClientError::VERSION_CONFLICT
+            ),
+
+            Model(model::Error::Ehr(ehr::Error::TemplateViolations { .. })) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ClientError::TEMPLATE_VIOLATION
+            ),
+
+            Model(model::Error::InvalidComposition(_))
+            | Model(model::Error::InvalidPatient(_))
+            | Model(model::Error::Ehr(_)) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS
+            ),
+
+            DIDManager(did_manager::Error::DIDNotFound(_))
+            | DIDManager(did_manager::Error::KeyNotFound(_)) => (
+                StatusCode::NOT_FOUND,
+                ClientError::ENTITY_NOT_FOUND
+            ),
+
+            DIDManager(did_manager::Error::DIDRevoked(_))
+            | DIDManager(did_manager::Error::DIDDeactivated(_))
+            | DIDManager(did_manager::Error::NotCustodial(_))
+            | DIDManager(did_manager::Error::LedgerRejected(_)) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS
+            ),
+
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR, 
                 ClientError::SERVICE_ERROR
@@ -66,11 +155,39 @@ impl Error {
     }
 }
 
+impl Error {
+    /// Seconds a rate-limited client should wait (Retry-After)
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Error::Auth(auth::Error::ChallengeRateLimited { retry_after }) => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// Details the client needs to fix its request, added to the error body
+    pub fn client_detail(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::Model(model::Error::Ehr(ehr::Error::TemplateViolations { template_id, violations })) => Some(serde_json::json!({
+                "template_id": template_id,
+                "violations": violations,
+            })),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, strum_macros::AsRefStr)]
 #[allow(non_camel_case_types)]
 pub enum ClientError {
     LOGIN_FAIL,
     NO_AUTH,
+    PERMISSION_DENIED,
     ENTITY_NOT_FOUND,
+    INVALID_PARAMS,
+    RATE_LIMITED,
+    STEP_UP_REQUIRED,
+    VERSION_CONFLICT,
+    TEMPLATE_VIOLATION,
+    UNSUPPORTED_FORMAT,
     SERVICE_ERROR,
 }
\ No newline at end of file
@@ -4,12 +4,22 @@ pub mod routes_login;
 pub mod routes_patient;
 pub mod routes_anchor;
 pub mod routes_health;
+pub mod routes_wellknown;
+pub mod routes_admin;
+pub mod routes_credentials;
+pub mod routes_custody;
+pub mod routes_did;
+pub mod routes_template;
+pub mod routes_fhir;
 pub mod mw_auth;
 pub mod mw_ehr;
 pub mod routes_static;
 pub mod mw_res_map;
 
 pub const AUTH_TOKEN: &str = "auth-token";
+pub const REFRESH_TOKEN: &str = "refresh-token";
+/// Per-request verifiable credential (VC-JWT), may be repeated
+pub const VC_HEADER: &str = "x-verifiable-credential";
 
 pub use self::error::{Error, Result};
 pub use self::error::ClientError;
\ No newline at end of file
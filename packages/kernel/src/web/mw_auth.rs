@@ -3,14 +3,15 @@ use axum::body::Body;
 use axum::extract::{FromRequestParts, State};
 use axum::http::request::Parts;
 use axum::middleware::Next;
-use axum_extra::extract::cookie::{CookieJar, Cookie};
+use axum_extra::extract::cookie::CookieJar;
 use serde::Serialize;
+use crate::auth::Roles;
 use crate::ctx::Ctx;
-use crate::model::ModelManager;
-use crate::auth::TokenManager;
-use crate::web::AUTH_TOKEN;
+use crate::web::routes_login::{self, AuthState};
+use crate::web::{AUTH_TOKEN, VC_HEADER};
 use crate::web::{Error, Result};
-use axum::http::{Request, Response};
+use axum::http::{HeaderMap, HeaderValue, Request, Response};
+use axum::http::header::AUTHORIZATION;
 
 #[allow(dead_code)]
 pub async fn mw_ctx_require<B>(
@@ -28,47 +29,196 @@ pub async fn mw_ctx_require<B>(
     
 }
 
+/// Per-route permission layer: reject callers lacking the required role(s)
+///
+/// ```ignore
+/// .route("/anchor/batch", post(create_batch)
+///     .route_layer(middleware::from_fn_with_state(Roles::ANCHORER, mw_require_role)))
+/// ```
+pub async fn mw_require_role(
+    State(required): State<Roles>,
+    ctx: Result<Ctx>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>> {
+    println!("->> {:<12} - mw_require_role - {}", "MIDDLEWARE", required);
+
+    let ctx = ctx?;
+    if !ctx.has_role(required) {
+        println!("   ->> Denied: has [{}], needs [{}]", ctx.roles(), required);
+        return Err(Error::PermissionDenied(required.to_string()));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Credential type (from a trusted issuer) that makes its holder a clinician
+pub const CLINICIAN_CREDENTIAL: &str = "LicensedPhysician";
+
+/// Care staff - admins and clinicians - work on every patient record
+pub fn is_care_staff(ctx: &Ctx) -> bool {
+    ctx.has_role(Roles::ADMIN)
+        || ctx.credential_types().iter().any(|credential_type| credential_type == CLINICIAN_CREDENTIAL)
+}
+
+/// Route layer for patient-wide routes: care staff only
+pub async fn mw_require_care_staff(
+    ctx: Result<Ctx>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>> {
+    println!("->> {:<12} - mw_require_care_staff", "MIDDLEWARE");
+
+    let ctx = ctx?;
+    if !is_care_staff(&ctx) {
+        println!("   ->> Denied: has [{}] and no {} credential", ctx.roles(), CLINICIAN_CREDENTIAL);
+        return Err(Error::PermissionDenied(format!("ADMIN or {}", CLINICIAN_CREDENTIAL)));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// A patient's record is open to care staff and to the account holding the patient's DID
+///
+/// `holder_account` is the account the patient DID is bound to, if any.
+pub fn authorize_patient(ctx: &Ctx, holder_account: Option<u64>) -> Result<()> {
+    if is_care_staff(ctx) || holder_account == Some(ctx.user_id()) {
+        return Ok(());
+    }
+
+    println!("   ->> Denied: account {} is neither care staff nor the patient's holder", ctx.user_id());
+    Err(Error::PermissionDenied("Care staff or the patient's own account".to_string()))
+}
+
+/// Resolve the request `Ctx` from an access token
+///
+/// Token sources, in order of precedence:
+/// 1. `Authorization: Bearer <token>` header (mobile apps, server-to-server)
+/// 2. `auth-token` cookie (browser sessions)
+///
+/// When the header is present the cookie is not consulted, even if the header
+/// turns out to be malformed or its token invalid.
 pub async fn mw_ctx_resolve<B>(
-    State(_mm): State<ModelManager>,
+    State(auth_state): State<AuthState>,
     cookies: CookieJar,
     mut req: Request<Body>,
     next: Next,
 ) -> Result<Response<Body>> {
     println!("->> {:<12} - mw_ctx_resolve", "MIDDLEWARE");
 
-    let auth_token = cookies.get(AUTH_TOKEN).map(|c| c.value().to_string());
-
-    // Parse and validate token
-    let result_ctx = match auth_token {
-        Some(token) => {
-            // Validate token using TokenManager
-            match TokenManager::validate_token(&token, None) {
-                Ok(claims) => {
-                    println!("   ✅ Token valid - user_id: {}, DID: {}", claims.user_id, claims.did);
-                    Ctx::new(claims.user_id).map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
-                }
-                Err(e) => {
-                    println!("   ->> Token validation failed: {:?}", e);
-                    Err(CtxExtError::CtxCreateFail(format!("{:?}", e)))
-                }
+    let result_ctx = match req.headers().get(AUTHORIZATION) {
+        Some(header) => match bearer_token(header) {
+            Some(token) => ctx_from_token(&auth_state, token, TokenSource::Bearer).await,
+            None => {
+                println!("   ->> Malformed Authorization header");
+                Err(CtxExtError::AuthHeaderMalformed)
+            }
+        },
+        None => match cookies.get(AUTH_TOKEN) {
+            Some(cookie) => ctx_from_token(&auth_state, cookie.value(), TokenSource::Cookie).await,
+            None => {
+                println!("   ->> No auth token in header or cookie");
+                Err(CtxExtError::TokenNotInRequest)
             }
-        }
-        None => {
-            println!("   ->> No auth token in cookie");
-            Err(CtxExtError::TokenNotInCookie)
-        }
+        },
     };
 
-    // Remove invalid tokens
-    if result_ctx.is_err() && !matches!(result_ctx, Err(CtxExtError::TokenNotInCookie)) {
-        let _cookies = cookies.remove(Cookie::from(AUTH_TOKEN));
+    // Remove invalid cookie tokens (a bad bearer token says nothing about the cookie)
+    if matches!(
+        result_ctx,
+        Err(CtxExtError::CookieTokenInvalid(_)) | Err(CtxExtError::CookieSessionRevoked)
+    ) {
+        let _cookies = cookies.remove(routes_login::session_cookie(AUTH_TOKEN));
     }
 
+    // Credentials presented with this request, on top of the login ones
+    let result_ctx = match result_ctx {
+        Ok(ctx) => add_presented_credentials(&auth_state, ctx, req.headers()).await,
+        Err(ex) => Err(ex),
+    };
+
     req.extensions_mut().insert(result_ctx);
 
     Ok(next.run(req).await)
 }
 
+#[derive(Debug, Clone, Copy)]
+enum TokenSource {
+    Bearer,
+    Cookie,
+}
+
+/// Extract the token of an `Authorization: Bearer <token>` header
+fn bearer_token(header: &HeaderValue) -> Option<&str> {
+    let (scheme, token) = header.to_str().ok()?.trim().split_once(' ')?;
+    let token = token.trim();
+
+    (scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Verify the VC-JWTs of the `X-Verifiable-Credential` header(s) against the caller's DID
+async fn add_presented_credentials(auth_state: &AuthState, ctx: Ctx, headers: &HeaderMap) -> CtxExtResult {
+    let Some(did) = ctx.did().map(str::to_string) else { return Ok(ctx) };
+
+    let mut types = Vec::new();
+    for value in headers.get_all(VC_HEADER) {
+        let vc_jwt = value.to_str()
+            .map_err(|_| CtxExtError::CredentialInvalid("Malformed credential header".to_string()))?;
+
+        let credential = auth_state.did_resolver
+            .verify_credential(vc_jwt, &did)
+            .await
+            .map_err(|e| {
+                println!("   ->> Presented credential rejected: {:?}", e);
+                CtxExtError::CredentialInvalid(format!("{:?}", e))
+            })?;
+        types.extend(credential.types);
+    }
+
+    Ok(ctx.with_credentials(types))
+}
+
+/// Validate an access token and check its session hasn't been revoked
+async fn ctx_from_token(auth_state: &AuthState, token: &str, source: TokenSource) -> CtxExtResult {
+    let claims = auth_state.token_manager
+        .validate_token(token, None)
+        .map_err(|e| {
+            println!("   ->> {:?} token validation failed: {:?}", source, e);
+            match source {
+                TokenSource::Bearer => CtxExtError::BearerTokenInvalid(format!("{:?}", e)),
+                TokenSource::Cookie => CtxExtError::CookieTokenInvalid(format!("{:?}", e)),
+            }
+        })?;
+
+    if !auth_state.session_store.is_active(&claims.sid).await {
+        println!("   ->> Session {} revoked or expired", claims.sid);
+        return Err(match source {
+            TokenSource::Bearer => CtxExtError::BearerSessionRevoked,
+            TokenSource::Cookie => CtxExtError::CookieSessionRevoked,
+        });
+    }
+
+    // A revoked/deactivated DID loses access at once, not at token expiry
+    auth_state.did_resolver
+        .check_did_active(&claims.did)
+        .await
+        .map_err(|e| {
+            println!("   ->> {:?}", e);
+            CtxExtError::DIDInactive(claims.did.clone())
+        })?;
+
+    let roles = Roles::from_names(&claims.roles)
+        .map_err(|e| CtxExtError::CtxCreateFail(e.to_string()))?;
+
+    println!("   ✅ {:?} token valid - user_id: {}, DID: {}, roles: [{}]", source, claims.user_id, claims.did, roles);
+
+    Ctx::new(claims.user_id, claims.did)
+        .map(|ctx| ctx
+            .with_session_id(claims.sid)
+            .with_roles(roles)
+            .with_credentials(claims.credentials))
+        .map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
 
 // Implementing Ctx as extractor
 #[async_trait]
@@ -94,7 +244,18 @@ type CtxExtResult = core::result::Result<Ctx, CtxExtError>;
 pub enum CtxExtError {
     CtxNotInRequestExt,
     CtxCreateFail(String),
-    TokenNotInCookie,
+    /// Neither an Authorization header nor an auth-token cookie was sent
+    TokenNotInRequest,
+    /// Authorization header present but not of the form "Bearer <token>"
+    AuthHeaderMalformed,
+    BearerTokenInvalid(String),
+    BearerSessionRevoked,
+    CookieTokenInvalid(String),
+    CookieSessionRevoked,
+    /// A credential in the X-Verifiable-Credential header failed verification
+    CredentialInvalid(String),
+    /// The token's DID was revoked or deactivated
+    DIDInactive(String),
 }
 
 // fn parse_token(token: String) -> Result<(u64, String, String)> {
@@ -109,4 +270,37 @@ pub enum CtxExtError {
 //     let signature = signature.to_string();
 
 //     Ok((user_id, exp, signature))
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_authorize_patient() {
+        let caller = Ctx::new(7, "did:key:z6Mkcaller").unwrap();
+
+        // A role-less session is not enough to read someone else's record
+        let denied = authorize_patient(&caller, Some(8)).unwrap_err();
+        assert_eq!(denied.client_status_and_error().0, StatusCode::FORBIDDEN);
+        assert!(authorize_patient(&caller, None).is_err());
+        assert!(!is_care_staff(&caller.clone().with_roles(Roles::ANCHORER)));
+
+        // The patient's own account, admins and clinicians get through
+        assert!(authorize_patient(&caller, Some(7)).is_ok());
+        assert!(authorize_patient(&caller.clone().with_roles(Roles::ADMIN), Some(8)).is_ok());
+        assert!(authorize_patient(&caller.with_credentials([CLINICIAN_CREDENTIAL.to_string()]), None).is_ok());
+    }
+
+    #[test]
+    fn test_bearer_token_parsing() {
+        let header = |v: &str| HeaderValue::from_str(v).unwrap();
+
+        assert_eq!(bearer_token(&header("Bearer v1.k1.abc.def")), Some("v1.k1.abc.def"));
+        assert_eq!(bearer_token(&header("bearer  eyJ.x.y ")), Some("eyJ.x.y"));
+        assert_eq!(bearer_token(&header("Basic dXNlcjpwYXNz")), None);
+        assert_eq!(bearer_token(&header("Bearer")), None);
+        assert_eq!(bearer_token(&header("Bearer ")), None);
+    }
+}
@@ -7,13 +7,15 @@ use serde_json::Value;
 use crate::ctx::Ctx;
 use crate::model::{Patient, PatientForCreate, ModelManager};
 use crate::did_manager::DIDRegistry;
-use crate::ehr::{CompositionBuilder, CompositionCategory, Entry, Observation, ObservationValue, DvText, DvCodedText};
+use crate::auth::AccountRegistry;
+use crate::ehr::{CompositionBuilder, CompositionCategory, Ehr, Entry, Observation, ObservationValue, ObjectVersionId, DvText, DvCodedText, TemplateRegistry, DEMOGRAPHICS_TEMPLATE_ID};
 use crate::web::{Error, Result};
 
 #[derive(Clone)]
 pub struct EHRState {
     pub mm: ModelManager,
     pub did_registry: DIDRegistry,
+    pub templates: TemplateRegistry,
 }
 
 /// Middleware to organize patient data into openEHR composition before storage
@@ -43,15 +45,24 @@ pub async fn create_patient_with_ehr(
     ctx: &Ctx,
     mm: &ModelManager,
     did_registry: &DIDRegistry,
+    templates: &TemplateRegistry,
+    account_registry: &AccountRegistry,
     patient_c: PatientForCreate,
 ) -> Result<Patient> {
     let patient_id = uuid::Uuid::new_v4().to_string();
     
     println!("->> EHR: Creating patient with ID: {}", patient_id);
 
+    let holder_did = patient_c.holder_did.clone();
+    if let Some(holder_did) = &holder_did {
+        if !holder_did.starts_with("did:") {
+            return Err(Error::Model(crate::model::Error::InvalidPatient(format!("holder_did is not a DID: {}", holder_did))));
+        }
+    }
+
     // Step 1: Create patient DID
     let patient_did = did_registry
-        .create_patient_did(patient_id.clone(), ctx.user_id())
+        .create_patient_did(patient_id.clone(), ctx.user_id(), patient_c.key_algorithm)
         .await
         .map_err(|e| Error::Model(crate::model::Error::SerializationError(format!("DID creation failed: {}", e))))?;
 
@@ -60,7 +71,7 @@ pub async fn create_patient_with_ehr(
     println!("   ✅ DID created: {}", patient_did.did);
 
     // Step 2: Build openEHR composition
-    let composition_id = format!("{}_demographics_v1", patient_id);
+    let composition_id = ObjectVersionId::new_object().to_string();
     let archetype_id = "openEHR-EHR-COMPOSITION.person.v1";
     
     let mut composition_builder = CompositionBuilder::new(
@@ -69,7 +80,8 @@ pub async fn create_patient_with_ehr(
         archetype_id,
         "Patient Demographics",
         format!("user:{}", ctx.user_id()),
-    );
+    )
+    .template_id(DEMOGRAPHICS_TEMPLATE_ID);
 
     // Add demographics as observations
     let demographics_obs = Observation::new(
@@ -105,10 +117,18 @@ pub async fn create_patient_with_ehr(
     }
 
     let composition = composition_builder.build();
+    templates.validate(&composition)
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?;
+
+    // Step 3: Open the patient's EHR with the demographics composition as its first commit
+    let mut ehr = Ehr::new(patient_did.did.clone());
+    let (composition, _) = ehr
+        .commit_composition(composition, None, format!("user:{}", ctx.user_id()), Some("Patient registration".to_string()))
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?;
 
     println!("   ✅ openEHR composition built (category: {:?})", composition.category);
 
-    // Step 3: Create complete patient record
+    // Step 4: Create complete patient record
     let patient = Patient {
         id: patient_id_clone.clone(),
         did: patient_did.did.clone(),
@@ -127,9 +147,21 @@ pub async fn create_patient_with_ehr(
 
     println!("   ✅ Patient record structured");
 
-    // Step 4: Store in ReductStore
+    // The patient's own account holds the patient DID from the start, so no
+    // other account can be bound to it (custodial signing, key export)
+    if let Some(holder_did) = &holder_did {
+        let holder = account_registry.resolve_or_create(holder_did).await
+            .map_err(Error::Auth)?;
+        account_registry.bind_did(holder.id, &patient.did).await
+            .map_err(Error::Auth)?;
+        println!("   ✅ Patient DID held by account {}", holder.id);
+    }
+
+    // Step 5: Store in ReductStore
     mm.store_patient(&patient).await
         .map_err(|e| Error::Model(e))?;
+    mm.create_ehr(&patient.id, &ehr).await
+        .map_err(|e| Error::Model(e))?;
 
     println!("   ✅ Stored in ReductStore");
     println!("   📊 Patient {} ready for anchoring", patient.id);
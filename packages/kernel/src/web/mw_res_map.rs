@@ -2,7 +2,7 @@ use axum::Json;
 use axum::http::Uri;
 use crate::ctx::Ctx;
 use crate::web;
-use axum::http::Method;
+use axum::http::{Method, HeaderValue, header};
 use axum::response::{Response, IntoResponse};
 use uuid::Uuid;
 use serde_json::json;
@@ -24,15 +24,22 @@ pub async fn mw_reponse_map(
     let error_response = client_status_error
         .as_ref()
         .map(|(status_code, client_error)| {
-            let client_error_body = json!({
+            let mut client_error_body = json!({
                 "error": {
                     "type": client_error.as_ref(),
                     "req_uuid": uuid.to_string(),
                 }
             });
+            if let Some(detail) = web_error.and_then(|e| e.client_detail()) {
+                client_error_body["error"]["detail"] = detail;
+            }
             println!("     ->> client_error_body: {client_error_body}");
 
-            (*status_code, Json(client_error_body)).into_response()
+            let mut response = (*status_code, Json(client_error_body)).into_response();
+            if let Some(retry_after) = web_error.and_then(|e| e.retry_after()) {
+                response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            }
+            response
         });
 
     let client_error = client_status_error.unzip().1;
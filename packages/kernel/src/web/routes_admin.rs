@@ -0,0 +1,246 @@
+use crate::auth::{Account, AccountRegistry, CacheStats, DIDResolver, Roles, RoleRegistry, Session, SessionStore};
+use crate::web::{Error, Result};
+use crate::web::mw_auth::mw_require_role;
+use axum::Json;
+use axum::extract::{State, Path};
+use axum::{Router, middleware};
+use axum::routing::{get, post, delete};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub session_store: SessionStore,
+    pub role_registry: RoleRegistry,
+    pub account_registry: AccountRegistry,
+    pub did_resolver: DIDResolver,
+}
+
+/// Admin routes - every route requires the ADMIN role
+pub fn routes(state: AdminState) -> Router {
+    Router::new()
+        .route("/admin/sessions/:did", get(list_sessions))
+        .route("/admin/sessions/:did", delete(revoke_sessions))
+        .route("/admin/sessions/:did/:sid", delete(revoke_session))
+        .route("/admin/roles/:did", get(get_roles).post(update_roles))
+        .route("/admin/accounts/:id", get(get_account))
+        .route("/admin/accounts/:id/dids", post(bind_did))
+        .route("/admin/accounts/:id/dids/:did", delete(unbind_did))
+        .route("/admin/accounts/:id/onchain-bindings", post(record_onchain_binding))
+        .route("/admin/status-lists", post(import_status_list))
+        .route("/admin/did-cache", get(did_cache_stats))
+        .route_layer(middleware::from_fn_with_state(Roles::ADMIN, mw_require_role))
+        .with_state(state)
+}
+
+// ==================== Sessions ====================
+
+/// List the active sessions of a DID
+async fn list_sessions(
+    State(state): State<AdminState>,
+    Path(did): Path<String>,
+) -> Result<Json<Vec<Session>>> {
+    println!("->> {:<12} - list_sessions - {did}", "HANDLER");
+
+    Ok(Json(state.session_store.list_for_did(&did).await))
+}
+
+/// Kill every active session of a DID
+async fn revoke_sessions(
+    State(state): State<AdminState>,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - revoke_sessions - {did}", "HANDLER");
+
+    let revoked = state.session_store.revoke_all_for_did(&did).await;
+
+    Ok(Json(json!({
+        "success": true,
+        "did": did,
+        "revoked_count": revoked,
+    })))
+}
+
+/// Kill a single session of a DID
+async fn revoke_session(
+    State(state): State<AdminState>,
+    Path((did, sid)): Path<(String, String)>,
+) -> Result<Json<Session>> {
+    println!("->> {:<12} - revoke_session - {did} / {sid}", "HANDLER");
+
+    let owned = state.session_store.list_for_did(&did).await
+        .iter()
+        .any(|session| session.sid == sid);
+    if !owned {
+        return Err(Error::SessionNotFound { did, sid });
+    }
+
+    let session = state.session_store.revoke(&sid)
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(session))
+}
+
+// ==================== Roles ====================
+
+/// Roles currently assigned to a DID
+async fn get_roles(
+    State(state): State<AdminState>,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - get_roles - {did}", "HANDLER");
+
+    let roles = state.role_registry.roles_for(&did).await;
+
+    Ok(Json(roles_body(&did, roles)))
+}
+
+/// Grant and/or revoke roles of a DID (effective from its next access token)
+async fn update_roles(
+    State(state): State<AdminState>,
+    Path(did): Path<String>,
+    Json(payload): Json<RolesUpdate>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - update_roles - {did}", "HANDLER");
+
+    let grant = Roles::from_names(&payload.grant).map_err(Error::Auth)?;
+    let revoke = Roles::from_names(&payload.revoke).map_err(Error::Auth)?;
+
+    state.role_registry.grant(&did, grant).await;
+    let roles = state.role_registry.revoke(&did, revoke).await;
+
+    Ok(Json(roles_body(&did, roles)))
+}
+
+fn roles_body(did: &str, roles: Roles) -> Value {
+    json!({
+        "did": did,
+        "roles": roles.names(),
+        "bits": roles.bits(),
+    })
+}
+
+// ==================== Accounts ====================
+
+async fn get_account(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Account>> {
+    println!("->> {:<12} - get_account - {id}", "HANDLER");
+
+    let account = state.account_registry.get(id).await.map_err(Error::Auth)?;
+
+    Ok(Json(account))
+}
+
+/// Bind another DID to an account (DIDRoleRegistry::bind_did counterpart)
+async fn bind_did(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<BindDid>,
+) -> Result<Json<Account>> {
+    println!("->> {:<12} - bind_did - {} -> {id}", "HANDLER", payload.did);
+
+    let account = state.account_registry
+        .bind_did(id, &payload.did)
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(account))
+}
+
+/// Unbind a DID from an account and end its sessions
+async fn unbind_did(
+    State(state): State<AdminState>,
+    Path((id, did)): Path<(u64, String)>,
+) -> Result<Json<Account>> {
+    println!("->> {:<12} - unbind_did - {did} -> {id}", "HANDLER");
+
+    let account = state.account_registry
+        .unbind_did(id, &did)
+        .await
+        .map_err(Error::Auth)?;
+
+    // Sessions opened with the DID still carry this account's user_id
+    state.session_store.revoke_all_for_did(&did).await;
+
+    Ok(Json(account))
+}
+
+/// Record the on-chain bind_did transaction for a DID of this account
+async fn record_onchain_binding(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<OnChainBindingRecord>,
+) -> Result<Json<Account>> {
+    println!("->> {:<12} - record_onchain_binding - {} -> {}", "HANDLER", payload.did, payload.address);
+
+    let account = state.account_registry
+        .record_onchain_binding(id, &payload.did, &payload.address, &payload.tx_digest)
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(account))
+}
+
+// ==================== Credential Status Lists ====================
+
+/// Import an issuer's StatusList2021Credential so its credentials can be checked
+async fn import_status_list(
+    State(state): State<AdminState>,
+    Json(payload): Json<StatusListImport>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - import_status_list", "HANDLER");
+
+    let list = state.did_resolver
+        .import_status_list(&payload.credential)
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "id": list.id,
+        "issuer": list.issuer,
+    })))
+}
+
+// ==================== DID Resolution ====================
+
+/// DID resolution cache hit/miss counts
+async fn did_cache_stats(
+    State(state): State<AdminState>,
+) -> Result<Json<CacheStats>> {
+    println!("->> {:<12} - did_cache_stats", "HANDLER");
+
+    Ok(Json(state.did_resolver.cache_stats().await))
+}
+
+// ==================== Request Structures ====================
+
+#[derive(Debug, Deserialize)]
+pub struct RolesUpdate {
+    #[serde(default)]
+    pub grant: Vec<String>,
+    #[serde(default)]
+    pub revoke: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BindDid {
+    pub did: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OnChainBindingRecord {
+    pub did: String,
+    /// IOTA address passed to bind_did
+    pub address: String,
+    pub tx_digest: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusListImport {
+    /// StatusList2021Credential as a VC-JWT
+    pub credential: String,
+}
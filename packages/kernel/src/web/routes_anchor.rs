@@ -1,17 +1,20 @@
 use crate::ctx::Ctx;
-use crate::model::{ModelManager, AnchorService, verify_proof};
+use crate::model::{ModelManager, AnchorService, AnchorRecord, verify_proof};
 use crate::web::{Error, Result};
 use axum::Json;
 use axum::extract::{State, Path};
-use axum::Router;
+use axum::{Router, middleware};
 use axum::routing::{post, get};
+use crate::auth::Roles;
+use crate::web::mw_auth::mw_require_role;
 use serde_json::{json, Value};
 
 pub fn routes(mm: ModelManager) -> Router {
     Router::new()
-        .route("/anchor/batch", post(create_batch))
+        .route("/anchor/batch", post(create_batch)
+            .route_layer(middleware::from_fn_with_state(Roles::ANCHORER, mw_require_role)))
         .route("/anchor/pending", get(pending_count))
-        .route("/anchor/verify/:patient_id", get(verify_patient))
+        .route("/anchor/verify/:record_id", get(verify_patient))
         .with_state(mm)
 }
 
@@ -27,20 +30,29 @@ async fn create_batch(
         .map_err(|e| Error::Model(e))?;
 
     match result {
-        Some((batch, patient_ids)) => {
+        Some((batch, tree, records)) => {
             // Anchor to IOTA blockchain using deployed smart contract
             let tx_hash = AnchorService::anchor_to_blockchain(&mm, &batch)
                 .await
                 .map_err(|e| Error::Model(e))?;
             
+            let record_ids: Vec<String> = records.iter().map(AnchorRecord::id).collect();
+            let patient_ids: Vec<String> = records.iter()
+                .filter_map(|record| match record {
+                    AnchorRecord::Patient(patient_id) => Some(patient_id.clone()),
+                    AnchorRecord::Composition { .. } => None,
+                })
+                .collect();
+
             // Store the batch for proof generation
-            mm.store_anchored_batch(batch.clone(), patient_ids.clone()).await;
+            mm.store_anchored_batch(batch.clone(), tree).await;
 
             Ok(Json(json!({
                 "success": true,
                 "batch": batch,
                 "tx_hash": tx_hash,
                 "patient_ids": patient_ids,
+                "record_ids": record_ids,
                 "message": "Batch created and anchored to IOTA"
             })))
         }
@@ -67,7 +79,7 @@ async fn pending_count(
     })))
 }
 
-/// Verify a patient record using Merkle proof
+/// Verify a patient record (or "composition:{uid}") using Merkle proof
 async fn verify_patient(
     State(mm): State<ModelManager>,
     Path(patient_id): Path<String>,
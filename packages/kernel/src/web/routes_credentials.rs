@@ -0,0 +1,173 @@
+use crate::auth::{CredentialIssuer, GatewayCredentialType, IssuedCredential, Roles};
+use crate::ctx::Ctx;
+use crate::did_manager::{DIDRegistry, DIDStatus};
+use crate::model::ModelManager;
+use crate::web::{Error, Result};
+use crate::web::mw_auth::mw_require_role;
+use axum::Json;
+use axum::extract::{State, Path};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::{Router, middleware};
+use axum::routing::{get, post};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+#[derive(Clone)]
+pub struct CredentialState {
+    pub issuer: CredentialIssuer,
+    pub did_registry: DIDRegistry,
+    pub mm: ModelManager,
+}
+
+/// Credential routes (nested under /api, require a session)
+pub fn routes(state: CredentialState) -> Router {
+    Router::new()
+        .route("/credentials", get(list_credentials))
+        .route("/credentials", post(issue_credential)
+            .route_layer(middleware::from_fn_with_state(Roles::PERMIT_ISSUER, mw_require_role)))
+        .route("/credentials/:id", get(get_credential))
+        .route("/credentials/:id/revoke", post(revoke_credential)
+            .route_layer(middleware::from_fn_with_state(Roles::PERMIT_ISSUER, mw_require_role)))
+        .with_state(state)
+}
+
+/// Status list route - public, verifiers fetch it without a session
+pub fn public_routes(state: CredentialState) -> Router {
+    Router::new()
+        .route("/credentials/status/1", get(status_list_credential))
+        .with_state(state)
+}
+
+// ==================== Issuance ====================
+
+/// Sign a gateway credential (PERMIT_ISSUER)
+async fn issue_credential(
+    State(state): State<CredentialState>,
+    ctx: Ctx,
+    Json(payload): Json<CredentialIssue>,
+) -> Result<Json<IssuedCredential>> {
+    println!("->> {:<12} - issue_credential - {:?} -> {}", "HANDLER", payload.credential_type, payload.subject);
+
+    let mut claims = payload.claims;
+
+    if payload.credential_type == GatewayCredentialType::PatientEnrollment {
+        // Only DIDs minted by this gateway can be enrolled patients
+        let patient_did = state.did_registry
+            .get_by_did(&payload.subject)
+            .await
+            .map_err(|_| Error::Auth(crate::auth::Error::CredentialInvalid(
+                format!("{} is not a registered patient DID", payload.subject)
+            )))?;
+
+        if patient_did.metadata.status != DIDStatus::Active {
+            return Err(Error::Auth(crate::auth::Error::CredentialInvalid(
+                format!("{} is {:?}", payload.subject, patient_did.metadata.status)
+            )));
+        }
+
+        claims.insert("patientId".to_string(), json!(patient_did.patient_id));
+    }
+
+    if payload.credential_type == GatewayCredentialType::RecordAnchored {
+        // Only receipts for batches this gateway anchored, with the root it computed
+        let invalid = |reason: String| Error::Auth(crate::auth::Error::CredentialInvalid(reason));
+        let batch_id = claims.get("batchId")
+            .and_then(|batch_id| batch_id.as_u64().or_else(|| batch_id.as_str()?.parse().ok()))
+            .ok_or_else(|| invalid("batchId must be the ID of an anchored batch".to_string()))?;
+        let batch = state.mm.get_anchored_batch(batch_id)
+            .await
+            .ok_or_else(|| invalid(format!("Batch {} has not been anchored", batch_id)))?;
+
+        let merkle_root = claims.get("merkleRoot").and_then(Value::as_str).unwrap_or_default();
+        if !merkle_root.trim_start_matches("0x").eq_ignore_ascii_case(&batch.root_hash_hex) {
+            return Err(invalid(format!("merkleRoot does not match batch {}", batch_id)));
+        }
+
+        claims.insert("merkleRoot".to_string(), json!(batch.root_hash_hex));
+        claims.insert("batchId".to_string(), json!(batch.batch_id));
+    }
+
+    let credential = state.issuer
+        .issue(payload.credential_type, &payload.subject, claims, payload.expires_in, ctx.user_id())
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(credential))
+}
+
+// ==================== Lookup ====================
+
+/// Credentials issued to the caller's DID
+async fn list_credentials(
+    State(state): State<CredentialState>,
+    ctx: Ctx,
+) -> Result<Json<Vec<IssuedCredential>>> {
+    println!("->> {:<12} - list_credentials", "HANDLER");
+
+    let credentials = match ctx.did() {
+        Some(did) => state.issuer.list_for_subject(did).await,
+        None => Vec::new(),
+    };
+
+    Ok(Json(credentials))
+}
+
+/// A single credential - visible to its subject and to issuers/admins
+async fn get_credential(
+    State(state): State<CredentialState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+) -> Result<Json<IssuedCredential>> {
+    println!("->> {:<12} - get_credential - {id}", "HANDLER");
+
+    let credential = state.issuer.get(&id).await.map_err(Error::Auth)?;
+
+    let is_subject = ctx.did() == Some(credential.subject.as_str());
+    if !is_subject && !ctx.has_role(Roles::PERMIT_ISSUER) && !ctx.has_role(Roles::ADMIN) {
+        // Don't reveal that the credential exists
+        return Err(Error::Auth(crate::auth::Error::CredentialNotFound(id)));
+    }
+
+    Ok(Json(credential))
+}
+
+// ==================== Revocation ====================
+
+/// Revoke a credential by setting its status list bit (PERMIT_ISSUER)
+async fn revoke_credential(
+    State(state): State<CredentialState>,
+    Path(id): Path<String>,
+) -> Result<Json<IssuedCredential>> {
+    println!("->> {:<12} - revoke_credential - {id}", "HANDLER");
+
+    let credential = state.issuer.revoke(&id).await.map_err(Error::Auth)?;
+
+    Ok(Json(credential))
+}
+
+/// The gateway's StatusList2021Credential (VC-JWT)
+async fn status_list_credential(
+    State(state): State<CredentialState>,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - status_list_credential", "HANDLER");
+
+    let jwt = state.issuer.status_list_credential().await.map_err(Error::Auth)?;
+
+    Ok(([(header::CONTENT_TYPE, "application/vc+jwt")], jwt))
+}
+
+// ==================== Request Structures ====================
+
+#[derive(Debug, Deserialize)]
+pub struct CredentialIssue {
+    #[serde(rename = "type")]
+    pub credential_type: GatewayCredentialType,
+    /// DID the credential is issued to
+    pub subject: String,
+    /// Extra credentialSubject claims (e.g. merkleRoot, batchId)
+    #[serde(default)]
+    pub claims: Map<String, Value>,
+    /// Lifetime in seconds (no expiry when absent)
+    pub expires_in: Option<u64>,
+}
@@ -0,0 +1,199 @@
+use crate::auth::{AccountRegistry, ChallengeStore, DIDResolver, Roles, StepUpGrant, StepUpStore};
+use crate::ctx::Ctx;
+use crate::did_manager::{CustodialSignature, CustodialSigner, CustodyAuditEntry, ExportedKey, SigningPurpose};
+use crate::web::{Error, Result};
+use axum::Json;
+use axum::Router;
+use axum::extract::{State, Path};
+use axum::routing::{get, post};
+use serde::Deserialize;
+
+#[derive(Clone)]
+pub struct CustodyState {
+    pub signer: CustodialSigner,
+    pub step_ups: StepUpStore,
+    pub account_registry: AccountRegistry,
+    pub challenge_store: ChallengeStore,
+    pub did_resolver: DIDResolver,
+}
+
+/// Custodial signing routes (nested under /api, require a session)
+pub fn routes(state: CustodyState) -> Router {
+    Router::new()
+        .route("/custody/step-up", post(step_up))
+        .route("/custody/:did/sign", post(sign))
+        .route("/custody/:did/export", post(export_key))
+        .route("/custody/:did/audit", get(audit))
+        .with_state(state)
+}
+
+// ==================== Step-Up ====================
+
+/// Re-sign a fresh challenge with the session DID to get a single-use grant
+async fn step_up(
+    State(state): State<CustodyState>,
+    ctx: Ctx,
+    Json(payload): Json<StepUpPayload>,
+) -> Result<Json<StepUpGrant>> {
+    let did = ctx.did()
+        .ok_or_else(|| Error::PermissionDenied("Step-up requires a DID session".to_string()))?;
+
+    println!("->> {:<12} - step_up - {did}", "HANDLER");
+
+    state.challenge_store
+        .verify_and_consume(&payload.nonce, did)
+        .await
+        .map_err(Error::Auth)?;
+
+    let message = format!("Anima Health Auth:{}", payload.nonce);
+    state.did_resolver
+        .verify_signature(did, &message, &payload.signature)
+        .await
+        .map_err(Error::Auth)?;
+
+    Ok(Json(state.step_ups.grant(ctx.user_id(), did).await))
+}
+
+// ==================== Signing ====================
+
+/// Sign a login challenge, consent statement or presentation with the DID's custodial key
+async fn sign(
+    State(state): State<CustodyState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+    Json(payload): Json<SignPayload>,
+) -> Result<Json<CustodialSignature>> {
+    println!("->> {:<12} - sign - {did}", "HANDLER");
+
+    require_holder(&state, &ctx, &did).await?;
+    let grant = state.step_ups
+        .consume(&payload.step_up_token, ctx.user_id())
+        .await
+        .map_err(Error::Auth)?;
+
+    let signature = state.signer
+        .sign(&did, payload.purpose, ctx.user_id(), &grant.did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    Ok(Json(signature))
+}
+
+/// Hand the private key to the patient; the DID becomes self-custodied
+async fn export_key(
+    State(state): State<CustodyState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+    Json(payload): Json<StepUpToken>,
+) -> Result<Json<ExportedKey>> {
+    println!("->> {:<12} - export_key - {did}", "HANDLER");
+
+    require_holder(&state, &ctx, &did).await?;
+    let grant = state.step_ups
+        .consume(&payload.step_up_token, ctx.user_id())
+        .await
+        .map_err(Error::Auth)?;
+
+    let exported = state.signer
+        .export(&did, ctx.user_id(), &grant.did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    Ok(Json(exported))
+}
+
+// ==================== Audit ====================
+
+/// Custodial key uses of a DID - visible to its holder and to admins
+async fn audit(
+    State(state): State<CustodyState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+) -> Result<Json<Vec<CustodyAuditEntry>>> {
+    println!("->> {:<12} - custody_audit - {did}", "HANDLER");
+
+    if !ctx.has_role(Roles::ADMIN) {
+        require_holder(&state, &ctx, &did).await?;
+    }
+
+    Ok(Json(state.signer.audit_for(&did).await))
+}
+
+/// The patient DID must be bound to the caller's account
+async fn require_holder(state: &CustodyState, ctx: &Ctx, did: &str) -> Result<()> {
+    match state.account_registry.account_for_did(did).await {
+        Some(account) if account.id == ctx.user_id() => Ok(()),
+        _ => Err(Error::PermissionDenied(format!("{} is not bound to this account", did))),
+    }
+}
+
+// ==================== Request Structures ====================
+
+#[derive(Debug, Deserialize)]
+pub struct StepUpPayload {
+    /// Fresh nonce from /api/auth/challenge for the session DID
+    pub nonce: String,
+    /// Hex signature over "Anima Health Auth:{nonce}"
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignPayload {
+    pub step_up_token: String,
+    #[serde(flatten)]
+    pub purpose: SigningPurpose,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StepUpToken {
+    pub step_up_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::KeyAlgorithm;
+    use crate::did_manager::DIDRegistry;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_non_holder_cannot_sign_or_export() {
+        let registry = DIDRegistry::new();
+        let patient_did = registry.create_patient_did("p-holder".to_string(), 1, KeyAlgorithm::Ed25519).await.unwrap();
+        let state = CustodyState {
+            signer: CustodialSigner::in_memory(registry.clone()),
+            step_ups: StepUpStore::new(),
+            account_registry: AccountRegistry::in_memory(),
+            challenge_store: ChallengeStore::new(),
+            did_resolver: DIDResolver::new(registry),
+        };
+        let holder = state.account_registry.resolve_or_create("did:key:z6Mkholder").await.unwrap();
+        state.account_registry.bind_did(holder.id, &patient_did.did).await.unwrap();
+        let other = state.account_registry.resolve_or_create("did:key:z6Mkother").await.unwrap();
+        let ctx = Ctx::new(other.id, "did:key:z6Mkother").unwrap();
+
+        let signed = sign(
+            State(state.clone()),
+            ctx.clone(),
+            Path(patient_did.did.clone()),
+            Json(SignPayload {
+                step_up_token: "t".to_string(),
+                purpose: SigningPurpose::Consent { statement: "share".to_string() },
+            }),
+        ).await;
+        let err = signed.err().expect("non-holder signed");
+        assert_eq!(err.client_status_and_error().0, StatusCode::FORBIDDEN);
+
+        let exported = export_key(
+            State(state.clone()),
+            ctx,
+            Path(patient_did.did.clone()),
+            Json(StepUpToken { step_up_token: "t".to_string() }),
+        ).await;
+        let err = exported.err().expect("non-holder exported the key");
+        assert_eq!(err.client_status_and_error().0, StatusCode::FORBIDDEN);
+
+        // Still custodial: nothing was handed over
+        assert!(state.signer.audit_for(&patient_did.did).await.is_empty());
+    }
+}
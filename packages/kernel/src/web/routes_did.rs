@@ -0,0 +1,222 @@
+use crate::auth::{AccountRegistry, DIDResolver, Roles, SessionStore};
+use crate::ctx::Ctx;
+use crate::did_manager::{DIDRegistry, DIDStatus, KeySelector, PatientDID};
+use crate::web::{Error, Result};
+use axum::Json;
+use axum::Router;
+use axum::extract::{State, Path};
+use axum::routing::{get, post};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+pub struct DidState {
+    pub did_registry: DIDRegistry,
+    pub account_registry: AccountRegistry,
+    pub did_resolver: DIDResolver,
+    pub session_store: SessionStore,
+}
+
+/// Patient DID routes (nested under /api, require a session)
+///
+/// DIDs are created with their patient (`POST /api/patient`), and each change
+/// is published to the DID ledger as it's made, so there's no create or
+/// publish route.
+pub fn routes(state: DidState) -> Router {
+    Router::new()
+        .route("/did/:did", get(resolve_did))
+        .route("/did/:did/rotate", post(rotate_key))
+        .route("/did/:did/revoke", post(revoke_did))
+        .route("/did/:did/deactivate", post(deactivate_did))
+        .route("/did/:did/verify", post(verify_signature))
+        .route("/did/:did/history", get(published_history))
+        .with_state(state)
+}
+
+// ==================== Resolution ====================
+
+/// Resolve any supported DID to its W3C DID document
+///
+/// Patient DIDs also get document metadata (created, updated, deactivated,
+/// versionId = current key version).
+async fn resolve_did(
+    State(state): State<DidState>,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - resolve_did - {did}", "HANDLER");
+
+    let doc = state.did_resolver.resolve(&did).await.map_err(Error::Auth)?;
+    let did_document: Value = serde_json::from_str(&doc.raw_document)
+        .map_err(|e| Error::Auth(crate::auth::Error::DIDDocumentInvalid(e.to_string())))?;
+
+    let did_document_metadata = match state.did_registry.get_by_did(&did).await {
+        Ok(patient_did) => document_metadata(&patient_did),
+        Err(_) => json!({}),
+    };
+
+    Ok(Json(json!({
+        "didDocument": did_document,
+        "didDocumentMetadata": did_document_metadata,
+    })))
+}
+
+fn document_metadata(patient_did: &PatientDID) -> Value {
+    json!({
+        "created": patient_did.metadata.created_at,
+        "updated": patient_did.metadata.updated_at,
+        "deactivated": patient_did.metadata.status != DIDStatus::Active,
+        "revoked": patient_did.metadata.status == DIDStatus::Revoked,
+        "versionId": patient_did.metadata.key_version.to_string(),
+    })
+}
+
+/// Every version of the DID document published to the DID ledger, oldest first
+///
+/// The chain (version links and signatures) is checked before it is returned.
+async fn published_history(
+    State(state): State<DidState>,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - published_history - {did}", "HANDLER");
+
+    let history = state.did_registry
+        .published_history(&did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    Ok(Json(json!({
+        "did": did,
+        "versions": history,
+    })))
+}
+
+// ==================== Key Rotation ====================
+
+/// Rotate a custodial DID's key - the previous key stays in the document as `#key-{n}`
+async fn rotate_key(
+    State(state): State<DidState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - rotate_key - {did}", "HANDLER");
+
+    require_controller(&state, &ctx, &did).await?;
+
+    let patient_did = state.did_registry
+        .rotate_key(&did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "did": patient_did.did,
+        "key_id": patient_did.key_id(),
+        "key_version": patient_did.metadata.key_version,
+        "document": patient_did.create_did_document(),
+    })))
+}
+
+// ==================== Revocation & Deactivation ====================
+
+/// Revoke a DID after a key compromise - no signature of it verifies any more
+async fn revoke_did(
+    State(state): State<DidState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - revoke_did - {did}", "HANDLER");
+
+    require_controller(&state, &ctx, &did).await?;
+
+    let patient_did = state.did_registry
+        .revoke(&did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    retired_response(&state, &patient_did).await
+}
+
+/// Deactivate a DID - no new logins, earlier signatures still verify by key version/time
+async fn deactivate_did(
+    State(state): State<DidState>,
+    ctx: Ctx,
+    Path(did): Path<String>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - deactivate_did - {did}", "HANDLER");
+
+    require_controller(&state, &ctx, &did).await?;
+
+    let patient_did = state.did_registry
+        .deactivate(&did)
+        .await
+        .map_err(Error::DIDManager)?;
+
+    retired_response(&state, &patient_did).await
+}
+
+/// End the DID's sessions (access tokens also fail the per-request DID check)
+async fn retired_response(state: &DidState, patient_did: &PatientDID) -> Result<Json<Value>> {
+    let revoked_sessions = state.session_store.revoke_all_for_did(&patient_did.did).await;
+
+    Ok(Json(json!({
+        "success": true,
+        "did": patient_did.did,
+        "status": patient_did.metadata.status,
+        "revoked_sessions": revoked_sessions,
+        "didDocumentMetadata": document_metadata(patient_did),
+    })))
+}
+
+// ==================== Verification ====================
+
+/// Check a signature against the DID's current key, a key version, or the key
+/// that was current when the message was signed
+async fn verify_signature(
+    State(state): State<DidState>,
+    Path(did): Path<String>,
+    Json(payload): Json<VerifyPayload>,
+) -> Result<Json<Value>> {
+    println!("->> {:<12} - verify_signature - {did}", "HANDLER");
+
+    let selector = match (payload.key_version, payload.signed_at) {
+        (Some(version), _) => KeySelector::Version(version),
+        (None, Some(signed_at)) => KeySelector::At(signed_at),
+        (None, None) => KeySelector::Current,
+    };
+
+    let valid = state.did_resolver
+        .verify_signature_with_key(&did, &payload.message, &payload.signature, selector)
+        .await
+        .is_ok();
+
+    Ok(Json(json!({
+        "did": did,
+        "valid": valid,
+    })))
+}
+
+/// The DID must be bound to the caller's account, or the caller is an admin
+async fn require_controller(state: &DidState, ctx: &Ctx, did: &str) -> Result<()> {
+    if ctx.has_role(Roles::ADMIN) {
+        return Ok(());
+    }
+
+    match state.account_registry.account_for_did(did).await {
+        Some(account) if account.id == ctx.user_id() => Ok(()),
+        _ => Err(Error::PermissionDenied(format!("{} is not bound to this account", did))),
+    }
+}
+
+// ==================== Request Structures ====================
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyPayload {
+    pub message: String,
+    /// Hex or base64 signature
+    pub signature: String,
+    /// Check against `#key-{key_version}` (takes precedence over `signed_at`)
+    pub key_version: Option<u64>,
+    /// Check against the key that was current at this time (RFC 3339)
+    pub signed_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,71 @@
+use crate::ctx::Ctx;
+use crate::fhir::{self, FhirConfig, Resource, FHIR_JSON};
+use crate::model::{ModelManager, PatientBmc};
+use crate::web::{Error, Result};
+use crate::web::mw_auth::mw_require_care_staff;
+use axum::Json;
+use axum::extract::{State, Path};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::{Router, middleware};
+use axum::routing::get;
+
+#[derive(Clone)]
+pub struct FhirState {
+    pub mm: ModelManager,
+    pub config: FhirConfig,
+}
+
+/// FHIR export routes - whole records for partner systems, so care staff only
+pub fn routes(mm: ModelManager, config: FhirConfig) -> Router {
+    Router::new()
+        .route("/fhir/Patient/:id", get(get_patient))
+        .route("/fhir/Patient/:id/$everything", get(patient_everything))
+        .route_layer(middleware::from_fn(mw_require_care_staff))
+        .with_state(FhirState { mm, config })
+}
+
+// ==================== FHIR R4 ====================
+
+/// A patient record as a FHIR Patient (DID and MRN as identifiers)
+async fn get_patient(
+    State(state): State<FhirState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - fhir_get_patient - {id}", "HANDLER");
+
+    let patient = PatientBmc::get(&ctx, &state.mm, &id)
+        .await
+        .map_err(Error::Model)?;
+    let ehr = state.mm.get_ehr(&id)
+        .await
+        .map_err(Error::Model)?;
+
+    Ok(fhir_json(Resource::Patient(fhir::to_fhir_patient(&patient, &ehr.latest_compositions(), &state.config))))
+}
+
+/// The patient and the observations of its EHR as a searchset Bundle
+async fn patient_everything(
+    State(state): State<FhirState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - fhir_patient_everything - {id}", "HANDLER");
+
+    let patient = PatientBmc::get(&ctx, &state.mm, &id)
+        .await
+        .map_err(Error::Model)?;
+    let ehr = state.mm.get_ehr(&id)
+        .await
+        .map_err(Error::Model)?;
+
+    let bundle = fhir::everything(&patient, &ehr.latest_compositions(), &state.config);
+    println!("   📦 Bundle of {} resources", bundle.entry.len());
+
+    Ok(fhir_json(Resource::Bundle(bundle)))
+}
+
+fn fhir_json(resource: Resource) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, FHIR_JSON)], Json(resource))
+}
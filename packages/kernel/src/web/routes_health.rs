@@ -25,22 +25,47 @@ async fn api_info() -> Json<Value> {
         "features": {
             "iota_did_auth": true,
             "openehr_compositions": true,
+            "fhir_export": true,
             "merkle_anchoring": true,
             "reductstore_integration": true
         },
         "endpoints": {
             "auth": [
                 "POST /api/auth/challenge - Request authentication challenge",
-                "POST /api/login - Submit signed challenge"
+                "POST /api/login - Submit signed challenge",
+                "POST /api/auth/refresh - Rotate refresh token, get new access token",
+                "POST /api/logout - Revoke the current session",
+                "GET /api/auth/me - Caller identity, roles and verified credentials",
+                "GET /.well-known/jwks.json - Gateway public keys for EdDSA access tokens"
             ],
             "patients": [
                 "POST /api/patient - Create patient with DID and openEHR",
                 "GET /api/patient - List all patients",
                 "GET /api/patient/:id - Get patient by ID",
-                "DELETE /api/patient/:id - Delete patient"
+                "DELETE /api/patient/:id - Delete patient (ADMIN)"
+            ],
+            "admin": [
+                "GET /api/admin/sessions/:did - List a DID's active sessions",
+                "DELETE /api/admin/sessions/:did - Revoke all sessions of a DID",
+                "DELETE /api/admin/sessions/:did/:sid - Revoke one session",
+                "GET /api/admin/roles/:did - Get a DID's roles",
+                "POST /api/admin/roles/:did - Grant/revoke roles",
+                "GET /api/admin/accounts/:id - Get an account and its DIDs",
+                "POST /api/admin/accounts/:id/dids - Bind a DID to an account",
+                "DELETE /api/admin/accounts/:id/dids/:did - Unbind a DID",
+                "POST /api/admin/accounts/:id/onchain-bindings - Record an on-chain bind_did",
+                "POST /api/admin/status-lists - Import a StatusList2021 credential",
+                "GET /api/admin/did-cache - DID resolution cache hits/misses"
+            ],
+            "credentials": [
+                "POST /api/credentials - Issue a gateway credential (PERMIT_ISSUER)",
+                "GET /api/credentials - List credentials issued to the caller",
+                "GET /api/credentials/:id - Get a credential (subject, PERMIT_ISSUER or ADMIN)",
+                "POST /api/credentials/:id/revoke - Revoke a credential (PERMIT_ISSUER)",
+                "GET /credentials/status/1 - Gateway StatusList2021 credential"
             ],
             "anchoring": [
-                "POST /api/anchor/batch - Create Merkle batch and anchor",
+                "POST /api/anchor/batch - Create Merkle batch and anchor (ANCHORER)",
                 "GET /api/anchor/pending - Get pending anchor count"
             ]
         },
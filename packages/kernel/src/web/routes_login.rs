@@ -1,21 +1,32 @@
 use crate::web::{Error, Result};
-use crate::auth::{ChallengeStore, DIDResolver, TokenManager};
+use crate::ctx::Ctx;
+use crate::auth::{ChallengeStore, TrustedProxies, DIDResolver, TokenManager, SessionStore, RoleRegistry, AccountRegistry, VerifiedCredential};
 use serde::{Deserialize, Serialize};
-use axum::{Json, Router, routing::post, extract::State};
+use axum::{Json, Router, routing::{get, post}, extract::{ConnectInfo, State}, http::HeaderMap};
 use axum_extra::extract::cookie::{Cookie, CookieJar};
 use serde_json::{Value, json};
 use crate::web;
+use std::net::SocketAddr;
 
 #[derive(Clone)]
 pub struct AuthState {
     pub challenge_store: ChallengeStore,
+    /// Reverse proxies whose X-Forwarded-For hops name the client
+    pub trusted_proxies: TrustedProxies,
     pub did_resolver: DIDResolver,
+    pub token_manager: TokenManager,
+    pub session_store: SessionStore,
+    pub role_registry: RoleRegistry,
+    pub account_registry: AccountRegistry,
 }
 
 pub fn routes(auth_state: AuthState) -> Router {
     Router::new()
         .route("/api/auth/challenge", post(request_challenge))
         .route("/api/login", post(api_login))
+        .route("/api/auth/refresh", post(api_refresh))
+        .route("/api/logout", post(api_logout))
+        .route("/api/auth/me", get(api_me))
         .with_state(auth_state)
 }
 
@@ -24,12 +35,21 @@ pub fn routes(auth_state: AuthState) -> Router {
 /// Client requests a challenge nonce for DID authentication
 async fn request_challenge(
     State(auth_state): State<AuthState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<ChallengeRequest>,
 ) -> Result<Json<ChallengeResponse>> {
-    println!("->> {:<12} - request_challenge", "HANDLER");
+    println!("->> {:<12} - request_challenge - DID: {}", "HANDLER", payload.did);
 
-    // Generate challenge nonce (expires in 5 minutes)
-    let challenge = auth_state.challenge_store.create_challenge(payload.did).await;
+    // Generate challenge nonce bound to the DID (expires in 5 minutes, rate limited)
+    let client_ip = auth_state.trusted_proxies.client_ip(
+        connect_info.map(|ConnectInfo(addr)| addr.ip()),
+        headers.get_all("x-forwarded-for").iter().filter_map(|value| value.to_str().ok()),
+    );
+    let challenge = auth_state.challenge_store
+        .create_challenge(&payload.did, client_ip)
+        .await
+        .map_err(Error::Auth)?;
 
     let response = ChallengeResponse {
         nonce: challenge.nonce,
@@ -49,15 +69,15 @@ async fn api_login(
 ) -> Result<(CookieJar, Json<Value>)> {
     println!("->> {:<12} - api_login - DID: {}", "HANDLER", payload.did);
 
-    // Step 1: Verify the challenge hasn't expired
-    let challenge = auth_state.challenge_store
-        .verify_and_consume(&payload.nonce)
+    // Step 1: Verify the challenge hasn't expired and was issued to this DID
+    auth_state.challenge_store
+        .verify_and_consume(&payload.nonce, &payload.did)
         .await
         .map_err(|e| Error::AuthFail(format!("Challenge verification failed: {}", e)))?;
 
     println!("   ✅ Challenge verified");
 
-    // Step 2: Resolve DID document from IOTA Tangle
+    // Step 2: Resolve DID document (did:key, did:web, did:iota)
     let _did_doc = auth_state.did_resolver
         .resolve(&payload.did)
         .await
@@ -69,61 +89,221 @@ async fn api_login(
     // Message format: "Anima Health Auth:{nonce}"
     let message = format!("Anima Health Auth:{}", payload.nonce);
     
-    let signature_valid = auth_state.did_resolver
+    auth_state.did_resolver
         .verify_signature(&payload.did, &message, &payload.signature)
         .await
-        .map_err(|e| Error::AuthFail(format!("Signature verification failed: {}", e)))?;
+        .map_err(Error::Auth)?;
+
+    println!("   ✅ Signature verified");
 
-    if !signature_valid {
-        return Err(Error::AuthFail("Invalid signature".to_string()));
+    // Step 3b: Verify presented credentials (all must be valid and held by the DID)
+    let mut credentials = Vec::new();
+    for vc_jwt in &payload.credentials {
+        let credential = auth_state.did_resolver
+            .verify_credential(vc_jwt, &payload.did)
+            .await
+            .map_err(Error::Auth)?;
+        credentials.push(credential);
     }
+    let credential_types = credential_types(&credentials);
 
-    println!("   ✅ Signature verified");
+    // Step 4: Resolve the account bound to the DID (created on first login)
+    let account = auth_state.account_registry
+        .resolve_or_create(&payload.did)
+        .await
+        .map_err(Error::Auth)?;
+    let user_id = account.id;
 
-    // Step 4: Generate access token (JWT-like)
-    // For POC: Simple user_id mapping (in production: lookup from DID registry)
-    let user_id = did_to_user_id(&payload.did);
+    println!("   ✅ Account {} resolved", user_id);
 
-    let token = TokenManager::generate_token(&payload.did, user_id)
-        .map_err(|e| Error::AuthFail(format!("Token generation failed: {}", e)))?;
+    // Step 5: Open a session and generate the signed access token
+    let (session, refresh_token) = auth_state.session_store
+        .create_session(&payload.did, user_id, credentials)
+        .await;
 
-    println!("   ✅ Access token generated");
+    let roles = auth_state.role_registry.roles_for(&payload.did).await;
 
-    // Step 5: Set cookie with token
-    let jar = jar.add(Cookie::new(web::AUTH_TOKEN, token));
+    let token = auth_state.token_manager
+        .generate_token(&payload.did, user_id, &session.sid, roles, &credential_types)
+        .map_err(|e| Error::AuthFail(format!("Token generation failed: {}", e)))?;
+
+    println!("   ✅ Access token generated (session: {}, roles: [{}])", session.sid, roles);
 
-    // Success response
-    let body = Json(json!({
+    // Step 6: Hand out access + refresh token (cookies, or the body for bearer clients)
+    let mut body = json!({
         "success": true,
         "user_id": user_id,
         "did": payload.did,
+        "roles": roles.names(),
+        "credentials": credential_types,
+        "expires_in": auth_state.token_manager.access_ttl_secs(),
         "message": "Authentication successful"
-    }));
+    });
+    let jar = deliver_tokens(jar, &mut body, payload.token_delivery, token, refresh_token);
 
-    Ok((jar, body))
+    Ok((jar, Json(body)))
 }
 
-/// Map DID to user_id (for POC - in production would query did_role_registry contract)
-fn did_to_user_id(did: &str) -> u64 {
-    // Simple hash-based mapping for POC
-    // In production: Call did_role_registry::get_did_accounts()
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    
-    let mut hasher = DefaultHasher::new();
-    did.hash(&mut hasher);
-    let hash = hasher.finish();
-    
-    // Convert to user_id (ensure not 0 for root_ctx protection)
-    (hash % 1_000_000) + 1
+// ==================== Refresh & Logout ====================
+
+/// Exchange a refresh token for a new access token (the refresh token rotates)
+async fn api_refresh(
+    State(auth_state): State<AuthState>,
+    jar: CookieJar,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<(CookieJar, Json<Value>)> {
+    println!("->> {:<12} - api_refresh", "HANDLER");
+
+    let (refresh_token, token_delivery) = match payload {
+        Some(Json(payload)) => (payload.refresh_token, payload.token_delivery),
+        None => (None, TokenDelivery::default()),
+    };
+
+    let refresh_token = refresh_token
+        .or_else(|| jar.get(web::REFRESH_TOKEN).map(|c| c.value().to_string()))
+        .ok_or_else(|| Error::AuthFail("No refresh token".to_string()))?;
+
+    let (session, refresh_token) = auth_state.session_store
+        .rotate_refresh_token(&refresh_token)
+        .await
+        .map_err(Error::Auth)?;
+
+    auth_state.did_resolver
+        .check_did_active(&session.did)
+        .await
+        .map_err(Error::Auth)?;
+
+    // Re-read roles so grants/revocations apply from the next access token on
+    let roles = auth_state.role_registry.roles_for(&session.did).await;
+
+    // Drop login credentials that expired or were revoked since
+    let mut credentials = Vec::new();
+    for credential in session.credentials {
+        match auth_state.did_resolver.credential_policy().check_status(&credential).await {
+            Ok(()) => credentials.push(credential),
+            Err(e) => println!("   ->> Dropping credential {:?}: {:?}", credential.types, e),
+        }
+    }
+    let credential_types = credential_types(&credentials);
+
+    let token = auth_state.token_manager
+        .generate_token(&session.did, session.user_id, &session.sid, roles, &credential_types)
+        .map_err(Error::Auth)?;
+
+    println!("   ✅ Session {} refreshed", session.sid);
+
+    let mut body = json!({
+        "success": true,
+        "did": session.did,
+        "roles": roles.names(),
+        "credentials": credential_types,
+        "expires_in": auth_state.token_manager.access_ttl_secs(),
+    });
+    let jar = deliver_tokens(jar, &mut body, token_delivery, token, refresh_token);
+
+    Ok((jar, Json(body)))
+}
+
+/// Revoke the current session and clear the session cookies
+async fn api_logout(
+    State(auth_state): State<AuthState>,
+    jar: CookieJar,
+    ctx: Option<Ctx>,
+) -> Result<(CookieJar, Json<Value>)> {
+    println!("->> {:<12} - api_logout", "HANDLER");
+
+    // Prefer the session of a valid access token, fall back to the refresh token
+    let revoked = match ctx.as_ref().and_then(|ctx| ctx.session_id()) {
+        Some(sid) => auth_state.session_store.revoke(sid).await.ok(),
+        None => match jar.get(web::REFRESH_TOKEN) {
+            Some(cookie) => auth_state.session_store
+                .revoke_by_refresh_token(cookie.value())
+                .await
+                .ok(),
+            None => None,
+        },
+    };
+
+    let jar = jar
+        .remove(session_cookie(web::AUTH_TOKEN))
+        .remove(session_cookie(web::REFRESH_TOKEN));
+
+    Ok((jar, Json(json!({
+        "success": true,
+        "session_revoked": revoked.is_some(),
+    }))))
+}
+
+/// Identity of the caller as seen by the gateway
+async fn api_me(ctx: Ctx) -> Result<Json<Value>> {
+    println!("->> {:<12} - api_me", "HANDLER");
+
+    Ok(Json(json!({
+        "user_id": ctx.user_id(),
+        "did": ctx.did(),
+        "session_id": ctx.session_id(),
+        "roles": ctx.roles().names(),
+        "credentials": ctx.credential_types(),
+    })))
+}
+
+// ==================== Session Cookies ====================
+
+/// Cookie skeleton with the path/flags used for session cookies (also used for removal)
+pub fn session_cookie(name: &'static str) -> Cookie<'static> {
+    let mut cookie = Cookie::from(name);
+    cookie.set_http_only(true);
+    // The refresh token is only ever sent to /api (refresh + logout)
+    cookie.set_path(if name == web::REFRESH_TOKEN { "/api" } else { "/" });
+    cookie
+}
+
+fn add_session_cookies(jar: CookieJar, access_token: String, refresh_token: String) -> CookieJar {
+    let mut access_cookie = session_cookie(web::AUTH_TOKEN);
+    access_cookie.set_value(access_token);
+
+    let mut refresh_cookie = session_cookie(web::REFRESH_TOKEN);
+    refresh_cookie.set_value(refresh_token);
+
+    jar.add(access_cookie).add(refresh_cookie)
+}
+
+/// Set the session cookies, or put the tokens in the response body for bearer clients
+fn deliver_tokens(
+    jar: CookieJar,
+    body: &mut Value,
+    delivery: TokenDelivery,
+    access_token: String,
+    refresh_token: String,
+) -> CookieJar {
+    match delivery {
+        TokenDelivery::Cookie => add_session_cookies(jar, access_token, refresh_token),
+        TokenDelivery::Body => {
+            body["access_token"] = json!(access_token);
+            body["refresh_token"] = json!(refresh_token);
+            body["token_type"] = json!("Bearer");
+            jar
+        }
+    }
+}
+
+/// Distinct credential types, in presentation order
+fn credential_types(credentials: &[VerifiedCredential]) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    for credential_type in credentials.iter().flat_map(|c| c.types.iter()) {
+        if !types.contains(credential_type) {
+            types.push(credential_type.clone());
+        }
+    }
+    types
 }
 
 // ==================== Request/Response Structures ====================
 
 #[derive(Debug, Deserialize)]
 pub struct ChallengeRequest {
-    /// Optional DID of the caller (for logging/tracking)
-    pub did: Option<String>,
+    /// DID that will log in with the challenge
+    pub did: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -134,6 +314,25 @@ pub struct ChallengeResponse {
     pub expires_at: u64,
 }
 
+/// How login/refresh hand out the session tokens
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenDelivery {
+    /// HttpOnly auth-token / refresh-token cookies (browsers)
+    #[default]
+    Cookie,
+    /// Tokens in the JSON body, to be sent back as `Authorization: Bearer`
+    Body,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshPayload {
+    /// Refresh token (falls back to the refresh-token cookie)
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub token_delivery: TokenDelivery,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoginPayload {
     /// DID of the caller, e.g. "did:iota:anima:abc123"
@@ -143,4 +342,9 @@ pub struct LoginPayload {
     /// Signature over the message "Anima Health Auth:{nonce}"
     /// Signed with the DID's private key
     pub signature: String,
+    /// Verifiable credentials (VC-JWT) held by the DID, e.g. LicensedPhysician
+    #[serde(default)]
+    pub credentials: Vec<String>,
+    #[serde(default)]
+    pub token_delivery: TokenDelivery,
 }
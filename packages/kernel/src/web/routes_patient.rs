@@ -1,26 +1,52 @@
 use crate::ctx::Ctx;
 use crate::model::{ModelManager, PatientBmc, PatientForCreate, Patient};
+use crate::ehr::{Composition, CompositionBuilder, CompositionCategory, Contribution, DvCodedText, DvDateTime, Entry, ObjectVersionId, RevisionHistoryItem, TemplateRegistry};
+use crate::ehr::format::{self, CompositionFormat};
 use crate::did_manager::DIDRegistry;
+use crate::auth::AccountRegistry;
 use crate::web::{Error, Result, mw_ehr};
 use axum::Json;
-use axum::extract::{State, Path};
-use axum::Router;
-use axum::routing::{post, get, delete};
+use axum::extract::{State, Path, Query};
+use axum::http::{HeaderMap, header};
+use axum::response::IntoResponse;
+use axum::{Router, middleware};
+use axum::routing::{post, get, put, delete};
+use crate::auth::Roles;
+use crate::web::mw_auth::{authorize_patient, mw_require_care_staff, mw_require_role};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Clone)]
 pub struct PatientState {
     pub mm: ModelManager,
     pub did_registry: DIDRegistry,
+    pub templates: TemplateRegistry,
+    /// Who holds each patient DID (the patient's own account)
+    pub account_registry: AccountRegistry,
 }
 
-pub fn routes(mm: ModelManager, did_registry: DIDRegistry) -> Router {
-    let state = PatientState { mm, did_registry };
+/// Patient routes: creating and listing patients and committing compositions is
+/// for care staff (admins and clinicians); a single record and its EHR can also
+/// be read by the patient's own account
+pub fn routes(mm: ModelManager, did_registry: DIDRegistry, templates: TemplateRegistry, account_registry: AccountRegistry) -> Router {
+    let state = PatientState { mm, did_registry, templates, account_registry };
     
     Router::new()
-        .route("/patient", post(create_patient))
-        .route("/patient", get(list_patients))
+        .route("/patient", post(create_patient)
+            .route_layer(middleware::from_fn(mw_require_care_staff)))
+        .route("/patient", get(list_patients)
+            .route_layer(middleware::from_fn(mw_require_care_staff)))
         .route("/patient/:id", get(get_patient))
-        .route("/patient/:id", delete(delete_patient))
+        .route("/patient/:id", delete(delete_patient)
+            .route_layer(middleware::from_fn_with_state(Roles::ADMIN, mw_require_role)))
+        .route("/patient/:id/compositions", post(create_composition)
+            .route_layer(middleware::from_fn(mw_require_care_staff)))
+        .route("/patient/:id/compositions", get(list_compositions))
+        .route("/patient/:id/compositions/:uid", get(get_composition))
+        .route("/patient/:id/compositions/:uid", put(update_composition)
+            .route_layer(middleware::from_fn(mw_require_care_staff)))
+        .route("/patient/:id/compositions/:uid/versions", get(composition_versions))
+        .route("/patient/:id/contributions", get(list_contributions))
         .with_state(state)
 }
 
@@ -40,6 +66,8 @@ async fn create_patient(
         &ctx,
         &state.mm,
         &state.did_registry,
+        &state.templates,
+        &state.account_registry,
         patient_c,
     ).await?;
 
@@ -57,13 +85,23 @@ async fn get_patient(
 ) -> Result<Json<Patient>> {
     println!("->> {:<12} - get_patient - {id}", "HANDLER");
 
-    let patient = PatientBmc::get(&ctx, &state.mm, &id)
-        .await
-        .map_err(|e| Error::Model(e))?;
+    let patient = authorized_patient(&state, &ctx, &id).await?;
 
     Ok(Json(patient))
 }
 
+/// A patient record, once the caller is known to be care staff or its holder
+async fn authorized_patient(state: &PatientState, ctx: &Ctx, id: &str) -> Result<Patient> {
+    let patient = PatientBmc::get(ctx, &state.mm, id)
+        .await
+        .map_err(Error::Model)?;
+
+    let holder = state.account_registry.account_for_did(&patient.did).await;
+    authorize_patient(ctx, holder.map(|account| account.id))?;
+
+    Ok(patient)
+}
+
 async fn list_patients(
     State(state): State<PatientState>,
     ctx: Ctx,
@@ -90,3 +128,343 @@ async fn delete_patient(
 
     Ok(Json(patient))
 }
+
+// ==================== Compositions ====================
+
+/// Record a new composition (encounter, vitals, diagnosis, ...) in the patient's EHR
+///
+/// The body is read by its Content-Type: the internal layout (`application/json`),
+/// openEHR canonical JSON, FLAT or STRUCTURED (`?template_id=` names the template
+/// of the latter two). The composition is validated against its template (422 with
+/// the violations otherwise), is about the patient's DID, composed by the caller,
+/// committed as version 1 of a new versioned object, and queued for Merkle anchoring
+/// on its own. The response carries it in the format of the Accept header.
+async fn create_composition(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+    Query(params): Query<FormatParams>,
+    headers: HeaderMap,
+    Json(document): Json<Value>,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - create_composition - {id}", "HANDLER");
+
+    let accepted = accepted_format(&headers)?;
+    let composition_c = composition_from_document(&state.templates, content_format(&headers)?, params.template_id, document)?;
+
+    let patient = authorized_patient(&state, &ctx, &id).await?;
+
+    let description = composition_c.description.clone();
+    let composition = build_composition(&ctx, &state.templates, ObjectVersionId::new_object().to_string(), patient.did, composition_c)?;
+
+    let (composition, contribution) = state.mm
+        .commit_composition(&id, composition, None, format!("user:{}", ctx.user_id()), description)
+        .await
+        .map_err(|e| Error::Model(e))?;
+
+    println!("   ✅ Composition {} added ({} entries)", composition.uid, composition.content.len());
+
+    commit_response(&state.templates, accepted, composition, contribution)
+}
+
+/// Commit a new version of a composition - earlier versions stay readable
+///
+/// The preceding version - `preceding_version_uid` in the internal layout, the
+/// `If-Match` header for the openEHR formats - must be the latest version of
+/// `:uid` (the versioned object id), otherwise the commit is refused with 412.
+async fn update_composition(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path((id, uid)): Path<(String, String)>,
+    Query(params): Query<FormatParams>,
+    headers: HeaderMap,
+    Json(document): Json<Value>,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - update_composition - {id}/{uid}", "HANDLER");
+
+    let accepted = accepted_format(&headers)?;
+    let (preceding_version_uid, composition_c) = match content_format(&headers)? {
+        CompositionFormat::Internal => {
+            let composition_u: CompositionForUpdate = serde_json::from_value(document)
+                .map_err(|e| Error::Model(crate::model::Error::InvalidComposition(e.to_string())))?;
+            (composition_u.preceding_version_uid, composition_u.composition)
+        }
+        content_format => {
+            let preceding_version_uid = headers.get(header::IF_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().trim_matches('"').to_string())
+                .ok_or_else(|| Error::Model(crate::model::Error::InvalidComposition(
+                    "If-Match (preceding version uid) is required".to_string()
+                )))?;
+            (preceding_version_uid, composition_from_document(&state.templates, content_format, params.template_id, document)?)
+        }
+    };
+
+    let preceding = ObjectVersionId::parse(&preceding_version_uid)
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?;
+    if preceding.object_id != uid {
+        return Err(Error::Model(crate::model::Error::InvalidComposition(
+            format!("{} is not a version of {}", preceding_version_uid, uid)
+        )));
+    }
+
+    let patient = authorized_patient(&state, &ctx, &id).await?;
+
+    let description = composition_c.description.clone();
+    let composition = build_composition(&ctx, &state.templates, preceding.next().to_string(), patient.did, composition_c)?;
+
+    let (composition, contribution) = state.mm
+        .commit_composition(&id, composition, Some(&preceding_version_uid), format!("user:{}", ctx.user_id()), description)
+        .await
+        .map_err(|e| Error::Model(e))?;
+
+    println!("   ✅ Composition {} committed", composition.uid);
+
+    commit_response(&state.templates, accepted, composition, contribution)
+}
+
+/// Build the composition and validate it against its template
+fn build_composition(
+    ctx: &Ctx,
+    templates: &TemplateRegistry,
+    uid: String,
+    subject_did: String,
+    composition_c: CompositionForCreate,
+) -> Result<Composition> {
+    if composition_c.content.is_empty() {
+        return Err(Error::Model(crate::model::Error::InvalidComposition(
+            "Composition has no content".to_string()
+        )));
+    }
+
+    let mut builder = CompositionBuilder::new(
+        uid,
+        subject_did,
+        composition_c.archetype_id,
+        composition_c.name,
+        format!("user:{}", ctx.user_id()),
+    )
+    .category(composition_c.category);
+
+    if let Some(setting) = composition_c.setting {
+        builder = builder.setting(setting);
+    }
+    if let Some(template_id) = composition_c.template_id {
+        builder = builder.template_id(template_id);
+    }
+    if let Some(start_time) = composition_c.start_time {
+        builder = builder.start_time(start_time);
+    }
+    for entry in composition_c.content {
+        builder = builder.add_entry(entry);
+    }
+
+    let mut composition = builder.build();
+    composition.template_id = templates.validate(&composition)
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?
+        .map(|template| template.template_id.clone());
+
+    Ok(composition)
+}
+
+/// Latest version of every composition of the patient's EHR, in creation order,
+/// in the format of the Accept header
+async fn list_compositions(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - list_compositions - {id}", "HANDLER");
+
+    authorized_patient(&state, &ctx, &id).await?;
+    let accepted = accepted_format(&headers)?;
+    let ehr = state.mm.get_ehr(&id)
+        .await
+        .map_err(|e| Error::Model(e))?;
+
+    let documents = ehr.latest_compositions()
+        .iter()
+        .map(|composition| composition_document(&state.templates, accepted, composition))
+        .collect::<Result<Vec<Value>>>()?;
+
+    Ok(negotiated(accepted, Value::Array(documents)))
+}
+
+/// One composition: `:uid` is a versioned object id (latest version) or a version uid
+///
+/// Served in the format of the Accept header: the internal layout, openEHR
+/// canonical JSON, FLAT or STRUCTURED (the last two need the composition's template).
+async fn get_composition(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path((id, uid)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    println!("->> {:<12} - get_composition - {id}/{uid}", "HANDLER");
+
+    authorized_patient(&state, &ctx, &id).await?;
+    let accepted = accepted_format(&headers)?;
+    let composition = state.mm.get_composition(&id, &uid)
+        .await
+        .map_err(|e| Error::Model(e))?;
+
+    Ok(negotiated(accepted, composition_document(&state.templates, accepted, &composition)?))
+}
+
+/// Revision history of a composition (audit of every version, oldest first)
+async fn composition_versions(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path((id, uid)): Path<(String, String)>,
+) -> Result<Json<Vec<RevisionHistoryItem>>> {
+    println!("->> {:<12} - composition_versions - {id}/{uid}", "HANDLER");
+
+    authorized_patient(&state, &ctx, &id).await?;
+    let ehr = state.mm.get_ehr(&id)
+        .await
+        .map_err(|e| Error::Model(e))?;
+    let versioned = ehr.versioned_composition(&uid)
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?;
+
+    Ok(Json(versioned.revision_history()))
+}
+
+/// Every commit to the patient's EHR, oldest first
+async fn list_contributions(
+    State(state): State<PatientState>,
+    ctx: Ctx,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<Contribution>>> {
+    println!("->> {:<12} - list_contributions - {id}", "HANDLER");
+
+    authorized_patient(&state, &ctx, &id).await?;
+    let ehr = state.mm.get_ehr(&id)
+        .await
+        .map_err(|e| Error::Model(e))?;
+
+    Ok(Json(ehr.contributions))
+}
+
+// ==================== Formats ====================
+
+/// Format the client accepts (internal layout without an Accept header)
+fn accepted_format(headers: &HeaderMap) -> Result<CompositionFormat> {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return Ok(CompositionFormat::Internal);
+    };
+    CompositionFormat::negotiate(accept).ok_or_else(|| Error::NotAcceptable(accept.to_string()))
+}
+
+/// Format of the request body (internal layout without a Content-Type)
+fn content_format(headers: &HeaderMap) -> Result<CompositionFormat> {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return Ok(CompositionFormat::Internal);
+    };
+    CompositionFormat::from_media_type(content_type).ok_or_else(|| Error::UnsupportedMediaType(content_type.to_string()))
+}
+
+/// Request body as a composition to commit
+fn composition_from_document(
+    templates: &TemplateRegistry,
+    content_format: CompositionFormat,
+    template_id: Option<String>,
+    document: Value,
+) -> Result<CompositionForCreate> {
+    let mut composition_c = match content_format {
+        CompositionFormat::Internal => serde_json::from_value::<CompositionForCreate>(document)
+            .map_err(|e| Error::Model(crate::model::Error::InvalidComposition(e.to_string())))?,
+        content_format => format::import(&document, content_format, template_id.as_deref(), templates)
+            .map(CompositionForCreate::from)
+            .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))?,
+    };
+
+    if composition_c.template_id.is_none() {
+        composition_c.template_id = template_id;
+    }
+    Ok(composition_c)
+}
+
+fn composition_document(templates: &TemplateRegistry, accepted: CompositionFormat, composition: &Composition) -> Result<Value> {
+    format::export(composition, accepted, templates)
+        .map_err(|e| Error::Model(crate::model::Error::Ehr(e)))
+}
+
+fn commit_response(
+    templates: &TemplateRegistry,
+    accepted: CompositionFormat,
+    composition: Composition,
+    contribution: Contribution,
+) -> Result<impl IntoResponse> {
+    let composition = composition_document(templates, accepted, &composition)?;
+    let response = serde_json::to_value(CommitResponse { composition, contribution })
+        .map_err(|e| Error::Model(crate::model::Error::SerializationError(e.to_string())))?;
+
+    Ok(negotiated(accepted, response))
+}
+
+/// JSON body labelled with the media type of its format
+fn negotiated(format: CompositionFormat, document: Value) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, format.media_type())], Json(document))
+}
+
+// ==================== Request Structures ====================
+
+#[derive(Debug, Deserialize)]
+pub struct FormatParams {
+    /// Template of a FLAT or STRUCTURED body
+    pub template_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompositionForCreate {
+    pub archetype_id: String,
+    /// Template to validate against (the only template for `archetype_id` when omitted)
+    pub template_id: Option<String>,
+    pub name: String,
+    /// Event (default), persistent or episode
+    #[serde(default = "default_category")]
+    pub category: CompositionCategory,
+    /// Care setting (primary medical care when omitted)
+    pub setting: Option<DvCodedText>,
+    /// Composition time (now when omitted)
+    pub start_time: Option<DvDateTime>,
+    pub content: Vec<Entry>,
+    /// Reason for the commit (contribution audit)
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompositionForUpdate {
+    /// Latest version uid the change is based on
+    pub preceding_version_uid: String,
+    #[serde(flatten)]
+    pub composition: CompositionForCreate,
+}
+
+fn default_category() -> CompositionCategory {
+    CompositionCategory::Event
+}
+
+/// An imported composition, to be stamped and committed like any other
+impl From<Composition> for CompositionForCreate {
+    fn from(composition: Composition) -> Self {
+        Self {
+            archetype_id: composition.archetype_id,
+            template_id: composition.template_id,
+            name: composition.name.value,
+            category: composition.category,
+            setting: Some(composition.context.setting),
+            start_time: Some(composition.context.start_time),
+            content: composition.content,
+            description: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitResponse {
+    /// The stored composition, in the accepted format
+    pub composition: Value,
+    pub contribution: Contribution,
+}
@@ -0,0 +1,61 @@
+use crate::ctx::Ctx;
+use crate::ehr::{TemplateRegistry, WebTemplate};
+use crate::web::{Error, Result};
+use axum::Json;
+use axum::extract::{State, Path};
+use axum::Router;
+use axum::routing::get;
+use serde::Serialize;
+
+pub fn routes(templates: TemplateRegistry) -> Router {
+    Router::new()
+        .route("/templates", get(list_templates))
+        .route("/templates/:template_id", get(get_template))
+        .with_state(templates)
+}
+
+// ==================== Templates ====================
+
+/// Operational templates compositions are validated against
+async fn list_templates(
+    State(templates): State<TemplateRegistry>,
+    _ctx: Ctx,
+) -> Result<Json<Vec<TemplateSummary>>> {
+    println!("->> {:<12} - list_templates", "HANDLER");
+
+    Ok(Json(templates.list()
+        .into_iter()
+        .map(|template| TemplateSummary {
+            template_id: template.template_id.clone(),
+            name: template.tree.name.clone(),
+            archetype_id: template.tree.node_id.clone(),
+            version: template.version.clone(),
+        })
+        .collect()))
+}
+
+/// One template as web template JSON
+async fn get_template(
+    State(templates): State<TemplateRegistry>,
+    _ctx: Ctx,
+    Path(template_id): Path<String>,
+) -> Result<Json<WebTemplate>> {
+    println!("->> {:<12} - get_template - {template_id}", "HANDLER");
+
+    let Some(template) = templates.get(&template_id) else {
+        return Err(Error::Model(crate::model::Error::Ehr(crate::ehr::Error::TemplateNotFound(template_id))));
+    };
+
+    Ok(Json(template.clone()))
+}
+
+// ==================== Response Structures ====================
+
+#[derive(Debug, Serialize)]
+pub struct TemplateSummary {
+    pub template_id: String,
+    pub name: Option<String>,
+    /// Composition archetype the template constrains
+    pub archetype_id: Option<String>,
+    pub version: Option<String>,
+}
@@ -0,0 +1,16 @@
+use crate::auth::TokenManager;
+use axum::{Json, Router, routing::get, extract::State};
+use serde_json::Value;
+
+pub fn routes(token_manager: TokenManager) -> Router {
+    Router::new()
+        .route("/.well-known/jwks.json", get(jwks))
+        .with_state(token_manager)
+}
+
+/// Gateway public key(s) for offline verification of EdDSA access tokens
+async fn jwks(State(token_manager): State<TokenManager>) -> Json<Value> {
+    println!("->> {:<12} - jwks", "HANDLER");
+
+    Json(token_manager.gateway_key().jwks())
+}